@@ -0,0 +1,9 @@
+use sqlparser::{dialect::GenericDialect, parser::Parser};
+fn main() {
+    for sql in std::env::args().skip(1) {
+        match Parser::parse_sql(&GenericDialect {}, &sql) {
+            Ok(s) => println!("{:?}\n", s),
+            Err(e) => println!("ERR {}\n", e),
+        }
+    }
+}
@@ -0,0 +1,17 @@
+use std::rc::Rc;
+use vektur::{datasource::csv::CsvDataSource, logical_plan::plan::Catalog, physical_plan::planner::create_physical_plan, sql_support::sql::{parse_sql, sql_to_logical_plan}};
+fn main() {
+    let mut catalog = Catalog::new();
+    let args: Vec<String> = std::env::args().collect();
+    catalog.register_table("t".into(), Rc::new(CsvDataSource::new(args[1].clone()).unwrap()));
+    for sql in &args[2..] {
+        let stmts = match parse_sql(sql) { Ok(s) => s, Err(vektur::errors::LexerError::InvalidToken { message }) => { println!("{}", message); continue } };
+        if let vektur::sql_support::sql::Statement::CreateTable(_) = &stmts[0] { println!("{:?}", vektur::sql_support::sql::create_external_table(&stmts[0], &mut catalog)); continue }
+        let plan = match sql_to_logical_plan(&stmts[0], &catalog) { Ok(p) => p, Err(e) => { println!("{:?}", e); continue } };
+        let plan = match vektur::optimizer::Optimizer::new().optimize(&plan, &catalog) { Ok(p) => p, Err(e) => { println!("{:?}", e); continue } };
+        if std::env::var("PLAN").is_ok() { println!("{}", plan); }
+        let phys = match create_physical_plan(&plan, &catalog, &vektur::SessionConfig::new().with_batch_size(std::env::var("BATCH").ok().and_then(|b| b.parse().ok()).unwrap_or(8192))) { Ok(p) => p, Err(e) => { println!("{:?}", e); continue } };
+        println!("{:?}", phys.schema().fields.iter().map(|f| (&f.name, f.field_type)).collect::<Vec<_>>());
+        for b in phys.execute() { match b { Ok(b) => { let n = b.columns.first().map_or(0, |c| c.values.len()); if std::env::var("SIZES").is_ok() { println!("-- batch of {}", n); continue } for r in 0..n { println!("{:?}", b.columns.iter().map(|c| &c.values[r]).collect::<Vec<_>>()); } } Err(e) => println!("{:?}", e) } }
+    }
+}
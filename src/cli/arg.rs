@@ -1,14 +1,35 @@
 use clap::Parser;
 
+use crate::writers::OutputFormat;
+
 /// Cli Arguments to test the query engine
 #[derive(Parser, Debug)]
 #[command(version, about, long_about = None)]
 pub struct Args {
-    /// Path to the file of the data source
+    /// Path to the file of the data source, or `-` for CSV on stdin, which is
+    /// also read when no file is given
     #[arg(short, long)]
-    pub file_path: String,
+    pub file_path: Option<String>,
 
-    /// Path to the file containing the SQL query
+    /// Name of the table the data source is registered as. Defaults to the
+    /// file name without its extensions, or `stdin`
     #[arg(short, long)]
-    pub query_path: String,
+    pub table_name: Option<String>,
+
+    /// SQL query to run
+    #[arg(short, long, conflicts_with = "query_path")]
+    pub query: Option<String>,
+
+    /// Path to the file containing the SQL query
+    #[arg(long)]
+    pub query_path: Option<String>,
+
+    /// Path to a JSON catalog file. The tables and views it defines are loaded
+    /// at startup, and CREATE and DROP statements save their changes to it
+    #[arg(long)]
+    pub catalog: Option<String>,
+
+    /// Output format: table, markdown, csv, tsv, json or ndjson
+    #[arg(long, default_value_t = OutputFormat::Table)]
+    pub format: OutputFormat,
 }
@@ -0,0 +1,31 @@
+/// Settings that apply to every query run in a session.
+#[derive(Debug, Clone)]
+pub struct SessionConfig {
+    /// How many rows operators aim to put in each batch they produce. Sources
+    /// fill batches up to this size, and small batches left by selective
+    /// filters are merged back up to it. Always at least one.
+    batch_size: usize,
+}
+
+impl Default for SessionConfig {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SessionConfig {
+    pub fn new() -> Self {
+        Self { batch_size: 8192 }
+    }
+
+    /// Sets the target batch size. A size of zero is treated as one row per batch.
+    pub fn with_batch_size(mut self, batch_size: usize) -> Self {
+        self.batch_size = batch_size.max(1);
+        self
+    }
+
+    /// The target batch size, which is never zero.
+    pub fn batch_size(&self) -> usize {
+        self.batch_size
+    }
+}
@@ -0,0 +1,158 @@
+use std::{fs::File, io::{BufRead, BufReader, Read}, path::Path};
+
+use crate::errors::QueryError;
+
+/// How a data file is compressed. Compressed files are decompressed while they
+/// are read, so they never have to be unpacked on disk.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FileCompression {
+    Uncompressed,
+    Gzip,
+    Zstd,
+    Bzip2,
+}
+
+impl FileCompression {
+    /// The compression named by the last extension of `path`, such as `.gz` in `events.csv.gz`.
+    pub fn from_extension(path: &Path) -> Option<Self> {
+        let extension = path.extension()?.to_string_lossy().to_lowercase();
+        match extension.as_str() {
+            "gz" | "gzip" => Some(FileCompression::Gzip),
+            "zst" | "zstd" => Some(FileCompression::Zstd),
+            "bz2" | "bzip2" => Some(FileCompression::Bzip2),
+            _ => None,
+        }
+    }
+
+    /// The compression whose magic number `bytes` starts with.
+    pub fn from_magic_bytes(bytes: &[u8]) -> Self {
+        match bytes {
+            [0x1f, 0x8b, ..] => FileCompression::Gzip,
+            [0x28, 0xb5, 0x2f, 0xfd, ..] => FileCompression::Zstd,
+            [b'B', b'Z', b'h', ..] => FileCompression::Bzip2,
+            _ => FileCompression::Uncompressed,
+        }
+    }
+}
+
+/// The extension of a data file once any compression extension is removed:
+/// `csv` for both `events.csv` and `events.csv.gz`.
+pub fn data_extension(path: &Path) -> Option<String> {
+    let path = match FileCompression::from_extension(path) {
+        Some(_) => Path::new(path.file_stem()?),
+        None => path,
+    };
+    path.extension().map(|extension| extension.to_string_lossy().to_lowercase())
+}
+
+/// Opens a file for reading, decompressing it when its extension or its first
+/// bytes show that it is compressed.
+pub fn open_file(path: &Path) -> Result<Box<dyn BufRead>, QueryError> {
+    let file = File::open(path).map_err(|e| QueryError::DataSourceError {
+        message: format!("Failed to open file: {}", e),
+    })?;
+    decompress(BufReader::new(file), FileCompression::from_extension(path))
+}
+
+/// Reads a stream such as stdin, decompressing it when its first bytes show
+/// that it is compressed.
+pub fn open_reader(reader: Box<dyn Read>) -> Result<Box<dyn BufRead>, QueryError> {
+    decompress(BufReader::new(reader), None)
+}
+
+/// Wraps `reader` in a decoder for `compression`, or for the compression its
+/// first bytes show when `compression` is `None`.
+fn decompress<R: Read + 'static>(mut reader: BufReader<R>, compression: Option<FileCompression>) -> Result<Box<dyn BufRead>, QueryError> {
+    let read_error = |e: std::io::Error| QueryError::DataSourceError {
+        message: format!("Failed to read input: {}", e),
+    };
+    let compression = match compression {
+        Some(compression) => compression,
+        None => FileCompression::from_magic_bytes(reader.fill_buf().map_err(read_error)?),
+    };
+    Ok(match compression {
+        FileCompression::Uncompressed => Box::new(reader),
+        FileCompression::Gzip => decompressed(flate2::bufread::MultiGzDecoder::new(reader)),
+        FileCompression::Zstd => decompressed(zstd::Decoder::with_buffer(reader).map_err(read_error)?),
+        FileCompression::Bzip2 => decompressed(bzip2::bufread::MultiBzDecoder::new(reader)),
+    })
+}
+
+fn decompressed(decoder: impl Read + 'static) -> Box<dyn BufRead> {
+    Box::new(BufReader::new(decoder))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, io::{Cursor, Write}};
+
+    use crate::logical_plan::plan::Catalog;
+    use crate::test_util::{int, rows, temp_dir};
+
+    use super::*;
+
+    fn compress(text: &str, compression: FileCompression) -> Vec<u8> {
+        match compression {
+            FileCompression::Uncompressed => text.as_bytes().to_vec(),
+            FileCompression::Gzip => {
+                let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+                encoder.write_all(text.as_bytes()).unwrap();
+                encoder.finish().unwrap()
+            },
+            FileCompression::Zstd => zstd::encode_all(text.as_bytes(), 0).unwrap(),
+            FileCompression::Bzip2 => {
+                let mut encoder = bzip2::write::BzEncoder::new(Vec::new(), bzip2::Compression::default());
+                encoder.write_all(text.as_bytes()).unwrap();
+                encoder.finish().unwrap()
+            },
+        }
+    }
+
+    fn read_all(mut reader: Box<dyn BufRead>) -> String {
+        let mut text = String::new();
+        reader.read_to_string(&mut text).unwrap();
+        text
+    }
+
+    const ALL: [FileCompression; 4] = [FileCompression::Uncompressed, FileCompression::Gzip, FileCompression::Zstd, FileCompression::Bzip2];
+
+    #[test]
+    fn streams_are_recognised_by_their_first_bytes() {
+        for compression in ALL {
+            let bytes = compress("id\n1\n", compression);
+            assert_eq!(FileCompression::from_magic_bytes(&bytes), compression);
+            assert_eq!(read_all(open_reader(Box::new(Cursor::new(bytes))).unwrap()), "id\n1\n");
+        }
+        // Concatenated gzip members, as `cat a.gz b.gz` makes, read as one stream.
+        let mut bytes = compress("id\n1\n", FileCompression::Gzip);
+        bytes.extend(compress("2\n", FileCompression::Gzip));
+        assert_eq!(read_all(open_reader(Box::new(Cursor::new(bytes))).unwrap()), "id\n1\n2\n");
+    }
+
+    #[test]
+    fn extensions_name_the_compression_and_the_data_format() {
+        assert_eq!(FileCompression::from_extension(Path::new("events.csv.GZ")), Some(FileCompression::Gzip));
+        assert_eq!(FileCompression::from_extension(Path::new("events.csv")), None);
+        assert_eq!(data_extension(Path::new("events.json.zst")).as_deref(), Some("json"));
+        assert_eq!(data_extension(Path::new("events.csv")).as_deref(), Some("csv"));
+        assert_eq!(data_extension(Path::new("events.bz2")), None);
+    }
+
+    #[test]
+    fn compressed_files_are_queried_like_plain_ones() {
+        let dir = temp_dir("compression-files");
+        let mut catalog = Catalog::new();
+        for (name, compression) in [("a.csv.gz", FileCompression::Gzip), ("b.csv.bz2", FileCompression::Bzip2), ("c.csv", FileCompression::Zstd)] {
+            let path = dir.join(name);
+            fs::write(&path, compress("id,n\n1,10\n2,20\n", compression)).unwrap();
+            assert_eq!(read_all(open_file(&path).unwrap()), "id,n\n1,10\n2,20\n");
+            rows(&mut catalog, &format!("CREATE EXTERNAL TABLE t STORED AS CSV LOCATION '{}'", path.display()));
+            assert_eq!(rows(&mut catalog, "SELECT SUM(n) FROM t"), vec![vec![int(30)]], "{}", name);
+            rows(&mut catalog, "DROP TABLE t");
+        }
+        let path = dir.join("events.json.zst");
+        fs::write(&path, compress("{\"n\": 1}\n{\"n\": 2}\n", FileCompression::Zstd)).unwrap();
+        rows(&mut catalog, &format!("CREATE EXTERNAL TABLE events STORED AS JSON LOCATION '{}'", path.display()));
+        assert_eq!(rows(&mut catalog, "SELECT SUM(n) FROM events"), vec![vec![int(3)]]);
+    }
+}
@@ -1,26 +1,254 @@
-use std::{ffi::OsString, fs::File, io::{BufRead, BufReader}, iter, path::{Path, PathBuf}};
+use std::{cell::{Cell, RefCell}, fmt, io::{self, BufRead, Cursor, Read}, iter, path::{Path, PathBuf}, rc::Rc};
 
-use crate::{ColumnVector, DataSource, DataType, Field, RecordBatch, ScalarValue, Schema, errors::QueryError};
+use crate::{
+    ColumnVector, DataSource, DataType, Field, RecordBatch, ScalarValue, Schema, SessionConfig,
+    errors::QueryError,
+    logical_plan::plan::Expression,
+};
+
+use super::compression::{data_extension, open_file, open_reader};
+use super::csv_parser::CsvRecordReader;
+use super::rejected::{RejectedRow, RejectedRowsSink, RejectedRowsWriter};
+use super::FilterSupport;
+use super::filter::ColumnComparison;
+
+/// How the bytes of a CSV file are turned into text.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CsvEncoding {
+    /// UTF-8, failing on the first invalid byte sequence.
+    Utf8,
+    /// UTF-8, replacing invalid byte sequences with U+FFFD.
+    Utf8Lossy,
+    /// ISO-8859-1, where every byte is one character.
+    Latin1,
+}
+
+/// What a scan does with a record that does not fit the schema: a value of the
+/// wrong type, an empty value in a non-nullable column, or the wrong number of fields.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MalformedRowPolicy {
+    /// Stop the scan with the error.
+    FailFast,
+    /// Leave the record out and carry on.
+    Skip,
+    /// Keep the record with NULL in every field that could not be read. Missing
+    /// fields are NULL and extra fields are dropped. Records that would need a
+    /// NULL in a non-nullable column are skipped instead.
+    SetToNull,
+}
+
+/// Options for reading a CSV file, set with chained builder calls:
+///
+/// ```ignore
+/// let options = CsvReadOptions::new().delimiter('\t').null_value("NA").file_extension("tsv");
+/// ```
+#[derive(Debug, Clone)]
+pub struct CsvReadOptions {
+    pub delimiter: char,
+    /// Whether the first record holds the column names. Without a header the
+    /// columns are named `column_1`, `column_2` and so on.
+    pub has_header: bool,
+    pub quote: char,
+    /// A character that makes the next character in a quoted field literal, such
+    /// as `\`. Quotes can always be escaped by doubling them.
+    pub escape: Option<char>,
+    /// Field values read as NULL. Empty fields are always NULL.
+    pub null_values: Vec<String>,
+    /// Lines starting with this prefix are skipped.
+    pub comment: Option<String>,
+    /// Number of lines to skip before the header or the first record.
+    pub skip_rows: usize,
+    pub encoding: CsvEncoding,
+    /// Accepted file extensions, compared case-insensitively. Empty accepts any file.
+    pub file_extensions: Vec<String>,
+    /// The schema to read the file with. When set, no inference is done.
+    pub schema: Option<Schema>,
+    /// How many records schema inference looks at. `None` reads the whole file.
+    pub schema_infer_max_records: Option<usize>,
+    pub malformed_row_policy: MalformedRowPolicy,
+    /// Receives every malformed record unless the policy is `FailFast`.
+    pub rejected_rows: Option<RejectedRowsSink>,
+}
+
+impl Default for CsvReadOptions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl CsvReadOptions {
+    pub fn new() -> Self {
+        Self {
+            delimiter: ',',
+            has_header: true,
+            quote: '"',
+            escape: None,
+            null_values: Vec::new(),
+            comment: None,
+            skip_rows: 0,
+            encoding: CsvEncoding::Utf8,
+            file_extensions: vec!["csv".to_string()],
+            schema: None,
+            schema_infer_max_records: Some(100),
+            malformed_row_policy: MalformedRowPolicy::FailFast,
+            rejected_rows: None,
+        }
+    }
+
+    pub fn delimiter(self, delimiter: char) -> Self {
+        Self { delimiter, ..self }
+    }
+
+    pub fn has_header(self, has_header: bool) -> Self {
+        Self { has_header, ..self }
+    }
+
+    pub fn quote(self, quote: char) -> Self {
+        Self { quote, ..self }
+    }
+
+    pub fn escape(self, escape: char) -> Self {
+        Self { escape: Some(escape), ..self }
+    }
+
+    /// Adds a value that is read as NULL.
+    pub fn null_value(mut self, value: &str) -> Self {
+        self.null_values.push(value.to_string());
+        self
+    }
+
+    pub fn comment(self, prefix: &str) -> Self {
+        Self { comment: Some(prefix.to_string()), ..self }
+    }
+
+    pub fn skip_rows(self, skip_rows: usize) -> Self {
+        Self { skip_rows, ..self }
+    }
+
+    pub fn encoding(self, encoding: CsvEncoding) -> Self {
+        Self { encoding, ..self }
+    }
+
+    /// Adds an accepted file extension, without the leading dot.
+    pub fn file_extension(mut self, extension: &str) -> Self {
+        self.file_extensions.push(extension.trim_start_matches('.').to_string());
+        self
+    }
+
+    /// Accepts files with any extension.
+    pub fn any_file_extension(self) -> Self {
+        Self { file_extensions: Vec::new(), ..self }
+    }
+
+    pub fn schema(self, schema: Schema) -> Self {
+        Self { schema: Some(schema), ..self }
+    }
+
+    pub fn schema_infer_max_records(self, max_records: usize) -> Self {
+        Self { schema_infer_max_records: Some(max_records), ..self }
+    }
+
+    /// Infers the schema from every record in the file rather than a sample.
+    pub fn infer_schema_from_all_records(self) -> Self {
+        Self { schema_infer_max_records: None, ..self }
+    }
+
+    pub fn malformed_row_policy(self, malformed_row_policy: MalformedRowPolicy) -> Self {
+        Self { malformed_row_policy, ..self }
+    }
+
+    pub fn rejected_rows(self, sink: RejectedRowsSink) -> Self {
+        Self { rejected_rows: Some(sink), ..self }
+    }
+
+    /// Sets one option from its name and text value, as written in
+    /// `CREATE EXTERNAL TABLE ... OPTIONS (...)`. A `file_extension` of `*`
+    /// accepts files with any extension.
+    pub fn set(self, key: &str, value: &str) -> Result<Self, QueryError> {
+        let invalid = || QueryError::ValidationError {
+            message: format!("Invalid value '{}' for CSV option '{}'", value, key),
+        };
+        let single_char = || {
+            let value = match value {
+                "\\t" | "tab" => "\t",
+                _ => value,
+            };
+            let mut chars = value.chars();
+            match (chars.next(), chars.next()) {
+                (Some(c), None) => Ok(c),
+                _ => Err(invalid()),
+            }
+        };
+        Ok(match key.to_lowercase().as_str() {
+            "delimiter" | "sep" => self.delimiter(single_char()?),
+            "has_header" | "header" => self.has_header(value.parse::<bool>().map_err(|_| invalid())?),
+            "quote" => self.quote(single_char()?),
+            "escape" => self.escape(single_char()?),
+            "null" | "null_value" => self.null_value(value),
+            "comment" => self.comment(value),
+            "skip_rows" => self.skip_rows(value.parse::<usize>().map_err(|_| invalid())?),
+            "encoding" => self.encoding(match value.to_lowercase().as_str() {
+                "utf8" | "utf-8" => CsvEncoding::Utf8,
+                "utf8_lossy" => CsvEncoding::Utf8Lossy,
+                "latin1" | "iso-8859-1" => CsvEncoding::Latin1,
+                _ => return Err(invalid()),
+            }),
+            "file_extension" if value == "*" => self.any_file_extension(),
+            "file_extension" => self.file_extension(value),
+            "on_malformed_row" => self.malformed_row_policy(match value.to_lowercase().as_str() {
+                "fail" => MalformedRowPolicy::FailFast,
+                "skip" => MalformedRowPolicy::Skip,
+                "null" => MalformedRowPolicy::SetToNull,
+                _ => return Err(invalid()),
+            }),
+            "rejected_rows_file" => self.rejected_rows(RejectedRowsSink::File(PathBuf::from(value))),
+            "schema_infer_max_records" if value.eq_ignore_ascii_case("all") => self.infer_schema_from_all_records(),
+            "schema_infer_max_records" => self.schema_infer_max_records(value.parse::<usize>().map_err(|_| invalid())?),
+            _ => return Err(QueryError::ValidationError {
+                message: format!("Unknown CSV option '{}'", key),
+            }),
+        })
+    }
+
+    fn is_null(&self, value: &str) -> bool {
+        value.is_empty() || self.null_values.iter().any(|null| null == value)
+    }
+}
 
 #[derive(Debug)]
 pub struct ValidCsvPath(PathBuf);
 
 impl ValidCsvPath {
-    pub fn new(path: &str) -> Result<Self, QueryError> {
+    pub fn new(path: &str, extensions: &[String]) -> Result<Self, QueryError> {
         let path = Path::new(path);
         if !path.exists() || !path.is_file() {
             return Err(QueryError::DataSourceError { message: "File doesn't exist".into() });
         }
-        if path.extension().map(|e| e.to_ascii_lowercase()) != Some(OsString::from("csv")) {
-            return Err(QueryError::DataSourceError { message: "Not a CSV file".into() });
+        let extension = data_extension(path);
+        let allowed = extensions.is_empty()
+            || extension.is_some_and(|extension| extensions.iter().any(|e| e.to_lowercase() == extension));
+        if !allowed {
+            return Err(QueryError::DataSourceError {
+                message: format!("Not a CSV file: expected one of the extensions {:?}", extensions),
+            });
         }
         Ok(Self(path.to_path_buf()))
     }
 }
 
 struct CsvBatchIterator {
-    reader: BufReader<File>,
+    records: CsvRecordReader<Box<dyn BufRead>>,
     schema: Schema,
+    /// Indexes of the schema columns to return.
+    projection: Vec<usize>,
+    projected_schema: Schema,
+    filters: Vec<ColumnComparison>,
+    /// Indexes of every schema column that is parsed: the projection, the
+    /// filter columns and any other column a value can be malformed in.
+    parsed_columns: Vec<usize>,
+    options: CsvReadOptions,
+    rejected: Option<RejectedRowsWriter>,
+    rejected_count: Rc<Cell<usize>>,
     batch_size: usize,
     finished: bool,
     header_skipped: bool,
@@ -34,269 +262,399 @@ impl Iterator for CsvBatchIterator {
             return None;
         }
 
-        if !self.header_skipped {
-            let mut header_line = String::new();
-            if let Ok(val) = self.reader.read_line(&mut header_line) {
-                if val == 0 {
+        if !self.header_skipped && self.options.has_header {
+            match self.records.read_record() {
+                Ok(Some(_header)) => {},
+                Ok(None) => {
                     self.finished = true;
                     return None;
+                },
+                Err(e) => {
+                    self.finished = true;
+                    return Some(Err(e));
                 }
-            } else {
-                return Some(Err(QueryError::DataSourceError { 
-                    message: "Failed to read header".to_string() 
-                }));
             }
             self.header_skipped = true;
         }
 
-        let mut lines = Vec::new();
-        let mut line = String::new();
-        for _ in 0..self.batch_size {
-            line.clear();
-            if let Ok(val) = self.reader.read_line(&mut line) {
-                if val == 0 {
+        let mut records = Vec::new();
+        while records.len() < self.batch_size {
+            match self.records.read_record() {
+                Ok(Some(record)) => match self.parse_record(&record) {
+                    Ok(Some(values)) => records.push(values),
+                    Ok(None) => {},
+                    Err(e) => {
+                        self.finished = true;
+                        return Some(Err(e));
+                    }
+                },
+                Ok(None) => {
                     self.finished = true;
                     break;
-                } else {
-                    lines.push(line.clone());
+                },
+                Err(e) => {
+                    self.finished = true;
+                    return Some(Err(e));
                 }
-            } else {
-                return Some(Err(QueryError::DataSourceError { message: "Failed to read file buffer".to_string() }));
+            }
+        }
+        if records.is_empty() {
+            return None;
+        }
+
+        let mut columnar_data: Vec<Vec<ScalarValue>> = vec![Vec::with_capacity(records.len()); self.projection.len()];
+        for record in records {
+            for (column, value) in columnar_data.iter_mut().zip(record) {
+                column.push(value);
             }
         }
 
-        let columns = self.schema.fields.len();
+        let columns = columnar_data.into_iter().map(ColumnVector::new).collect::<Vec<ColumnVector>>();
 
-        let mut columnar_data: Vec<Vec<ScalarValue>> = Vec::with_capacity(columns);
+        let schema = self.projected_schema.clone();
+        Some(RecordBatch::new(schema, columns))
         
-        for _ in 0..columns {
-            columnar_data.push(vec![]);
+    }
+}
+
+impl CsvBatchIterator {
+    /// Reads a record as one value per projected column. Returns `None` when the
+    /// filters or the malformed row policy drop the record. Every column that
+    /// can hold a bad value is checked, projected or not, so the same records
+    /// count as malformed whatever the query reads.
+    fn parse_record(&mut self, record: &[String]) -> Result<Option<Vec<ScalarValue>>, QueryError> {
+        let row = self.records.record_line();
+        let policy = self.options.malformed_row_policy;
+        let fields = &self.schema.fields;
+
+        let mut problem = (record.len() != fields.len()).then_some(QueryError::FieldCountMismatch {
+            row,
+            expected: fields.len(),
+            actual: record.len(),
+        });
+        let mut values: Vec<Option<ScalarValue>> = vec![None; fields.len()];
+        if problem.is_none() || policy == MalformedRowPolicy::SetToNull {
+            for &i in &self.parsed_columns {
+                let field = &fields[i];
+                let item = record.get(i).map_or("", String::as_str);
+                let quoted = self.records.is_quoted(i);
+                values[i] = Some(parse_field(item, quoted, field, row, &self.options).unwrap_or_else(|e| {
+                    problem.get_or_insert(e);
+                    ScalarValue::null(field.field_type)
+                }));
+            }
         }
 
-        for line in lines.iter() {
-            let line = line.trim().split(",").collect::<Vec<&str>>();
-            for i in 0..columns {
-                if let Some(item) = line.get(i) {
-                    if let Some(field) = self.schema.fields.get(i) {
-
-                        match field.field_type {
-                            DataType::Int32 => {
-                                if !item.is_empty() {
-                                    if let Ok(item) = item.parse::<i32>() {
-                                        columnar_data[i].push(ScalarValue::Int32(Some(item)));
-                                    } else {
-                                        return Some(Err(QueryError::DataSourceError { message: "Item does not match the column type: Int32".to_string() }))
-                                    }
-                                } else if field.is_nullable {
-                                    columnar_data[i].push(ScalarValue::Int32(None))
-                                } else {
-                                    return Some(Err(QueryError::DataSourceError { message: "Found a value null for a nullable field".to_string() }))
-                                }
-                            },
-                            DataType::Float64 => {
-                                if !item.is_empty() {
-                                    if let Ok(item) = item.parse::<f64>() {
-                                        columnar_data[i].push(ScalarValue::Float64(Some(item)));
-                                    } else {
-                                        return Some(Err(QueryError::DataSourceError { message: "Item does not match the column type: Float64".to_string() }))
-                                    }
-                                } else if field.is_nullable {
-                                    columnar_data[i].push(ScalarValue::Float64(None))
-                                } else {
-                                    return Some(Err(QueryError::DataSourceError { message: "Found a value null for a nullable field".to_string() }))
-                                }
-                            },
-                            DataType::Bool => {
-                                if !item.is_empty() {
-                                    if let Ok(item) = item.parse::<bool>() {
-                                        columnar_data[i].push(ScalarValue::Bool(Some(item)));
-                                    } else {
-                                        return Some(Err(QueryError::DataSourceError { message: "Item does not match the column type: bool".to_string() }))
-                                    }
-                                } else if field.is_nullable {
-                                    columnar_data[i].push(ScalarValue::Bool(None))
-                                } else {
-                                    return Some(Err(QueryError::DataSourceError { message: "Found a value null for a nullable field".to_string() }))
-                                }
-                            },
-                            DataType::String => {
-                                if !item.is_empty() {
-                                    columnar_data[i].push(ScalarValue::String(Some(item.to_string())));
-                                } else if field.is_nullable {
-                                    columnar_data[i].push(ScalarValue::String(None))
-                                } else {
-                                    return Some(Err(QueryError::DataSourceError { message: "Found a value null for a nullable field".to_string() }))
-                                }
-                            }
-                        }
-                    } else {
-                        return Some(Err(QueryError::DataSourceError { message: "Data type not found in schema".to_string() }))
-                    }
-                } else {
-                    return Some(Err(QueryError::DataSourceError { message: "Data type not found in schema".to_string() }))
-                }
-            };
+        let problem = match problem {
+            None => return Ok(self.passes_filters(&values).then(|| self.take_projected(values))),
+            Some(problem) => problem,
         };
+        if policy == MalformedRowPolicy::FailFast {
+            return Err(problem);
+        }
+        if let Some(rejected) = &mut self.rejected {
+            rejected.write(RejectedRow {
+                line: row,
+                reason: rejection_reason(&problem),
+                record: self.records.record_text().to_string(),
+            })?;
+        }
+        self.rejected_count.set(self.rejected_count.get() + 1);
 
-        let columns = columnar_data.into_iter().map(move |col| {
-            ColumnVector {
-                values: col
-            }
-        })
-        .collect::<Vec<ColumnVector>>();
+        let keeps_record = policy == MalformedRowPolicy::SetToNull
+            && self.parsed_columns.iter().all(|&i| fields[i].is_nullable || values[i].as_ref().is_some_and(|v| !v.is_null()))
+            && self.passes_filters(&values);
+        Ok(keeps_record.then(|| self.take_projected(values)))
+    }
 
-        let schema = self.schema.clone();
-        Some(RecordBatch::new(schema, columns))
-        
+    /// Whether the parsed values of a record satisfy every filter.
+    fn passes_filters(&self, values: &[Option<ScalarValue>]) -> bool {
+        self.filters.iter().all(|filter| values[filter.column].as_ref().is_some_and(|value| filter.matches(value)))
+    }
+
+    fn take_projected(&self, mut values: Vec<Option<ScalarValue>>) -> Vec<ScalarValue> {
+        self.projection
+            .iter()
+            .map(|&i| values[i].take().unwrap_or_else(|| ScalarValue::null(self.schema.fields[i].field_type)))
+            .collect()
+    }
+}
+
+fn rejection_reason(problem: &QueryError) -> String {
+    match problem {
+        QueryError::InvalidValue { column_name, expected, value, .. } => {
+            format!("Invalid {:?} value '{}' in column {}", expected, value, column_name)
+        },
+        QueryError::UnexpectedNull { column_name, .. } => format!("NULL in non-nullable column {}", column_name),
+        QueryError::FieldCountMismatch { expected, actual, .. } => {
+            format!("Expected {} fields, found {}", expected, actual)
+        },
+        other => format!("{:?}", other),
+    }
+}
+
+/// Reads one field as a value of its column's type. `quoted` says whether the
+/// field was wrapped in quotes, and `row` is only used in errors.
+fn parse_field(item: &str, quoted: bool, field: &Field, row: usize, options: &CsvReadOptions) -> Result<ScalarValue, QueryError> {
+    // Only text keeps its surrounding whitespace, matching how types are inferred.
+    let item = match field.field_type {
+        DataType::String => item,
+        _ => item.trim(),
+    };
+    // A quoted empty text field is an empty string rather than NULL, which is
+    // how `CsvWriter` tells the two apart.
+    let empty_string = quoted && item.is_empty() && field.field_type == DataType::String;
+    if options.is_null(item) && !empty_string {
+        return if field.is_nullable {
+            Ok(ScalarValue::null(field.field_type))
+        } else {
+            Err(QueryError::UnexpectedNull { row, column_name: field.name.clone() })
+        };
+    }
+
+    let value = match field.field_type {
+        DataType::Int32 => item.parse::<i32>().ok().map(|v| ScalarValue::Int32(Some(v))),
+        DataType::Float64 => item.parse::<f64>().ok().map(|v| ScalarValue::Float64(Some(v))),
+        DataType::Bool => item.to_lowercase().parse::<bool>().ok().map(|v| ScalarValue::Bool(Some(v))),
+        DataType::String => Some(ScalarValue::String(Some(item.to_string()))),
+    };
+    value.ok_or_else(|| QueryError::InvalidValue {
+        row,
+        column_name: field.name.clone(),
+        expected: field.field_type,
+        value: item.to_string(),
+    })
+}
+
+/// Where a `CsvDataSource` reads its records from.
+enum CsvInput {
+    File(ValidCsvPath),
+    /// A stream that can only be read once. The first scan takes it.
+    Stream(RefCell<Option<Box<dyn BufRead>>>),
+}
+
+impl fmt::Debug for CsvInput {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CsvInput::File(path) => f.debug_tuple("File").field(path).finish(),
+            CsvInput::Stream(_) => f.write_str("Stream"),
+        }
+    }
+}
+
+impl CsvInput {
+    fn open(&self) -> Result<Box<dyn BufRead>, QueryError> {
+        match self {
+            CsvInput::File(path) => open_file(&path.0),
+            CsvInput::Stream(stream) => stream.borrow_mut().take().ok_or_else(|| QueryError::DataSourceError {
+                message: "The input stream was already read by an earlier scan".to_string(),
+            }),
+        }
+    }
+}
+
+/// Reads through a stream while keeping a copy of every byte consumed, so the
+/// records sampled for schema inference can be read again by the scan.
+struct RecordingReader<R> {
+    inner: R,
+    recorded: Vec<u8>,
+}
+
+impl<R: BufRead> Read for RecordingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read = self.inner.read(buf)?;
+        self.recorded.extend_from_slice(&buf[..read]);
+        Ok(read)
+    }
+}
+
+impl<R: BufRead> BufRead for RecordingReader<R> {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        self.inner.fill_buf()
+    }
+
+    fn consume(&mut self, amount: usize) {
+        if let Ok(buffer) = self.inner.fill_buf() {
+            self.recorded.extend_from_slice(&buffer[..amount.min(buffer.len())]);
+        }
+        self.inner.consume(amount);
     }
 }
 
 #[derive(Debug)]
 pub struct CsvDataSource {
-    file_path: ValidCsvPath,
+    input: CsvInput,
     original_schema: Schema,
+    options: CsvReadOptions,
+    rejected_count: Rc<Cell<usize>>,
 }
 
 impl CsvDataSource {
     pub fn new(file_path: String) -> Result<Self, QueryError> {
+        Self::with_options(file_path, CsvReadOptions::new())
+    }
 
-        let file_path = ValidCsvPath::new(&file_path)?;
+    pub fn with_options(file_path: String, options: CsvReadOptions) -> Result<Self, QueryError> {
 
-        let schema = Self::infer_schema(&file_path);
+        let file_path = ValidCsvPath::new(&file_path, &options.file_extensions)?;
 
-        match schema {
-            Ok(fields) => {
-                Ok(Self {
-                    original_schema: Schema::new(fields),
-                    file_path
-                })
+        let schema = match &options.schema {
+            Some(schema) => schema.clone(),
+            None => Schema::new(Self::infer_schema(&file_path, &options)?),
+        };
+
+        Ok(Self {
+            original_schema: schema,
+            input: CsvInput::File(file_path),
+            options,
+            rejected_count: Rc::new(Cell::new(0)),
+        })
+    }
+
+    /// Reads CSV from a stream such as stdin, a pipe or an in-memory buffer.
+    /// Compressed input is recognised by its first bytes. The records read to
+    /// infer the schema are kept in memory and read again by the scan, so the
+    /// stream is only read once and can only be scanned once.
+    pub fn from_reader(reader: Box<dyn Read>, options: CsvReadOptions) -> Result<Self, QueryError> {
+        let reader = open_reader(reader)?;
+        let (schema, reader) = match &options.schema {
+            Some(schema) => (schema.clone(), reader),
+            None => {
+                let mut records = CsvRecordReader::new(RecordingReader { inner: reader, recorded: Vec::new() }, &options);
+                let fields = Self::infer_schema_from_records(&mut records, &options)?;
+                let RecordingReader { inner, recorded } = records.into_inner();
+                let replayed: Box<dyn BufRead> = Box::new(Cursor::new(recorded).chain(inner));
+                (Schema::new(fields), replayed)
             },
-            Err(err) => {
-                Err(QueryError::DataSourceError { message: err })
-            }
-        }
+        };
+
+        Ok(Self {
+            original_schema: schema,
+            input: CsvInput::Stream(RefCell::new(Some(reader))),
+            options,
+            rejected_count: Rc::new(Cell::new(0)),
+        })
+    }
+
+    /// How many malformed records the latest scan skipped or patched with NULLs.
+    pub fn rejected_row_count(&self) -> usize {
+        self.rejected_count.get()
+    }
+
+    /// Infers column names from the header and column types from up to
+    /// `schema_infer_max_records` records. A column is only marked as not
+    /// nullable when inference read the whole file and found no NULL in it.
+    pub fn infer_schema(file_path: &ValidCsvPath, options: &CsvReadOptions) -> Result<Vec<Field>, QueryError> {
+        let mut records = CsvRecordReader::new(open_file(&file_path.0)?, options);
+        Self::infer_schema_from_records(&mut records, options)
     }
-    pub fn infer_schema(file_path: &ValidCsvPath) -> Result<Vec<Field>, String> {
-        if let Ok(file) = File::open(&file_path.0) {
-            let mut buf_file = BufReader::new(file);
 
-            let mut lines = Vec::new();
-            let mut line = String::new();
-            for _ in 0..101 {
-                line.clear();
-                if let Ok(val) = buf_file.read_line(&mut line) {
-                    if val == 0 {
+    fn infer_schema_from_records<R: BufRead>(
+        records: &mut CsvRecordReader<R>,
+        options: &CsvReadOptions,
+    ) -> Result<Vec<Field>, QueryError> {
+        let first = records.read_record()?.ok_or_else(|| QueryError::DataSourceError {
+            message: "Failed to infer schema: the file is empty".to_string(),
+        })?;
+        let (names, mut pending) = if options.has_header {
+            (first, Vec::new())
+        } else {
+            let names = (1..=first.len()).map(|i| format!("column_{}", i)).collect();
+            (names, vec![first])
+        };
+        let mut columns = vec![ColumnStats::default(); names.len()];
+        let mut seen = 0;
+        let mut read_whole_file = false;
+        loop {
+            if options.schema_infer_max_records.is_some_and(|max| seen >= max) {
+                break;
+            }
+            let record = match pending.pop() {
+                Some(record) => record,
+                None => match records.read_record()? {
+                    Some(record) => record,
+                    None => {
+                        read_whole_file = true;
                         break;
-                    } else {
-                        lines.push(line.clone());
                     }
+                },
+            };
+            for (i, column) in columns.iter_mut().enumerate() {
+                let item = record.get(i).map_or("", |item| item.trim());
+                if item.is_empty() && records.is_quoted(i) {
+                    column.has_empty_string = true;
                 } else {
-                    return Err("Failed to read file buffer".to_string())
+                    column.observe(item, options);
                 }
             }
+            seen += 1;
+        }
 
-            let first_line = lines.get(0);
-            match first_line {
-                Some(line) => {
-                    if let Some(_second_line) = lines.get(1) {
-                        let types = Self::detect_types(&lines, line.trim().split(",").collect::<Vec<&str>>().len());
-                        Ok(line.trim().split(",").enumerate().map(move |(i, header)| {
-                            Field {
-                                name: header.trim().to_string(),
-                                field_type: types[i],
-                                is_nullable: true
-                            }
-                        }).collect::<Vec<Field>>())
-                    } else {
-                        Ok(line.trim().split(",").map(move |header| {
-                            Field {
-                                name: header.trim().to_string(),
-                                field_type: DataType::String,
-                                is_nullable: true
-                            }
-                        }).collect::<Vec<Field>>())
-                        
-                    }
-                },
-                None => {
-                    Err("Failed to infer schema".to_string())
-                }
+        Ok(names.iter().zip(&columns).map(|(name, column)| {
+            Field {
+                name: name.trim().to_string(),
+                field_type: column.data_type(),
+                is_nullable: column.is_nullable() || !read_whole_file,
             }
+        }).collect::<Vec<Field>>())
+    }
+}
 
-        } else {
-            Err("Failed to open file".to_string())
-        }
-
+/// What schema inference has learned about one column from the values seen so far.
+#[derive(Debug, Clone)]
+struct ColumnStats {
+    has_value: bool,
+    has_null: bool,
+    /// Whether a quoted empty field was seen, which is an empty string in a
+    /// text column and NULL in any other.
+    has_empty_string: bool,
+    is_int: bool,
+    is_float: bool,
+    is_bool: bool,
+}
 
+impl Default for ColumnStats {
+    fn default() -> Self {
+        Self {
+            has_value: false,
+            has_null: false,
+            has_empty_string: false,
+            is_int: true,
+            is_float: true,
+            is_bool: true,
+        }
     }
+}
 
-    fn detect_types(lines: &Vec<String>, columns: usize) -> Vec<DataType> {
-        let mut types: Vec<DataType> = Vec::with_capacity(columns);
-        let mut columnar_data: Vec<Vec<&str>> = Vec::with_capacity(columns);
-        for _ in 0..columns {
-            types.push(DataType::String);
-            columnar_data.push(vec![]);
+impl ColumnStats {
+    fn observe(&mut self, item: &str, options: &CsvReadOptions) {
+        if options.is_null(item) {
+            self.has_null = true;
+            return;
         }
-        for line in lines.iter().skip(1).take(100) {
-            let line = line.trim().split(",").collect::<Vec<&str>>();
-            for i in 0..columns {
-                if let Some(item) = line.get(i) {
-                    columnar_data[i].push(item.trim());
-                } else {
-                    columnar_data[i].push("");
-                }
-            };
-        };
-        for (index, data) in columnar_data.iter().enumerate() {
-
-            let is_all_empty = data.iter().all(|data| {
-                data.is_empty()
-            });
-
-            let is_float = data.iter().all(|data| {
-                if data.is_empty() { 
-                    return true;
-                };
-                let number = data.parse::<f64>();
-                match number {
-                    Ok(_number) => true,
-                    Err(_error) => false,
-                }
-            });
-            let is_int = data.iter().all(|data| {
-                if data.is_empty() { 
-                    return true;
-                };
-                let number = data.parse::<i32>();
-                match number {
-                    Ok(_number) => true,
-                    Err(_error) => false,
-                }
-            });
+        self.has_value = true;
+        self.is_int = self.is_int && item.parse::<i32>().is_ok();
+        self.is_float = self.is_float && item.parse::<f64>().is_ok();
+        self.is_bool = self.is_bool && item.to_lowercase().parse::<bool>().is_ok();
+    }
 
-            let is_boolean = data.iter().all(|data| {
-                if data.is_empty() {
-                    return true;
-                };
-                let boolean = data.to_lowercase().parse::<bool>();
-                match boolean {
-                    Ok(_boolean) => true,
-                    Err(_error) => false,
-                }
-            });
-                
-            if !is_all_empty {
-                if is_int {
-                    types[index] = DataType::Int32;
-                } else if is_float {
-                    types[index] = DataType::Float64;
-                } else if is_boolean {
-                    types[index] = DataType::Bool;
-                }
-            }
+    fn is_nullable(&self) -> bool {
+        self.has_null || (self.has_empty_string && self.data_type() != DataType::String)
+    }
 
-        };
-        types
+    /// Columns without any non-null value are read as text.
+    fn data_type(&self) -> DataType {
+        if !self.has_value {
+            DataType::String
+        } else if self.is_int {
+            DataType::Int32
+        } else if self.is_float {
+            DataType::Float64
+        } else if self.is_bool {
+            DataType::Bool
+        } else {
+            DataType::String
+        }
     }
 }
 
@@ -304,21 +662,275 @@ impl DataSource for CsvDataSource {
     fn schema(&self) -> &Schema {
         &self.original_schema
     }
-    fn scan(&self) -> Box<dyn Iterator<Item = Result<RecordBatch, QueryError>>> {
-        match File::open(&self.file_path.0) {
-            Ok(file) => {
-                let reader = BufReader::new(file);
+
+    /// Comparisons between a column and a literal are evaluated exactly while parsing.
+    fn supports_filter(&self, filter: &Expression) -> FilterSupport {
+        match ColumnComparison::from_expression(filter, &self.original_schema) {
+            Some(_) => FilterSupport::Exact,
+            None => FilterSupport::Unsupported,
+        }
+    }
+
+    fn scan(
+        &self,
+        config: &SessionConfig,
+        projection: Option<&[usize]>,
+        filters: &[Expression],
+    ) -> Box<dyn Iterator<Item = Result<RecordBatch, QueryError>>> {
+        let rejected = match self.options.rejected_rows.as_ref().map(RejectedRowsSink::open).transpose() {
+            Ok(rejected) => rejected,
+            Err(e) => return Box::new(iter::once(Err(e))),
+        };
+        self.rejected_count.set(0);
+        let projection = projection.map_or_else(|| (0..self.original_schema.fields.len()).collect(), <[usize]>::to_vec);
+        let filters: Vec<ColumnComparison> = filters
+            .iter()
+            .filter_map(|filter| ColumnComparison::from_expression(filter, &self.original_schema))
+            .collect();
+        // Any text fits a nullable text column, so only those can go unread
+        // when no projection or filter needs them.
+        let mut keep: Vec<bool> = self
+            .original_schema
+            .fields
+            .iter()
+            .map(|field| field.field_type != DataType::String || !field.is_nullable)
+            .collect();
+        for &index in projection.iter().chain(filters.iter().map(|filter| &filter.column)) {
+            keep[index] = true;
+        }
+        let parsed_columns = (0..keep.len()).filter(|&i| keep[i]).collect();
+        match self.input.open() {
+            Ok(reader) => {
+                let records = CsvRecordReader::new(reader, &self.options).with_projection(keep);
                 Box::new(CsvBatchIterator {
-                    batch_size: 16,
+                    rejected,
+                    rejected_count: self.rejected_count.clone(),
+                    batch_size: config.batch_size(),
                     finished: false,
-                    reader: reader,
+                    records,
+                    projected_schema: self.original_schema.project(&projection),
+                    projection,
+                    filters,
+                    parsed_columns,
                     schema: self.original_schema.clone(),
+                    options: self.options.clone(),
                     header_skipped: false,
                 })
             },
-            Err(e) => {
-                Box::new(iter::once(Err(QueryError::DataSourceError { message: format!("Failed to open file: {:?}", e) })))
-            }
+            Err(e) => Box::new(iter::once(Err(e))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use crate::logical_plan::plan::Catalog;
+    use crate::test_util::{int, query, rows, temp_dir, text};
+
+    use super::*;
+
+    /// A catalog with table `t` over a file holding `contents`, created with
+    /// the given column list and options, either of which may be empty.
+    fn external_table(test: &str, contents: &str, columns: &str, options: &str) -> Result<Catalog, QueryError> {
+        let path = temp_dir(test).join("t.csv");
+        fs::write(&path, contents).unwrap();
+        let mut catalog = Catalog::new();
+        query(&mut catalog, &format!("CREATE EXTERNAL TABLE t {} STORED AS CSV LOCATION '{}' {}", columns, path.display(), options))?;
+        Ok(catalog)
+    }
+
+    /// A catalog with table `t (id, val, note)` over a file whose third record
+    /// has a bad `val`, read with the given malformed row policy.
+    fn catalog_with_bad_row(test: &str, policy: &str) -> Catalog {
+        external_table(
+            test,
+            "id,val,note\n1,10,a\n2,20,b\n3,oops,c\n4,40,d\n",
+            "(id INT NOT NULL, val INT NOT NULL, note TEXT)",
+            &format!("OPTIONS (on_malformed_row = '{}', rejected_rows_table = 'rejected')", policy),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn columns_a_query_does_not_read_are_still_checked() {
+        let mut catalog = catalog_with_bad_row("csv-fail", "fail");
+        for sql in ["SELECT COUNT(*) FROM t", "SELECT SUM(val) FROM t", "SELECT note FROM t WHERE id = 1"] {
+            assert!(
+                matches!(query(&mut catalog, sql), Err(QueryError::InvalidValue { row: 4, ref column_name, .. }) if column_name == "val"),
+                "{} did not fail on the bad row",
+                sql,
+            );
+        }
+    }
+
+    #[test]
+    fn skipped_rows_are_the_same_whatever_the_query_reads() {
+        let mut catalog = catalog_with_bad_row("csv-skip", "skip");
+        assert_eq!(rows(&mut catalog, "SELECT COUNT(*) FROM t"), vec![vec![int(3)]]);
+        assert_eq!(rows(&mut catalog, "SELECT SUM(val) FROM t"), vec![vec![int(70)]]);
+        assert_eq!(rows(&mut catalog, "SELECT note FROM t WHERE id = 3"), Vec::<Vec<ScalarValue>>::new());
+        assert_eq!(rows(&mut catalog, "SELECT note FROM t"), vec![vec![text("a")], vec![text("b")], vec![text("d")]]);
+        assert_eq!(
+            rows(&mut catalog, "SELECT line, reason, record FROM rejected"),
+            vec![vec![int(4), text("Invalid Int32 value 'oops' in column val"), text("3,oops,c")]],
+        );
+    }
+
+    #[test]
+    fn nulled_rows_are_dropped_when_the_column_is_not_nullable() {
+        let mut catalog = catalog_with_bad_row("csv-null", "null");
+        assert_eq!(rows(&mut catalog, "SELECT COUNT(*) FROM t"), vec![vec![int(3)]]);
+        assert_eq!(rows(&mut catalog, "SELECT id FROM t WHERE id > 2"), vec![vec![int(4)]]);
+        assert_eq!(rows(&mut catalog, "SELECT COUNT(*) FROM rejected"), vec![vec![int(1)]]);
+    }
+
+    #[test]
+    fn nulled_rows_are_kept_when_the_column_is_nullable() {
+        let mut catalog = external_table("csv-nullable", "id,val\n1,10\n2,oops\n", "(id INT, val INT)", "OPTIONS (on_malformed_row = 'null')").unwrap();
+        assert_eq!(rows(&mut catalog, "SELECT id, val FROM t"), vec![vec![int(1), int(10)], vec![int(2), ScalarValue::Int32(None)]]);
+        assert_eq!(rows(&mut catalog, "SELECT id FROM t"), vec![vec![int(1)], vec![int(2)]]);
+    }
+
+    #[test]
+    fn options_change_how_the_file_is_split() {
+        let contents = "exported by a tool\na;b\n# a note\n1;NA\n\n2;\"x;y\"\n";
+        let options = "OPTIONS (skip_rows = '1', delimiter = ';', comment = '#', null_value = 'NA')";
+        let mut catalog = external_table("csv-options", contents, "", options).unwrap();
+        assert_eq!(rows(&mut catalog, "SELECT a, b FROM t"), vec![vec![int(1), ScalarValue::String(None)], vec![int(2), text("x;y")]]);
+
+        let options = "OPTIONS (header = 'false', skip_rows = '3', sep = ';')";
+        let mut catalog = external_table("csv-no-header", contents, "", options).unwrap();
+        assert_eq!(rows(&mut catalog, "SELECT column_1, column_2 FROM t"), vec![vec![int(1), text("NA")], vec![int(2), text("x;y")]]);
+    }
+
+    #[test]
+    fn unknown_options_and_bad_values_are_rejected() {
+        let error = |options: &str| match external_table("csv-bad-option", "a\n1\n", "", options) {
+            Err(QueryError::ValidationError { message }) => message,
+            other => panic!("{} was accepted: {:?}", options, other.map(|_| ())),
+        };
+        assert_eq!(error("OPTIONS (delimiter = ';;')"), "Invalid value ';;' for CSV option 'delimiter'");
+        assert_eq!(error("OPTIONS (has_header = 'maybe')"), "Invalid value 'maybe' for CSV option 'has_header'");
+        assert_eq!(error("OPTIONS (colour = 'red')"), "Unknown CSV option 'colour'");
+    }
+
+    /// The name, type and nullability of each column of table `t`.
+    fn describe(catalog: &mut Catalog) -> Vec<(String, String, bool)> {
+        rows(catalog, "DESCRIBE t")
+            .into_iter()
+            .map(|row| match row.as_slice() {
+                [ScalarValue::String(Some(name)), ScalarValue::String(Some(field_type)), ScalarValue::Bool(Some(nullable))] => {
+                    (name.clone(), field_type.clone(), *nullable)
+                },
+                other => panic!("unexpected DESCRIBE row {:?}", other),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn inference_reads_types_and_nullability_from_the_records() {
+        let contents = "i,f,b,s,e\n1,1.5,true,x,\n2,2,FALSE,,\n";
+        let column = |name: &str, field_type: &str, nullable| (name.to_string(), field_type.to_string(), nullable);
+        let mut catalog = external_table("csv-infer", contents, "", "").unwrap();
+        assert_eq!(describe(&mut catalog), vec![
+            column("i", "Int32", false),
+            column("f", "Float64", false),
+            column("b", "Bool", false),
+            column("s", "String", true),
+            column("e", "String", true),
+        ]);
+        // Records past the limit may hold NULLs, so every column is nullable.
+        let mut catalog = external_table("csv-infer-limit", contents, "", "OPTIONS (schema_infer_max_records = '1')").unwrap();
+        assert!(describe(&mut catalog).iter().all(|(_, _, nullable)| *nullable));
+    }
+
+    #[test]
+    fn values_past_the_inference_limit_must_fit_the_inferred_type() {
+        let contents = "n\n1\n2\nthree\n";
+        let mut catalog = external_table("csv-limit", contents, "", "OPTIONS (schema_infer_max_records = '2')").unwrap();
+        assert!(matches!(
+            query(&mut catalog, "SELECT n FROM t"),
+            Err(QueryError::InvalidValue { row: 4, expected: DataType::Int32, ref value, .. }) if value == "three"
+        ));
+        let mut catalog = external_table("csv-limit-all", contents, "", "OPTIONS (schema_infer_max_records = 'all')").unwrap();
+        assert_eq!(rows(&mut catalog, "SELECT n FROM t WHERE n = 'three'"), vec![vec![text("three")]]);
+    }
+
+    #[test]
+    fn explicit_schemas_are_checked_row_by_row() {
+        let contents = "i,f,s\n1,1.5,x\n2,2,\n";
+        let check = |test: &str, columns: &str| query(&mut external_table(test, contents, columns, "").unwrap(), "SELECT i FROM t");
+        assert!(matches!(
+            check("csv-schema-count", "(i INT, s TEXT)"),
+            Err(QueryError::FieldCountMismatch { row: 2, expected: 2, actual: 3 })
+        ));
+        assert!(matches!(
+            check("csv-schema-type", "(i INT, f INT, s TEXT)"),
+            Err(QueryError::InvalidValue { row: 2, ref column_name, expected: DataType::Int32, ref value }) if column_name == "f" && value == "1.5"
+        ));
+        assert!(matches!(
+            check("csv-schema-null", "(i INT, f FLOAT, s TEXT NOT NULL)"),
+            Err(QueryError::UnexpectedNull { row: 3, ref column_name }) if column_name == "s"
+        ));
+        let (_, values) = check("csv-schema", "(i INT NOT NULL, f FLOAT, s TEXT)").unwrap();
+        assert_eq!(values, vec![vec![int(1)], vec![int(2)]]);
+    }
+
+    /// Hands out at most seven bytes per read, like a slow pipe.
+    struct Trickle(Cursor<Vec<u8>>);
+
+    impl Read for Trickle {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            let len = buf.len().min(7);
+            self.0.read(&mut buf[..len])
+        }
+    }
+
+    /// Every row of one scan of `source`.
+    fn scan_rows(source: &CsvDataSource) -> Result<Vec<Vec<ScalarValue>>, QueryError> {
+        let mut rows = Vec::new();
+        for batch in source.scan(&SessionConfig::new(), None, &[]) {
+            let batch = batch?;
+            let height = batch.columns.first().map_or(0, |column| column.values.len());
+            rows.extend((0..height).map(|row| batch.columns.iter().map(|column| column.values[row].clone()).collect::<Vec<_>>()));
+        }
+        Ok(rows)
+    }
+
+    #[test]
+    fn streams_are_read_once_including_the_records_sampled_for_inference() {
+        // A quoted line break in the record that ends the sample.
+        let mut input = String::from("id,note\n");
+        for id in 1..=150 {
+            input.push_str(&if id == 100 { format!("{},\"two\nlines\"\n", id) } else { format!("{},n{}\n", id, id) });
         }
+        let source = CsvDataSource::from_reader(Box::new(Trickle(Cursor::new(input.into_bytes()))), CsvReadOptions::new()).unwrap();
+        let read = scan_rows(&source).unwrap();
+        assert_eq!(read.len(), 150);
+        assert_eq!(read[0], vec![int(1), text("n1")]);
+        assert_eq!(read[99], vec![int(100), text("two\nlines")]);
+        assert_eq!(read[149], vec![int(150), text("n150")]);
+        assert!(matches!(
+            scan_rows(&source),
+            Err(QueryError::DataSourceError { message }) if message == "The input stream was already read by an earlier scan"
+        ));
+    }
+
+    #[test]
+    fn blank_lines_of_a_single_column_are_nulls() {
+        let source = CsvDataSource::from_reader(Box::new(Cursor::new(b"a\n1\n\n3\n".to_vec())), CsvReadOptions::new()).unwrap();
+        assert!(source.schema().fields[0].is_nullable);
+        assert_eq!(scan_rows(&source).unwrap(), vec![vec![int(1)], vec![ScalarValue::Int32(None)], vec![int(3)]]);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn compressed_streams_are_decompressed() {
+        let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        io::Write::write_all(&mut encoder, b"a;b\n1;x\n").unwrap();
+        let options = CsvReadOptions::new().delimiter(';');
+        let source = CsvDataSource::from_reader(Box::new(Cursor::new(encoder.finish().unwrap())), options).unwrap();
+        assert_eq!(scan_rows(&source).unwrap(), vec![vec![int(1), text("x")]]);
+    }
+}
@@ -0,0 +1,321 @@
+use std::{io::BufRead, mem};
+
+use crate::errors::QueryError;
+
+use super::csv::{CsvEncoding, CsvReadOptions};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    /// At the start of a field, before any character of it has been read.
+    FieldStart,
+    /// Inside a field that did not start with a quote.
+    Unquoted,
+    /// Inside a quoted field, where delimiters and line breaks are data.
+    Quoted,
+    /// Just read a quote inside a quoted field: either the closing quote or the
+    /// first half of an escaped `""`.
+    QuoteInQuoted,
+    /// Just read the escape character inside a quoted field, so the next character is data.
+    Escaped,
+}
+
+/// Splits CSV text into records as described by RFC 4180.
+///
+/// Fields may be wrapped in quotes, in which case they can contain delimiters,
+/// line breaks and quotes written twice (`""`) or behind the escape character.
+/// Records end with either LF or CRLF. Blank lines and comment lines between
+/// records are skipped, except that once the first record has turned out to
+/// have a single field, a blank line is a record with one empty field, as in a
+/// one-column file with a missing value. Input that strays from the RFC is read leniently: a
+/// quote in the middle of an unquoted field, or text after a closing quote, is
+/// kept as data. A quoted field that is still open at the end of the input is
+/// an error, and so is text that is not valid in the configured encoding.
+pub struct CsvRecordReader<R: BufRead> {
+    reader: R,
+    delimiter: char,
+    quote: char,
+    escape: Option<char>,
+    comment: Option<String>,
+    encoding: CsvEncoding,
+    rows_to_skip: usize,
+    projection: Option<Vec<bool>>,
+    bytes: Vec<u8>,
+    line: String,
+    line_number: usize,
+    record_line: usize,
+    record_text: String,
+    quoted_fields: Vec<bool>,
+    /// How many fields the first record had, once it is read.
+    first_field_count: Option<usize>,
+}
+
+impl<R: BufRead> CsvRecordReader<R> {
+    pub fn new(reader: R, options: &CsvReadOptions) -> Self {
+        Self {
+            reader,
+            delimiter: options.delimiter,
+            quote: options.quote,
+            escape: options.escape.filter(|&escape| escape != options.quote),
+            comment: options.comment.clone(),
+            encoding: options.encoding,
+            rows_to_skip: options.skip_rows,
+            projection: None,
+            bytes: Vec::new(),
+            line: String::new(),
+            line_number: 0,
+            record_line: 0,
+            record_text: String::new(),
+            quoted_fields: Vec::new(),
+            first_field_count: None,
+        }
+    }
+
+    /// Only keeps the text of the fields whose index is set in `keep`; the
+    /// others are still split off but returned as empty strings.
+    pub fn with_projection(mut self, keep: Vec<bool>) -> Self {
+        self.projection = Some(keep);
+        self
+    }
+
+    /// Gives back the reader, positioned after the last line read.
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+
+    fn keeps(&self, index: usize) -> bool {
+        self.projection.as_ref().is_none_or(|keep| keep.get(index).copied().unwrap_or(false))
+    }
+
+    /// Reads one physical line into `self.line`, returning false at the end of the input.
+    fn read_line(&mut self) -> Result<bool, QueryError> {
+        self.bytes.clear();
+        self.line.clear();
+        let read = self.reader.read_until(b'\n', &mut self.bytes).map_err(|e| QueryError::DataSourceError {
+            message: format!("Failed to read line {}: {}", self.line_number + 1, e),
+        })?;
+        if read == 0 {
+            return Ok(false);
+        }
+        self.line_number += 1;
+
+        let mut bytes = self.bytes.as_slice();
+        if self.line_number == 1 && self.encoding != CsvEncoding::Latin1 {
+            bytes = bytes.strip_prefix(b"\xEF\xBB\xBF").unwrap_or(bytes);
+        }
+        match self.encoding {
+            CsvEncoding::Utf8 => match std::str::from_utf8(bytes) {
+                Ok(text) => self.line.push_str(text),
+                Err(e) => return Err(QueryError::DataSourceError {
+                    message: format!("Invalid UTF-8 on line {} at byte {}", self.line_number, e.valid_up_to()),
+                }),
+            },
+            CsvEncoding::Utf8Lossy => self.line.push_str(&String::from_utf8_lossy(bytes)),
+            CsvEncoding::Latin1 => self.line.extend(bytes.iter().map(|&b| char::from(b))),
+        }
+        Ok(true)
+    }
+
+    /// The line the last record returned by `read_record` started on, counting
+    /// from 1. A record with quoted line breaks spans several lines.
+    pub fn record_line(&self) -> usize {
+        self.record_line
+    }
+
+    /// The text of the last record as it appeared in the input, without its final line break.
+    pub fn record_text(&self) -> &str {
+        &self.record_text
+    }
+
+    /// Whether field `index` of the last record was wrapped in quotes, which
+    /// tells a quoted empty field (`""`) apart from a missing one.
+    pub fn is_quoted(&self, index: usize) -> bool {
+        self.quoted_fields.get(index).copied().unwrap_or(false)
+    }
+
+    /// Reads the next record, or returns `None` at the end of the input.
+    pub fn read_record(&mut self) -> Result<Option<Vec<String>>, QueryError> {
+        let record = self.read_fields()?;
+        if let Some(fields) = &record {
+            self.first_field_count.get_or_insert(fields.len());
+        }
+        Ok(record)
+    }
+
+    fn read_fields(&mut self) -> Result<Option<Vec<String>>, QueryError> {
+        let mut fields = Vec::new();
+        let mut field = String::new();
+        let mut state = State::FieldStart;
+        let mut record_start = None;
+
+        while self.rows_to_skip > 0 {
+            self.rows_to_skip -= 1;
+            if !self.read_line()? {
+                return Ok(None);
+            }
+        }
+
+        loop {
+            if !self.read_line()? {
+                return match record_start {
+                    None => Ok(None),
+                    Some(start) if matches!(state, State::Quoted | State::Escaped) => Err(QueryError::DataSourceError {
+                        message: format!("Unterminated quoted field in the record starting on line {}", start),
+                    }),
+                    Some(_) => {
+                        fields.push(field);
+                        self.quoted_fields.push(state == State::QuoteInQuoted);
+                        Ok(Some(fields))
+                    }
+                };
+            }
+
+            let body = self.line.trim_end_matches('\n').trim_end_matches('\r');
+            if record_start.is_none() {
+                let is_comment = self.comment.as_ref().is_some_and(|prefix| body.starts_with(prefix.as_str()));
+                if (body.is_empty() && self.first_field_count != Some(1)) || is_comment {
+                    continue;
+                }
+                record_start = Some(self.line_number);
+                self.record_line = self.line_number;
+                self.record_text.clear();
+                self.quoted_fields.clear();
+            }
+            self.record_text.push_str(body);
+
+            let mut keeping = self.keeps(fields.len());
+            for c in body.chars() {
+                state = match state {
+                    State::FieldStart | State::Unquoted if c == self.delimiter => {
+                        fields.push(mem::take(&mut field));
+                        self.quoted_fields.push(false);
+                        keeping = self.keeps(fields.len());
+                        State::FieldStart
+                    }
+                    State::FieldStart if c == self.quote => State::Quoted,
+                    State::Quoted if Some(c) == self.escape => State::Escaped,
+                    State::Quoted if c == self.quote => State::QuoteInQuoted,
+                    State::Escaped => {
+                        if keeping {
+                            field.push(c);
+                        }
+                        State::Quoted
+                    }
+                    State::QuoteInQuoted if c == self.quote => {
+                        if keeping {
+                            field.push(c);
+                        }
+                        State::Quoted
+                    }
+                    State::QuoteInQuoted if c == self.delimiter => {
+                        fields.push(mem::take(&mut field));
+                        self.quoted_fields.push(true);
+                        keeping = self.keeps(fields.len());
+                        State::FieldStart
+                    }
+                    State::Quoted => {
+                        if keeping {
+                            field.push(c);
+                        }
+                        State::Quoted
+                    }
+                    State::FieldStart | State::Unquoted | State::QuoteInQuoted => {
+                        if keeping {
+                            field.push(c);
+                        }
+                        State::Unquoted
+                    }
+                };
+            }
+
+            if matches!(state, State::Quoted | State::Escaped) {
+                // The line break belongs to the quoted field, so the record continues.
+                state = State::Quoted;
+                if keeping {
+                    field.push_str(&self.line[body.len()..]);
+                }
+                self.record_text.push_str(&self.line[body.len()..]);
+            } else {
+                fields.push(field);
+                self.quoted_fields.push(state == State::QuoteInQuoted);
+                return Ok(Some(fields));
+            }
+        }
+    }
+}
+
+impl<R: BufRead> Iterator for CsvRecordReader<R> {
+    type Item = Result<Vec<String>, QueryError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.read_record().transpose()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn records(text: &str, options: &CsvReadOptions) -> Result<Vec<Vec<String>>, QueryError> {
+        CsvRecordReader::new(text.as_bytes(), options).collect()
+    }
+
+    fn fields(values: &[&str]) -> Vec<String> {
+        values.iter().map(|value| value.to_string()).collect()
+    }
+
+    #[test]
+    fn quoted_fields_hold_delimiters_and_doubled_quotes() {
+        let read = records("a,\"b,c\",\"say \"\"hi\"\"\"\n\"\",x\"y,\"z\"tail\n", &CsvReadOptions::new()).unwrap();
+        assert_eq!(read, vec![fields(&["a", "b,c", "say \"hi\""]), fields(&["", "x\"y", "ztail"])]);
+    }
+
+    #[test]
+    fn quoted_empty_fields_are_told_apart_from_missing_ones() {
+        let mut reader = CsvRecordReader::new("\"\",,\"a\",b\n".as_bytes(), &CsvReadOptions::new());
+        assert_eq!(reader.read_record().unwrap(), Some(fields(&["", "", "a", "b"])));
+        let quoted: Vec<bool> = (0..4).map(|i| reader.is_quoted(i)).collect();
+        assert_eq!(quoted, [true, false, true, false]);
+    }
+
+    #[test]
+    fn crlf_and_lf_both_end_records() {
+        let read = records("a,b\r\n1,2\n3,4\r\n\r\n5,6", &CsvReadOptions::new()).unwrap();
+        assert_eq!(read, vec![fields(&["a", "b"]), fields(&["1", "2"]), fields(&["3", "4"]), fields(&["5", "6"])]);
+    }
+
+    #[test]
+    fn blank_lines_are_empty_fields_once_records_have_a_single_field() {
+        let read = records("\na\n1\n\n3\n", &CsvReadOptions::new()).unwrap();
+        assert_eq!(read, vec![fields(&["a"]), fields(&["1"]), fields(&[""]), fields(&["3"])]);
+    }
+
+    #[test]
+    fn quoted_line_breaks_continue_the_record() {
+        let mut reader = CsvRecordReader::new("id,text\n1,\"two\r\nlines\"\n2,\"\n\"\n".as_bytes(), &CsvReadOptions::new());
+        assert_eq!(reader.read_record().unwrap(), Some(fields(&["id", "text"])));
+        assert_eq!(reader.read_record().unwrap(), Some(fields(&["1", "two\r\nlines"])));
+        assert_eq!((reader.record_line(), reader.record_text()), (2, "1,\"two\r\nlines\""));
+        assert_eq!(reader.read_record().unwrap(), Some(fields(&["2", "\n"])));
+        assert_eq!(reader.record_line(), 4);
+        assert_eq!(reader.read_record().unwrap(), None);
+    }
+
+    #[test]
+    fn an_unterminated_quote_is_an_error() {
+        let error = records("a\n\"open,\nstill open\n", &CsvReadOptions::new()).unwrap_err();
+        assert!(matches!(error, QueryError::DataSourceError { ref message } if message.ends_with("starting on line 2")), "{:?}", error);
+    }
+
+    #[test]
+    fn escape_character_and_comments_follow_the_options() {
+        let options = CsvReadOptions::new().delimiter(';').escape('\\').comment("#");
+        let read = records("\u{FEFF}# header note\n\n\"a\\\"b\";c\n# trailing\n", &options).unwrap();
+        assert_eq!(read, vec![fields(&["a\"b", "c"])]);
+    }
+
+    #[test]
+    fn projection_keeps_only_the_chosen_fields() {
+        let reader = CsvRecordReader::new("1,\"x,y\",3\n".as_bytes(), &CsvReadOptions::new()).with_projection(vec![false, true, false]);
+        assert_eq!(reader.collect::<Result<Vec<_>, _>>().unwrap(), vec![fields(&["", "x,y", ""])]);
+    }
+}
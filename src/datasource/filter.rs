@@ -0,0 +1,93 @@
+use std::cmp::Ordering;
+
+use crate::{
+    DataType, ScalarValue, Schema,
+    logical_plan::plan::{Expression, Operator},
+    physical_plan::eval::compare_values,
+};
+
+/// A pushed-down filter of the form `column <op> literal`, the shape data
+/// sources can check without a full expression evaluator. Comparisons written
+/// as `literal <op> column` are turned around.
+#[derive(Debug, Clone)]
+pub(crate) struct ColumnComparison {
+    /// Index of the column in the source schema.
+    pub column: usize,
+    pub operator: Operator,
+    pub value: ScalarValue,
+}
+
+impl ColumnComparison {
+    /// Recognises a comparison between a column of `schema` and a non-null
+    /// literal of a type it can be compared with.
+    pub fn from_expression(filter: &Expression, schema: &Schema) -> Option<Self> {
+        let Expression::Binary { left, right, operator } = filter else {
+            return None;
+        };
+        let (name, value, operator) = match (left.as_ref(), right.as_ref()) {
+            (Expression::Column { name, .. }, Expression::Literal(value)) => (name, value, operator.clone()),
+            (Expression::Literal(value), Expression::Column { name, .. }) => (name, value, flip(operator)?),
+            _ => return None,
+        };
+        let column = schema.index_of(name).ok()?;
+        let column_type = schema.fields[column].field_type;
+        let comparable = match (column_type, value.data_type()) {
+            (DataType::Int32 | DataType::Float64, DataType::Int32 | DataType::Float64) => true,
+            (DataType::String, DataType::String) => true,
+            (DataType::Bool, DataType::Bool) => matches!(operator, Operator::Eq | Operator::NotEq),
+            _ => false,
+        };
+        (comparable && !value.is_null() && flip(&operator).is_some()).then(|| Self { column, operator, value: value.clone() })
+    }
+
+    /// NULL compares as unknown, which a filter treats as false.
+    pub fn matches(&self, value: &ScalarValue) -> bool {
+        if value.is_null() {
+            return false;
+        }
+        let ordering = compare_values(value, &self.value);
+        match self.operator {
+            Operator::Eq => ordering == Ordering::Equal,
+            Operator::NotEq => ordering != Ordering::Equal,
+            Operator::Lt => ordering == Ordering::Less,
+            Operator::LtEq => ordering != Ordering::Greater,
+            Operator::Gt => ordering == Ordering::Greater,
+            Operator::GtEq => ordering != Ordering::Less,
+            Operator::Add | Operator::Subtract | Operator::Multiply | Operator::Divide => false,
+        }
+    }
+
+    /// Whether some value between `min` and `max`, inclusive, could pass the filter.
+    pub fn may_match_range(&self, min: &ScalarValue, max: &ScalarValue) -> bool {
+        let below_min = compare_values(&self.value, min) == Ordering::Less;
+        let above_max = compare_values(&self.value, max) == Ordering::Greater;
+        match self.operator {
+            Operator::Eq => !below_min && !above_max,
+            // Only a range holding nothing but the literal fails `<>`.
+            Operator::NotEq => !(self.matches_exactly(min) && self.matches_exactly(max)),
+            Operator::Lt => self.matches(min),
+            Operator::LtEq => self.matches(min),
+            Operator::Gt => self.matches(max),
+            Operator::GtEq => self.matches(max),
+            Operator::Add | Operator::Subtract | Operator::Multiply | Operator::Divide => true,
+        }
+    }
+
+    fn matches_exactly(&self, value: &ScalarValue) -> bool {
+        compare_values(value, &self.value) == Ordering::Equal
+    }
+}
+
+/// The comparison that gives the same result with its operands swapped, or
+/// `None` for operators that are not comparisons.
+fn flip(operator: &Operator) -> Option<Operator> {
+    match operator {
+        Operator::Eq => Some(Operator::Eq),
+        Operator::NotEq => Some(Operator::NotEq),
+        Operator::Lt => Some(Operator::Gt),
+        Operator::LtEq => Some(Operator::GtEq),
+        Operator::Gt => Some(Operator::Lt),
+        Operator::GtEq => Some(Operator::LtEq),
+        Operator::Add | Operator::Subtract | Operator::Multiply | Operator::Divide => None,
+    }
+}
@@ -0,0 +1,408 @@
+use std::{collections::HashMap, io::BufRead, iter, path::{Path, PathBuf}};
+
+use serde_json::{Map, Value};
+
+use crate::{
+    ColumnVector, DataSource, DataType, Field, RecordBatch, ScalarValue, Schema, SessionConfig,
+    errors::QueryError,
+    logical_plan::plan::Expression,
+};
+
+use super::compression::{data_extension, open_file};
+
+/// Options for reading a JSON Lines file, set with chained builder calls like `CsvReadOptions`.
+#[derive(Debug, Clone)]
+pub struct JsonReadOptions {
+    /// Accepted file extensions, compared case-insensitively. Empty accepts any file.
+    pub file_extensions: Vec<String>,
+    /// The schema to read the file with. When set, no inference is done.
+    pub schema: Option<Schema>,
+    /// How many objects schema inference looks at. `None` reads the whole file.
+    pub schema_infer_max_records: Option<usize>,
+}
+
+impl Default for JsonReadOptions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl JsonReadOptions {
+    pub fn new() -> Self {
+        Self {
+            file_extensions: vec!["jsonl".to_string(), "ndjson".to_string(), "json".to_string()],
+            schema: None,
+            schema_infer_max_records: Some(100),
+        }
+    }
+
+    /// Adds an accepted file extension, without the leading dot.
+    pub fn file_extension(mut self, extension: &str) -> Self {
+        self.file_extensions.push(extension.trim_start_matches('.').to_string());
+        self
+    }
+
+    /// Accepts files with any extension.
+    pub fn any_file_extension(self) -> Self {
+        Self { file_extensions: Vec::new(), ..self }
+    }
+
+    pub fn schema(self, schema: Schema) -> Self {
+        Self { schema: Some(schema), ..self }
+    }
+
+    pub fn schema_infer_max_records(self, max_records: usize) -> Self {
+        Self { schema_infer_max_records: Some(max_records), ..self }
+    }
+
+    /// Infers the schema from every object in the file rather than a sample.
+    pub fn infer_schema_from_all_records(self) -> Self {
+        Self { schema_infer_max_records: None, ..self }
+    }
+
+    /// Sets one option from its name and text value, as written in
+    /// `CREATE EXTERNAL TABLE ... OPTIONS (...)`. A `file_extension` of `*`
+    /// accepts files with any extension.
+    pub fn set(self, key: &str, value: &str) -> Result<Self, QueryError> {
+        let invalid = || QueryError::ValidationError {
+            message: format!("Invalid value '{}' for JSON option '{}'", value, key),
+        };
+        Ok(match key.to_lowercase().as_str() {
+            "file_extension" if value == "*" => self.any_file_extension(),
+            "file_extension" => self.file_extension(value),
+            "schema_infer_max_records" if value.eq_ignore_ascii_case("all") => self.infer_schema_from_all_records(),
+            "schema_infer_max_records" => self.schema_infer_max_records(value.parse::<usize>().map_err(|_| invalid())?),
+            _ => return Err(QueryError::ValidationError {
+                message: format!("Unknown JSON option '{}'", key),
+            }),
+        })
+    }
+}
+
+/// Reads newline-delimited JSON: one object per line, each object one row.
+///
+/// Keys become columns and keys missing from an object are NULL. JSON numbers
+/// are `Int32` when every sampled value is an integer that fits, and `Float64`
+/// otherwise. Strings are `String` and `true`/`false` are `Bool`. Arrays,
+/// nested objects and columns whose sampled values disagree on a type are read
+/// as `String` holding the JSON text of each value.
+#[derive(Debug)]
+pub struct JsonDataSource {
+    file_path: PathBuf,
+    schema: Schema,
+}
+
+impl JsonDataSource {
+    pub fn new(file_path: String) -> Result<Self, QueryError> {
+        Self::with_options(file_path, JsonReadOptions::new())
+    }
+
+    pub fn with_options(file_path: String, options: JsonReadOptions) -> Result<Self, QueryError> {
+        let path = Path::new(&file_path);
+        if !path.is_file() {
+            return Err(QueryError::DataSourceError { message: "File doesn't exist".into() });
+        }
+        let extension = data_extension(path);
+        let allowed = options.file_extensions.is_empty()
+            || extension.is_some_and(|extension| options.file_extensions.iter().any(|e| e.to_lowercase() == extension));
+        if !allowed {
+            return Err(QueryError::DataSourceError {
+                message: format!("Not a JSON file: expected one of the extensions {:?}", options.file_extensions),
+            });
+        }
+
+        let schema = match &options.schema {
+            Some(schema) => schema.clone(),
+            None => Schema::new(Self::infer_schema(path, &options)?),
+        };
+        Ok(Self { file_path: path.to_path_buf(), schema })
+    }
+
+    /// Infers columns from up to `schema_infer_max_records` objects, in the
+    /// order their keys first appear. A column is only marked as not nullable
+    /// when inference read the whole file and every object had a non-null value for it.
+    pub fn infer_schema(path: &Path, options: &JsonReadOptions) -> Result<Vec<Field>, QueryError> {
+        let mut objects = JsonObjectReader::open(path)?;
+        let mut names: Vec<String> = Vec::new();
+        let mut columns: HashMap<String, JsonColumnStats> = HashMap::new();
+        let mut seen = 0;
+        let mut read_whole_file = false;
+        loop {
+            if options.schema_infer_max_records.is_some_and(|max| seen >= max) {
+                break;
+            }
+            let Some(object) = objects.next_object()? else {
+                read_whole_file = true;
+                break;
+            };
+            for (key, value) in &object {
+                let column = columns.entry(key.clone()).or_insert_with(|| {
+                    names.push(key.clone());
+                    JsonColumnStats { data_type: None, values_seen: 0, has_null: seen > 0 }
+                });
+                column.observe(value);
+            }
+            seen += 1;
+            for column in columns.values_mut() {
+                column.has_null |= column.values_seen < seen;
+            }
+        }
+        if names.is_empty() {
+            return Err(QueryError::DataSourceError {
+                message: "Failed to infer schema: the file has no JSON object with any key".to_string(),
+            });
+        }
+
+        Ok(names.into_iter().map(|name| {
+            let column = &columns[&name];
+            Field {
+                name,
+                field_type: column.data_type.unwrap_or(DataType::String),
+                is_nullable: column.has_null || !read_whole_file,
+            }
+        }).collect())
+    }
+}
+
+/// What schema inference has learned about one key from the objects seen so far.
+struct JsonColumnStats {
+    /// The type that holds every non-null value seen, or `None` if there was none.
+    data_type: Option<DataType>,
+    values_seen: usize,
+    has_null: bool,
+}
+
+impl JsonColumnStats {
+    fn observe(&mut self, value: &Value) {
+        self.values_seen += 1;
+        let value_type = match value {
+            Value::Null => {
+                self.has_null = true;
+                return;
+            },
+            Value::Bool(_) => DataType::Bool,
+            Value::Number(n) if n.as_i64().is_some_and(|n| i32::try_from(n).is_ok()) => DataType::Int32,
+            Value::Number(_) => DataType::Float64,
+            Value::String(_) | Value::Array(_) | Value::Object(_) => DataType::String,
+        };
+        self.data_type = Some(match (self.data_type, value_type) {
+            (None, value_type) => value_type,
+            (Some(current), value_type) if current == value_type => current,
+            (Some(DataType::Int32 | DataType::Float64), DataType::Int32 | DataType::Float64) => DataType::Float64,
+            _ => DataType::String,
+        });
+    }
+}
+
+/// Reads a JSON Lines file one object at a time, skipping blank lines.
+struct JsonObjectReader {
+    reader: Box<dyn BufRead>,
+    line: String,
+    line_number: usize,
+}
+
+impl JsonObjectReader {
+    fn open(path: &Path) -> Result<Self, QueryError> {
+        Ok(Self { reader: open_file(path)?, line: String::new(), line_number: 0 })
+    }
+
+    /// Reads the next object, or returns `None` at the end of the file.
+    fn next_object(&mut self) -> Result<Option<Map<String, Value>>, QueryError> {
+        loop {
+            self.line.clear();
+            let read = self.reader.read_line(&mut self.line).map_err(|e| QueryError::DataSourceError {
+                message: format!("Failed to read line {}: {}", self.line_number + 1, e),
+            })?;
+            if read == 0 {
+                return Ok(None);
+            }
+            self.line_number += 1;
+
+            let text = self.line.trim();
+            let text = if self.line_number == 1 { text.trim_start_matches('\u{feff}') } else { text };
+            if text.is_empty() {
+                continue;
+            }
+            return match serde_json::from_str::<Value>(text) {
+                Ok(Value::Object(object)) => Ok(Some(object)),
+                Ok(_) => Err(QueryError::DataSourceError {
+                    message: format!("Line {} is not a JSON object", self.line_number),
+                }),
+                Err(e) => Err(QueryError::DataSourceError {
+                    message: format!("Invalid JSON on line {}: {}", self.line_number, e),
+                }),
+            };
+        }
+    }
+}
+
+struct JsonBatchIterator {
+    objects: JsonObjectReader,
+    schema: Schema,
+    batch_size: usize,
+    finished: bool,
+}
+
+impl Iterator for JsonBatchIterator {
+    type Item = Result<RecordBatch, QueryError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.finished {
+            return None;
+        }
+
+        let mut columnar_data: Vec<Vec<ScalarValue>> = vec![Vec::new(); self.schema.fields.len()];
+        let mut row_count = 0;
+        while row_count < self.batch_size {
+            let object = match self.objects.next_object() {
+                Ok(Some(object)) => object,
+                Ok(None) => {
+                    self.finished = true;
+                    break;
+                },
+                Err(e) => {
+                    self.finished = true;
+                    return Some(Err(e));
+                }
+            };
+            for (column, field) in columnar_data.iter_mut().zip(&self.schema.fields) {
+                match json_to_scalar(object.get(&field.name), field, self.objects.line_number) {
+                    Ok(value) => column.push(value),
+                    Err(e) => {
+                        self.finished = true;
+                        return Some(Err(e));
+                    }
+                }
+            }
+            row_count += 1;
+        }
+        if row_count == 0 {
+            return None;
+        }
+
+        let columns = columnar_data.into_iter().map(ColumnVector::new).collect();
+        Some(RecordBatch::new(self.schema.clone(), columns))
+    }
+}
+
+/// Reads one JSON value as a value of its column's type. `row` is only used in errors.
+fn json_to_scalar(value: Option<&Value>, field: &Field, row: usize) -> Result<ScalarValue, QueryError> {
+    let value = match value {
+        None | Some(Value::Null) if field.is_nullable => return Ok(ScalarValue::null(field.field_type)),
+        None | Some(Value::Null) => return Err(QueryError::UnexpectedNull { row, column_name: field.name.clone() }),
+        Some(value) => value,
+    };
+
+    let scalar = match field.field_type {
+        DataType::Int32 => value.as_i64().and_then(|n| i32::try_from(n).ok()).map(|n| ScalarValue::Int32(Some(n))),
+        DataType::Float64 => value.as_f64().map(|n| ScalarValue::Float64(Some(n))),
+        DataType::Bool => value.as_bool().map(|b| ScalarValue::Bool(Some(b))),
+        DataType::String => Some(ScalarValue::String(Some(match value {
+            Value::String(s) => s.clone(),
+            other => other.to_string(),
+        }))),
+    };
+    scalar.ok_or_else(|| QueryError::InvalidValue {
+        row,
+        column_name: field.name.clone(),
+        expected: field.field_type,
+        value: value.to_string(),
+    })
+}
+
+impl DataSource for JsonDataSource {
+    fn schema(&self) -> &Schema {
+        &self.schema
+    }
+
+    fn scan(
+        &self,
+        config: &SessionConfig,
+        projection: Option<&[usize]>,
+        _filters: &[Expression],
+    ) -> Box<dyn Iterator<Item = Result<RecordBatch, QueryError>>> {
+        let schema = match projection {
+            Some(indices) => self.schema.project(indices),
+            None => self.schema.clone(),
+        };
+        match JsonObjectReader::open(&self.file_path) {
+            Ok(objects) => Box::new(JsonBatchIterator {
+                objects,
+                schema,
+                batch_size: config.batch_size(),
+                finished: false,
+            }),
+            Err(e) => Box::new(iter::once(Err(e))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, rc::Rc};
+
+    use crate::logical_plan::plan::Catalog;
+    use crate::test_util::{float, int, query, rows, temp_dir, text};
+
+    use super::*;
+
+    /// A catalog with table `t` over a JSON Lines file holding `contents`.
+    fn json_table(test: &str, contents: &str, columns: &str) -> Catalog {
+        let path = temp_dir(test).join("t.json");
+        fs::write(&path, contents).unwrap();
+        let mut catalog = Catalog::new();
+        rows(&mut catalog, &format!(
+            "CREATE EXTERNAL TABLE t {} STORED AS JSON LOCATION '{}' OPTIONS (schema_infer_max_records = '1')",
+            columns,
+            path.display(),
+        ));
+        catalog
+    }
+
+    #[test]
+    fn keys_become_columns_in_the_order_they_appear() {
+        let contents = "{\"id\": 1, \"score\": 2, \"tags\": [\"a\"], \"mixed\": 1}\n\n{\"id\": 2, \"score\": 2.5, \"name\": \"b\", \"mixed\": \"x\", \"ok\": true}\n";
+        let path = temp_dir("json-infer").join("t.json");
+        fs::write(&path, contents).unwrap();
+        let source = JsonDataSource::new(path.to_string_lossy().into_owned()).unwrap();
+        let fields: Vec<(&str, DataType, bool)> = source.schema().fields.iter().map(|f| (f.name.as_str(), f.field_type, f.is_nullable)).collect();
+        // Integers and floats widen to Float64, and other mixes fall back to text.
+        assert_eq!(fields, [
+            ("id", DataType::Int32, false),
+            ("score", DataType::Float64, false),
+            ("tags", DataType::String, true),
+            ("mixed", DataType::String, false),
+            ("name", DataType::String, true),
+            ("ok", DataType::Bool, true),
+        ]);
+
+        let mut catalog = Catalog::new();
+        catalog.register_table("t".to_string(), Rc::new(source));
+        let null = ScalarValue::String(None);
+        assert_eq!(rows(&mut catalog, "SELECT * FROM t"), vec![
+            vec![int(1), float(2.0), text("[\"a\"]"), text("1"), null.clone(), ScalarValue::Bool(None)],
+            vec![int(2), float(2.5), null, text("x"), text("b"), ScalarValue::Bool(Some(true))],
+        ]);
+    }
+
+    #[test]
+    fn lines_that_are_not_objects_are_errors() {
+        let error = |test: &str, contents: &str| match query(&mut json_table(test, contents, ""), "SELECT id FROM t") {
+            Err(QueryError::DataSourceError { message }) => message,
+            other => panic!("{:?} was read: {:?}", contents, other.map(|(_, rows)| rows)),
+        };
+        assert_eq!(error("json-array", "{\"id\": 1}\n[1]\n"), "Line 2 is not a JSON object");
+        assert!(error("json-invalid", "{\"id\": 1}\n{\"id\": \n").starts_with("Invalid JSON on line 2: "));
+    }
+
+    #[test]
+    fn values_must_fit_their_column() {
+        let mut catalog = json_table("json-range", "{\"id\": 1}\n{\"id\": 3000000000}\n", "(id INT)");
+        assert!(matches!(
+            query(&mut catalog, "SELECT id FROM t"),
+            Err(QueryError::InvalidValue { row: 2, expected: DataType::Int32, ref value, .. }) if value == "3000000000"
+        ));
+        let mut catalog = json_table("json-null", "{\"id\": 1}\n{\"id\": null}\n", "(id INT NOT NULL)");
+        assert!(matches!(query(&mut catalog, "SELECT id FROM t"), Err(QueryError::UnexpectedNull { row: 2, .. })));
+    }
+}
@@ -0,0 +1,752 @@
+use std::{cell::Cell, collections::HashSet, fmt, fs, iter, path::{Path, PathBuf}, rc::Rc};
+
+use crate::{
+    ColumnVector, DataSource, DataType, Field, RecordBatch, ScalarValue, Schema, SessionConfig,
+    errors::QueryError,
+    logical_plan::plan::Expression,
+    physical_plan::planner::create_physical_expr,
+};
+
+use super::FilterSupport;
+use super::compression::data_extension;
+
+/// The virtual column of a `ListingTable` holding the path of the file each row was read from.
+pub const FILE_NAME_COLUMN: &str = "_file_name";
+
+/// Whether `location` names a set of files rather than a single file: a directory or a glob pattern.
+pub fn is_listing_location(location: &str) -> bool {
+    has_glob_chars(location) || Path::new(location).is_dir()
+}
+
+fn has_glob_chars(text: &str) -> bool {
+    text.contains(['*', '?', '['])
+}
+
+/// Lists the files a location names, sorted by path.
+///
+/// A directory lists every file below it, and a glob pattern lists the files
+/// it matches. Patterns use `*` and `?` within a path component, `[abc]`,
+/// `[a-z]` and `[!abc]` for sets of characters, and `**` for any number of
+/// directories. Only files with one of `extensions` are listed, unless it is
+/// empty. Hidden files and files starting with `_`, such as `_SUCCESS`
+/// markers, are left out.
+pub fn list_files(location: &str, extensions: &[String]) -> Result<Vec<PathBuf>, QueryError> {
+    let mut files = Vec::new();
+    if has_glob_chars(location) {
+        let components: Vec<&str> = location.split('/').collect();
+        let literal = components.iter().take_while(|component| !has_glob_chars(component)).count();
+        let base = listing_base(location);
+        let shown = base.to_string_lossy();
+        let dir = match literal {
+            0 => Path::new("."),
+            _ => base.as_path(),
+        };
+        glob_files(dir, &shown, &components[literal..], &mut files)?;
+    } else if Path::new(location).is_dir() {
+        walk_files(Path::new(location), &mut files)?;
+    } else {
+        return Err(QueryError::DataSourceError {
+            message: format!("'{}' is not a directory or a glob pattern", location),
+        });
+    }
+
+    files.retain(|file| {
+        let name = file.file_name().map_or(String::new(), |name| name.to_string_lossy().into_owned());
+        let extension = data_extension(file);
+        !name.starts_with(['.', '_'])
+            && (extensions.is_empty()
+                || extension.is_some_and(|extension| extensions.iter().any(|e| e.to_lowercase() == extension)))
+    });
+    files.sort();
+    Ok(files)
+}
+
+fn read_dir(dir: &Path) -> Result<Vec<PathBuf>, QueryError> {
+    let entries = fs::read_dir(dir).map_err(|e| QueryError::DataSourceError {
+        message: format!("Failed to list directory {}: {}", dir.display(), e),
+    })?;
+    entries
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<Result<Vec<PathBuf>, _>>()
+        .map_err(|e| QueryError::DataSourceError {
+            message: format!("Failed to list directory {}: {}", dir.display(), e),
+        })
+}
+
+fn walk_files(dir: &Path, files: &mut Vec<PathBuf>) -> Result<(), QueryError> {
+    for path in read_dir(dir)? {
+        if path.is_dir() {
+            walk_files(&path, files)?;
+        } else if path.is_file() {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+/// Collects the files below `dir` whose remaining path matches `pattern`, one
+/// component per directory level. `shown` is `dir` as it appears in the listed paths.
+fn glob_files(dir: &Path, shown: &str, pattern: &[&str], files: &mut Vec<PathBuf>) -> Result<(), QueryError> {
+    let Some((&component, rest)) = pattern.split_first() else {
+        return Ok(());
+    };
+    if component == "**" {
+        // `**` matches no directory at all, or any directory and then `**` again.
+        glob_files(dir, shown, rest, files)?;
+        for path in read_dir(dir)?.into_iter().filter(|path| path.is_dir()) {
+            let name = path.file_name().map_or(String::new(), |name| name.to_string_lossy().into_owned());
+            glob_files(&path, &join(shown, &name), pattern, files)?;
+        }
+        return Ok(());
+    }
+    if !has_glob_chars(component) {
+        let path = dir.join(component);
+        return match rest.is_empty() {
+            true if path.is_file() => {
+                files.push(PathBuf::from(join(shown, component)));
+                Ok(())
+            },
+            true => Ok(()),
+            false if path.is_dir() => glob_files(&path, &join(shown, component), rest, files),
+            false => Ok(()),
+        };
+    }
+    for path in read_dir(dir)? {
+        let name = path.file_name().map_or(String::new(), |name| name.to_string_lossy().into_owned());
+        if !glob_match(component, &name) {
+            continue;
+        }
+        if rest.is_empty() && path.is_file() {
+            files.push(PathBuf::from(join(shown, &name)));
+        } else if !rest.is_empty() && path.is_dir() {
+            glob_files(&path, &join(shown, &name), rest, files)?;
+        }
+    }
+    Ok(())
+}
+
+fn join(dir: &str, name: &str) -> String {
+    match dir {
+        "" => name.to_string(),
+        dir if dir.ends_with('/') => format!("{}{}", dir, name),
+        dir => format!("{}/{}", dir, name),
+    }
+}
+
+/// Matches one path component against a glob pattern without `/`.
+fn glob_match(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+    match_from(&pattern, &name)
+}
+
+fn match_from(pattern: &[char], name: &[char]) -> bool {
+    match pattern.first() {
+        None => name.is_empty(),
+        Some('*') => (0..=name.len()).any(|skip| match_from(&pattern[1..], &name[skip..])),
+        Some('?') => !name.is_empty() && match_from(&pattern[1..], &name[1..]),
+        Some('[') => {
+            let Some(end) = pattern.iter().skip(2).position(|&c| c == ']').map(|i| i + 2) else {
+                return name.first() == Some(&'[') && match_from(&pattern[1..], &name[1..]);
+            };
+            let Some(&c) = name.first() else {
+                return false;
+            };
+            let (negated, set) = match pattern[1] {
+                '!' | '^' => (true, &pattern[2..end]),
+                _ => (false, &pattern[1..end]),
+            };
+            let mut in_set = false;
+            let mut i = 0;
+            while i < set.len() {
+                if i + 2 < set.len() && set[i + 1] == '-' {
+                    in_set |= set[i] <= c && c <= set[i + 2];
+                    i += 3;
+                } else {
+                    in_set |= set[i] == c;
+                    i += 1;
+                }
+            }
+            in_set != negated && match_from(&pattern[end + 1..], &name[1..])
+        },
+        Some(&c) => name.first() == Some(&c) && match_from(&pattern[1..], &name[1..]),
+    }
+}
+
+/// One file of a `ListingTable`, opened only when a scan reads it.
+#[derive(Clone)]
+struct ListedFile {
+    path: String,
+    /// The values of the table's path columns for this file: its partition values and its path.
+    path_values: Vec<ScalarValue>,
+}
+
+/// Opens one file of a `ListingTable` as a data source.
+type OpenFile = Rc<dyn Fn(String) -> Result<Rc<dyn DataSource>, QueryError>>;
+
+/// A table made of every file in a directory or every file matching a glob
+/// pattern, such as one export per day. The files are read one after another
+/// in path order.
+///
+/// The table's file columns are those of an explicit schema, or else of the
+/// first file, and are nullable when there is more than one file. Each file is
+/// read by its own data source, opened when a scan reaches it, with columns
+/// matched by name. Columns missing from a file are NULL for its rows, columns
+/// the table does not have are not read, and an `Int32` column is read as
+/// `Float64` when the table has it as `Float64`. Any other disagreement about a
+/// column's type is an error when the file is scanned.
+///
+/// After the file columns come the path columns, which hold one value per
+/// file. Directories named `key=value` below the listed directory, as in
+/// `sales/year=2025/month=08/part.csv`, are Hive-style partitions and add a
+/// `key` column. Its type is inferred from the values of all files like a CSV
+/// column, and `__HIVE_DEFAULT_PARTITION__` is NULL. Every file must have the
+/// same partition keys in the same order. The last column, `_file_name`,
+/// holds the path of the file each row came from.
+///
+/// Filters that only read path columns are evaluated once per file, so files
+/// they reject are never opened.
+pub struct ListingTable {
+    files: Vec<ListedFile>,
+    schema: Schema,
+    /// How many columns come from the files rather than their paths.
+    file_column_count: usize,
+    open: OpenFile,
+    pruned_file_count: Cell<usize>,
+}
+
+impl fmt::Debug for ListingTable {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let paths: Vec<&str> = self.files.iter().map(|file| file.path.as_str()).collect();
+        f.debug_struct("ListingTable").field("files", &paths).field("schema", &self.schema).finish()
+    }
+}
+
+/// The partition value Hive writes for NULL.
+const HIVE_DEFAULT_PARTITION: &str = "__HIVE_DEFAULT_PARTITION__";
+
+impl ListingTable {
+    /// Lists the files at `location` with `list_files`, which `open` opens when
+    /// a scan reads them. Without a `schema`, the first file is opened to infer it.
+    pub fn try_new(
+        location: &str,
+        extensions: &[String],
+        schema: Option<Schema>,
+        open: impl Fn(String) -> Result<Rc<dyn DataSource>, QueryError> + 'static,
+    ) -> Result<Self, QueryError> {
+        let paths = list_files(location, extensions)?;
+        if paths.is_empty() {
+            return Err(QueryError::DataSourceError {
+                message: format!("No files found at '{}'", location),
+            });
+        }
+        let base = listing_base(location);
+        let partitions = paths
+            .iter()
+            .map(|path| partition_values(path.strip_prefix(&base).unwrap_or(path)))
+            .collect::<Vec<Vec<(String, String)>>>();
+        let keys: Vec<&str> = partitions[0].iter().map(|(key, _)| key.as_str()).collect();
+        if let Some((path, _)) = paths.iter().zip(&partitions).find(|(_, values)| {
+            !values.iter().map(|(key, _)| key.as_str()).eq(keys.iter().copied())
+        }) {
+            return Err(QueryError::DataSourceError {
+                message: format!(
+                    "{} does not have the partition keys {:?} of {}",
+                    path.display(), keys, paths[0].display()
+                ),
+            });
+        }
+
+        let open: OpenFile = Rc::new(open);
+        let paths: Vec<String> = paths.iter().map(|path| path.to_string_lossy().into_owned()).collect();
+        let file_schema = match schema {
+            Some(schema) => schema,
+            None => open_file(&open, &paths[0])?.schema().clone(),
+        };
+        // Later files may lack a column, which is then NULL for their rows.
+        let mut fields: Vec<Field> = file_schema
+            .fields
+            .into_iter()
+            .map(|field| Field { is_nullable: field.is_nullable || paths.len() > 1, ..field })
+            .collect();
+        let file_column_count = fields.len();
+
+        let mut path_values: Vec<Vec<ScalarValue>> = vec![Vec::new(); paths.len()];
+        for (k, key) in keys.iter().enumerate() {
+            if fields.iter().any(|field| field.name == *key) {
+                return Err(QueryError::DataSourceError {
+                    message: format!("Partition key {} is also a column of the files", key),
+                });
+            }
+            let texts: Vec<Option<&str>> = partitions
+                .iter()
+                .map(|values| Some(values[k].1.as_str()).filter(|text| !text.is_empty() && *text != HIVE_DEFAULT_PARTITION))
+                .collect();
+            let field_type = partition_type(texts.iter().flatten().copied());
+            for (values, text) in path_values.iter_mut().zip(&texts) {
+                values.push(match text {
+                    Some(text) => parse_partition_value(text, field_type),
+                    None => ScalarValue::null(field_type),
+                });
+            }
+            fields.push(Field { name: key.to_string(), field_type, is_nullable: texts.contains(&None) });
+        }
+        if fields.iter().all(|field| field.name != FILE_NAME_COLUMN) {
+            fields.push(Field { name: FILE_NAME_COLUMN.to_string(), field_type: DataType::String, is_nullable: false });
+            for (values, path) in path_values.iter_mut().zip(&paths) {
+                values.push(ScalarValue::String(Some(path.clone())));
+            }
+        }
+
+        let files = paths.into_iter().zip(path_values).map(|(path, path_values)| ListedFile { path, path_values }).collect();
+        Ok(Self { files, schema: Schema::new(fields), file_column_count, open, pruned_file_count: Cell::new(0) })
+    }
+
+    /// The paths of the files the table reads, in the order they are read.
+    pub fn file_paths(&self) -> Vec<&str> {
+        self.files.iter().map(|file| file.path.as_str()).collect()
+    }
+
+    /// How many files the latest scan skipped because of filters on path columns.
+    pub fn pruned_file_count(&self) -> usize {
+        self.pruned_file_count.get()
+    }
+
+    /// Whether every column `filter` reads is a path column.
+    fn reads_only_path_columns(&self, filter: &Expression) -> bool {
+        let mut names = HashSet::new();
+        filter.collect_columns(&mut names);
+        names.iter().all(|name| self.schema.index_of(name).is_ok_and(|index| index >= self.file_column_count))
+    }
+
+    /// Whether every column `filter` reads is a file column.
+    fn reads_only_file_columns(&self, filter: &Expression) -> bool {
+        let mut names = HashSet::new();
+        filter.collect_columns(&mut names);
+        names.iter().all(|name| self.schema.index_of(name).is_ok_and(|index| index < self.file_column_count))
+    }
+
+    /// Which files pass every filter that only reads path columns, evaluated
+    /// over a batch with one row of path values per file.
+    fn files_to_scan(&self, filters: &[Expression]) -> Result<Vec<bool>, QueryError> {
+        let mut keep = vec![true; self.files.len()];
+        let path_filters: Vec<&Expression> = filters.iter().filter(|filter| self.reads_only_path_columns(filter)).collect();
+        if path_filters.is_empty() {
+            return Ok(keep);
+        }
+        let path_schema = Schema::new(self.schema.fields[self.file_column_count..].to_vec());
+        let columns = (0..path_schema.fields.len())
+            .map(|i| ColumnVector::new(self.files.iter().map(|file| file.path_values[i].clone()).collect()))
+            .collect();
+        let batch = RecordBatch::new(path_schema.clone(), columns)?;
+        for filter in path_filters {
+            let passed = create_physical_expr(filter, &path_schema)?.evaluate(&batch)?;
+            for (keep, value) in keep.iter_mut().zip(&passed.values) {
+                *keep &= matches!(value, ScalarValue::Bool(Some(true)));
+            }
+        }
+        Ok(keep)
+    }
+}
+
+/// Opens the file at `path`, naming it in the errors of its data source.
+fn open_file(open: &OpenFile, path: &str) -> Result<Rc<dyn DataSource>, QueryError> {
+    open(path.to_string()).map_err(|e| match e {
+        QueryError::DataSourceError { message } => QueryError::DataSourceError {
+            message: format!("{}: {}", path, message),
+        },
+        other => other,
+    })
+}
+
+/// What every file read by one scan of a `ListingTable` shares.
+struct FileScan {
+    open: OpenFile,
+    /// The table's file columns.
+    file_fields: Vec<Field>,
+    /// The table columns to return, and their schema.
+    projection: Vec<usize>,
+    schema: Schema,
+    filters: Vec<Expression>,
+    config: SessionConfig,
+}
+
+impl FileScan {
+    /// Opens `file` and scans it, filling in the columns it lacks and its path columns.
+    fn scan(&self, file: ListedFile) -> Result<Box<dyn Iterator<Item = Result<RecordBatch, QueryError>>>, QueryError> {
+        let source = open_file(&self.open, &file.path)?;
+        let file_schema = source.schema();
+        // For each file column of the table, its index in this file's schema,
+        // or `None` when the file does not have the column.
+        let columns = self
+            .file_fields
+            .iter()
+            .map(|field| {
+                let Ok(index) = file_schema.index_of(&field.name) else {
+                    return Ok(None);
+                };
+                match (file_schema.fields[index].field_type, field.field_type) {
+                    (file_type, table_type) if file_type == table_type => Ok(Some(index)),
+                    (DataType::Int32, DataType::Float64) => Ok(Some(index)),
+                    (file_type, table_type) => Err(QueryError::DataSourceError {
+                        message: format!(
+                            "{} has column {} as {:?}, but the table reads it as {:?}",
+                            file.path, field.name, file_type, table_type
+                        ),
+                    }),
+                }
+            })
+            .collect::<Result<Vec<Option<usize>>, QueryError>>()?;
+
+        // Where each projected column comes from.
+        enum Origin {
+            File(usize),
+            Missing,
+            Path(usize),
+        }
+        let mut file_projection = Vec::new();
+        let origins: Vec<Origin> = self
+            .projection
+            .iter()
+            .map(|&index| match columns.get(index) {
+                None => Origin::Path(index - self.file_fields.len()),
+                Some(Some(file_index)) => {
+                    file_projection.push(*file_index);
+                    Origin::File(file_projection.len() - 1)
+                },
+                Some(None) => Origin::Missing,
+            })
+            .collect();
+        // A batch without columns has no row count, so read one column anyway.
+        if file_projection.is_empty() {
+            file_projection.push(0);
+        }
+        // A filter goes to the file when it reads only columns the file has
+        // with the table's type, and the file can apply it.
+        let file_filters: Vec<Expression> = self
+            .filters
+            .iter()
+            .filter(|filter| {
+                let mut names = HashSet::new();
+                filter.collect_columns(&mut names);
+                names.iter().all(|name| {
+                    self.file_fields.iter().zip(&columns).any(|(field, column)| {
+                        field.name == **name
+                            && column.is_some_and(|index| file_schema.fields[index].field_type == field.field_type)
+                    })
+                }) && source.supports_filter(filter) != FilterSupport::Unsupported
+            })
+            .cloned()
+            .collect();
+
+        let schema = self.schema.clone();
+        let path_values = file.path_values;
+        let batches = source.scan(&self.config, Some(&file_projection), &file_filters);
+        Ok(Box::new(batches.map(move |batch| {
+            let batch = batch?;
+            let row_count = batch.columns.first().map_or(0, |column| column.values.len());
+            let columns = origins
+                .iter()
+                .zip(&schema.fields)
+                .map(|(origin, field)| match origin {
+                    Origin::File(i) => ColumnVector::new(
+                        batch.columns[*i]
+                            .values
+                            .iter()
+                            .map(|value| match value {
+                                ScalarValue::Int32(v) if field.field_type == DataType::Float64 => {
+                                    ScalarValue::Float64(v.map(f64::from))
+                                },
+                                value => value.clone(),
+                            })
+                            .collect(),
+                    ),
+                    Origin::Missing => ColumnVector::new(vec![ScalarValue::null(field.field_type); row_count]),
+                    Origin::Path(i) => ColumnVector::new(vec![path_values[*i].clone(); row_count]),
+                })
+                .collect();
+            RecordBatch::new(schema.clone(), columns)
+        })))
+    }
+}
+
+/// The directory the listed paths of `location` start with: the directory
+/// itself, or the part of a glob pattern before its first wildcard.
+fn listing_base(location: &str) -> PathBuf {
+    if !has_glob_chars(location) {
+        return PathBuf::from(location);
+    }
+    let literal: Vec<&str> = location.split('/').take_while(|component| !has_glob_chars(component)).collect();
+    match literal.join("/") {
+        base if base.is_empty() && location.starts_with('/') => PathBuf::from("/"),
+        base => PathBuf::from(base),
+    }
+}
+
+/// The `key=value` directories of a path relative to the listing base, in order.
+fn partition_values(relative: &Path) -> Vec<(String, String)> {
+    let Some(dir) = relative.parent() else {
+        return Vec::new();
+    };
+    dir.components()
+        .filter_map(|component| {
+            let component = component.as_os_str().to_string_lossy();
+            let (key, value) = component.split_once('=')?;
+            (!key.is_empty()).then(|| (key.to_string(), value.to_string()))
+        })
+        .collect()
+}
+
+/// The narrowest type that holds every partition value: `Int32`, `Float64`,
+/// `Bool`, or `String`. Keys without any value are `String`.
+fn partition_type<'a>(mut texts: impl Iterator<Item = &'a str> + Clone) -> DataType {
+    if texts.clone().next().is_none() {
+        DataType::String
+    } else if texts.clone().all(|text| text.parse::<i32>().is_ok()) {
+        DataType::Int32
+    } else if texts.clone().all(|text| text.parse::<f64>().is_ok()) {
+        DataType::Float64
+    } else if texts.all(|text| text.to_lowercase().parse::<bool>().is_ok()) {
+        DataType::Bool
+    } else {
+        DataType::String
+    }
+}
+
+fn parse_partition_value(text: &str, field_type: DataType) -> ScalarValue {
+    match field_type {
+        DataType::Int32 => ScalarValue::Int32(text.parse().ok()),
+        DataType::Float64 => ScalarValue::Float64(text.parse().ok()),
+        DataType::Bool => ScalarValue::Bool(text.to_lowercase().parse().ok()),
+        DataType::String => ScalarValue::String(Some(text.to_string())),
+    }
+}
+
+impl DataSource for ListingTable {
+    fn schema(&self) -> &Schema {
+        &self.schema
+    }
+
+    /// Filters on partition columns and `_file_name` skip whole files. Other
+    /// filters on file columns go to the files that can apply them, which are
+    /// not known before they are opened, so they are inexact.
+    fn supports_filter(&self, filter: &Expression) -> FilterSupport {
+        if self.reads_only_path_columns(filter) {
+            FilterSupport::Exact
+        } else if self.reads_only_file_columns(filter) {
+            FilterSupport::Inexact
+        } else {
+            FilterSupport::Unsupported
+        }
+    }
+
+    fn scan(
+        &self,
+        config: &SessionConfig,
+        projection: Option<&[usize]>,
+        filters: &[Expression],
+    ) -> Box<dyn Iterator<Item = Result<RecordBatch, QueryError>>> {
+        let projection = projection.map_or_else(|| (0..self.schema.fields.len()).collect(), <[usize]>::to_vec);
+        let keep = match self.files_to_scan(filters) {
+            Ok(keep) => keep,
+            Err(e) => return Box::new(iter::once(Err(e))),
+        };
+        self.pruned_file_count.set(keep.iter().filter(|&&keep| !keep).count());
+
+        let files: Vec<ListedFile> = self.files.iter().zip(keep).filter(|(_, keep)| *keep).map(|(file, _)| file.clone()).collect();
+        let scan = FileScan {
+            open: self.open.clone(),
+            file_fields: self.schema.fields[..self.file_column_count].to_vec(),
+            schema: self.schema.project(&projection),
+            projection,
+            filters: filters.iter().filter(|filter| !self.reads_only_path_columns(filter)).cloned().collect(),
+            config: config.clone(),
+        };
+        Box::new(files.into_iter().flat_map(move |file| match scan.scan(file) {
+            Ok(batches) => batches,
+            Err(e) => Box::new(iter::once(Err(e))),
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::RefCell, fs};
+
+    use crate::datasource::csv::CsvDataSource;
+    use crate::logical_plan::plan::Catalog;
+    use crate::test_util::{float, int, query, rows, temp_dir, text};
+
+    use super::*;
+
+    /// A directory for test `test` holding `files`, given as paths relative to it and their contents.
+    fn directory(test: &str, files: &[(&str, &str)]) -> PathBuf {
+        let dir = temp_dir(test);
+        for (path, contents) in files {
+            let path = dir.join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, contents).unwrap();
+        }
+        dir
+    }
+
+    fn csv_listing(location: &str) -> Result<ListingTable, QueryError> {
+        ListingTable::try_new(location, &["csv".to_string()], None, |path| Ok(Rc::new(CsvDataSource::new(path)?) as Rc<dyn DataSource>))
+    }
+
+    /// A catalog with table `t` over the CSV files at `location`, and the table itself.
+    fn listing(location: &str) -> (Catalog, Rc<ListingTable>) {
+        let table = Rc::new(csv_listing(location).unwrap());
+        let mut catalog = Catalog::new();
+        catalog.register_table("t".to_string(), table.clone());
+        (catalog, table)
+    }
+
+    /// The paths of `paths` relative to `dir`.
+    fn relative(dir: &Path, paths: Vec<PathBuf>) -> Vec<String> {
+        paths.iter().map(|path| path.strip_prefix(dir).unwrap().to_string_lossy().into_owned()).collect()
+    }
+
+    #[test]
+    fn glob_patterns_match_one_path_component() {
+        assert!(glob_match("*.csv", "day.csv"));
+        assert!(!glob_match("*.csv", "day.csv.gz"));
+        assert!(glob_match("day-?[0-9].csv", "day-a7.csv"));
+        assert!(!glob_match("day-?[0-9].csv", "day-7a.csv"));
+        assert!(glob_match("[!_]*", "part"));
+        assert!(!glob_match("[!_]*", "_part"));
+        assert!(glob_match("[a-c]x*", "bx"));
+    }
+
+    #[test]
+    fn listings_skip_hidden_files_and_other_extensions() {
+        let dir = directory("listing-files", &[
+            ("b.csv", "id\n1\n"),
+            ("a.csv", "id\n1\n"),
+            ("sub/c.csv", "id\n1\n"),
+            ("sub/deeper/d.CSV", "id\n1\n"),
+            (".hidden.csv", "id\n1\n"),
+            ("_SUCCESS", ""),
+            ("notes.json", "{}\n"),
+        ]);
+        let csv = ["csv".to_string()];
+        let base = dir.to_str().unwrap();
+        assert_eq!(relative(&dir, list_files(base, &csv).unwrap()), ["a.csv", "b.csv", "sub/c.csv", "sub/deeper/d.CSV"]);
+        assert_eq!(relative(&dir, list_files(&format!("{}/*.csv", base), &csv).unwrap()), ["a.csv", "b.csv"]);
+        assert_eq!(relative(&dir, list_files(&format!("{}/**/[c-d].*", base), &csv).unwrap()), ["sub/c.csv", "sub/deeper/d.CSV"]);
+        assert_eq!(relative(&dir, list_files(base, &[]).unwrap()), ["a.csv", "b.csv", "notes.json", "sub/c.csv", "sub/deeper/d.CSV"]);
+    }
+
+    #[test]
+    fn files_are_read_as_one_table_with_columns_matched_by_name() {
+        let dir = directory("listing-union", &[("f1.csv", "id,x,y\n1,2.5,z\n"), ("f2.csv", "w,x,id\nv,2,2\n")]);
+        let (mut catalog, table) = listing(dir.to_str().unwrap());
+        assert_eq!(table.file_paths().len(), 2);
+        // `x` is Float64 in the first file and Int32 in the second, `y` is
+        // missing from the second, and `w` is not a column of the table.
+        let columns: Vec<String> = table.schema().fields.iter().map(|field| field.name.clone()).collect();
+        assert_eq!(columns, ["id", "x", "y", FILE_NAME_COLUMN]);
+        assert_eq!(rows(&mut catalog, "SELECT id, x, y FROM t"), vec![
+            vec![int(1), float(2.5), text("z")],
+            vec![int(2), float(2.0), ScalarValue::String(None)],
+        ]);
+        let file_names = rows(&mut catalog, "SELECT _file_name FROM t WHERE id = 2");
+        assert_eq!(file_names, vec![vec![text(&dir.join("f2.csv").to_string_lossy())]]);
+    }
+
+    #[test]
+    fn files_that_disagree_on_a_type_are_an_error() {
+        let dir = directory("listing-conflict", &[("a.csv", "id\n1\n"), ("b.csv", "id\nabc\n")]);
+        let (mut catalog, _) = listing(dir.to_str().unwrap());
+        let Err(QueryError::DataSourceError { message }) = query(&mut catalog, "SELECT id FROM t") else {
+            panic!("files with different types were read as one table");
+        };
+        assert_eq!(message, format!("{} has column id as String, but the table reads it as Int32", dir.join("b.csv").display()));
+    }
+
+    /// Three files partitioned by `year` and `month`, one of them with a NULL year.
+    fn partitioned(test: &str) -> PathBuf {
+        directory(test, &[
+            ("year=2024/month=1/part.csv", "id\n1\n"),
+            ("year=2025/month=2/part.csv", "id\n2\n"),
+            ("year=__HIVE_DEFAULT_PARTITION__/month=3/part.csv", "id\n3\n"),
+        ])
+    }
+
+    #[test]
+    fn partition_directories_become_typed_columns() {
+        let dir = partitioned("listing-partitions");
+        let (mut catalog, _) = listing(dir.to_str().unwrap());
+        let fields: Vec<(String, DataType, bool)> =
+            catalog.get_source("t").unwrap().schema().fields.iter().map(|f| (f.name.clone(), f.field_type, f.is_nullable)).collect();
+        assert_eq!(fields, [
+            ("id".to_string(), DataType::Int32, true),
+            ("year".to_string(), DataType::Int32, true),
+            ("month".to_string(), DataType::Int32, false),
+            (FILE_NAME_COLUMN.to_string(), DataType::String, false),
+        ]);
+        assert_eq!(rows(&mut catalog, "SELECT id, year, month FROM t"), vec![
+            vec![int(1), int(2024), int(1)],
+            vec![int(2), int(2025), int(2)],
+            vec![int(3), ScalarValue::Int32(None), int(3)],
+        ]);
+    }
+
+    #[test]
+    fn filters_on_path_columns_skip_whole_files() {
+        let dir = partitioned("listing-pruning");
+        let (mut catalog, table) = listing(dir.to_str().unwrap());
+        assert_eq!(rows(&mut catalog, "SELECT id FROM t WHERE year = 2025"), vec![vec![int(2)]]);
+        assert_eq!(table.pruned_file_count(), 2);
+        assert_eq!(rows(&mut catalog, "SELECT id FROM t WHERE month >= 2"), vec![vec![int(2)], vec![int(3)]]);
+        assert_eq!(table.pruned_file_count(), 1);
+        let file_name = dir.join("year=2024/month=1/part.csv").to_string_lossy().into_owned();
+        assert_eq!(rows(&mut catalog, &format!("SELECT id FROM t WHERE _file_name = '{}'", file_name)), vec![vec![int(1)]]);
+        assert_eq!(table.pruned_file_count(), 2);
+        // Filters on file columns open every file.
+        assert_eq!(rows(&mut catalog, "SELECT year FROM t WHERE id = 1"), vec![vec![int(2024)]]);
+        assert_eq!(table.pruned_file_count(), 0);
+    }
+
+    #[test]
+    fn only_the_files_a_scan_reads_are_opened() {
+        let dir = partitioned("listing-lazy");
+        let opened = Rc::new(RefCell::new(Vec::new()));
+        let open = |schema: Option<Schema>| {
+            let (opened, base) = (opened.clone(), dir.clone());
+            let table = ListingTable::try_new(dir.to_str().unwrap(), &[], schema, move |path| {
+                opened.borrow_mut().push(Path::new(&path).strip_prefix(&base).unwrap().to_string_lossy().into_owned());
+                Ok(Rc::new(CsvDataSource::new(path)?) as Rc<dyn DataSource>)
+            });
+            let mut catalog = Catalog::new();
+            catalog.register_table("t".to_string(), Rc::new(table.unwrap()));
+            catalog
+        };
+
+        // The schema comes from the first file, and the filter opens one more.
+        let mut catalog = open(None);
+        assert_eq!(*opened.borrow(), ["year=2024/month=1/part.csv"]);
+        assert_eq!(rows(&mut catalog, "SELECT id FROM t WHERE year = 2025"), vec![vec![int(2)]]);
+        assert_eq!(*opened.borrow(), ["year=2024/month=1/part.csv", "year=2025/month=2/part.csv"]);
+
+        opened.borrow_mut().clear();
+        let schema = Schema::new(vec![Field { name: "id".to_string(), field_type: DataType::Float64, is_nullable: true }]);
+        let mut catalog = open(Some(schema));
+        assert!(opened.borrow().is_empty());
+        assert_eq!(rows(&mut catalog, "SELECT id FROM t WHERE month = 3"), vec![vec![float(3.0)]]);
+        assert_eq!(*opened.borrow(), ["year=__HIVE_DEFAULT_PARTITION__/month=3/part.csv"]);
+    }
+
+    #[test]
+    fn every_file_must_have_the_same_partition_keys() {
+        let dir = directory("listing-keys", &[("a=1/x.csv", "id\n1\n"), ("b=2/x.csv", "id\n1\n")]);
+        let Err(QueryError::DataSourceError { message }) = csv_listing(dir.to_str().unwrap()) else {
+            panic!("files with different partition keys were read as one table");
+        };
+        assert_eq!(message, format!(
+            "{} does not have the partition keys [\"a\"] of {}",
+            dir.join("b=2/x.csv").display(),
+            dir.join("a=1/x.csv").display(),
+        ));
+    }
+}
@@ -0,0 +1,159 @@
+use std::{cell::RefCell, rc::Rc};
+
+use crate::{ColumnVector, DataSource, RecordBatch, ScalarValue, Schema, SessionConfig, errors::QueryError, logical_plan::plan::Expression};
+
+/// A table held in memory as a list of record batches, for small lookup tables
+/// and for data built by the program itself rather than read from a file.
+///
+/// The rows can be changed with `INSERT`, `UPDATE` and `DELETE`. A scan reads
+/// the rows as they were when it started, even if they change while it runs.
+#[derive(Debug)]
+pub struct MemTable {
+    schema: Schema,
+    batches: RefCell<Rc<Vec<RecordBatch>>>,
+}
+
+impl MemTable {
+    /// Creates a table over `batches`, checking that every batch has one column
+    /// per schema field and that each value has the field's type and nullability.
+    pub fn try_new(schema: Schema, batches: Vec<RecordBatch>) -> Result<Self, QueryError> {
+        check_batches(&schema, &batches)?;
+        Ok(Self { schema, batches: RefCell::new(Rc::new(batches)) })
+    }
+
+    /// The current rows.
+    pub fn batches(&self) -> Rc<Vec<RecordBatch>> {
+        self.batches.borrow().clone()
+    }
+}
+
+/// Checks that every batch has one column per schema field and that each value
+/// has the field's type and nullability.
+fn check_batches(schema: &Schema, batches: &[RecordBatch]) -> Result<(), QueryError> {
+    for batch in batches {
+        if batch.columns.len() != schema.fields.len() {
+            return Err(QueryError::SchemaCountMismatch {
+                expected: schema.fields.len(),
+                actual: batch.columns.len(),
+            });
+        }
+        for (field, column) in schema.fields.iter().zip(&batch.columns) {
+            let fits = |value: &ScalarValue| match value.is_null() {
+                true => field.is_nullable,
+                false => value.data_type() == field.field_type,
+            };
+            if let Some(value) = column.values.iter().find(|value| !fits(value)) {
+                return Err(QueryError::ValidationError {
+                    message: format!(
+                        "Value {:?} does not fit column {} of type {:?}{}",
+                        value,
+                        field.name,
+                        field.field_type,
+                        if field.is_nullable { "" } else { " NOT NULL" },
+                    ),
+                });
+            }
+        }
+    }
+    Ok(())
+}
+
+impl DataSource for MemTable {
+    fn schema(&self) -> &Schema {
+        &self.schema
+    }
+
+    fn scan(
+        &self,
+        config: &SessionConfig,
+        projection: Option<&[usize]>,
+        _filters: &[Expression],
+    ) -> Box<dyn Iterator<Item = Result<RecordBatch, QueryError>>> {
+        let projection = projection.map_or_else(|| (0..self.schema.fields.len()).collect(), <[usize]>::to_vec);
+        let schema = self.schema.project(&projection);
+        let batch_size = config.batch_size();
+        let batches = self.batches();
+
+        // Stored batches larger than the batch size are handed out in slices.
+        let slices = (0..batches.len()).flat_map(move |index| {
+            let row_count = batches[index].columns.first().map_or(0, |column| column.values.len());
+            (0..row_count).step_by(batch_size).map(move |start| (index, start, (start + batch_size).min(row_count)))
+        });
+        let batches = self.batches();
+        Box::new(slices.map(move |(index, start, end)| {
+            let columns = projection
+                .iter()
+                .map(|&i| ColumnVector::new(batches[index].columns[i].values[start..end].to_vec()))
+                .collect();
+            RecordBatch::new(schema.clone(), columns)
+        }))
+    }
+
+    fn is_writable(&self) -> bool {
+        true
+    }
+
+    fn insert(&self, batches: Vec<RecordBatch>) -> Result<(), QueryError> {
+        check_batches(&self.schema, &batches)?;
+        Rc::make_mut(&mut self.batches.borrow_mut()).extend(batches);
+        Ok(())
+    }
+
+    fn overwrite(&self, batches: Vec<RecordBatch>) -> Result<(), QueryError> {
+        check_batches(&self.schema, &batches)?;
+        *self.batches.borrow_mut() = Rc::new(batches);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{DataType, Field};
+
+    use super::*;
+
+    fn table(values: &[i32]) -> MemTable {
+        let schema = Schema::new(vec![Field { name: "n".to_string(), field_type: DataType::Int32, is_nullable: false }]);
+        let column = ColumnVector::new(values.iter().map(|&n| ScalarValue::Int32(Some(n))).collect());
+        MemTable::try_new(schema.clone(), vec![RecordBatch::new(schema, vec![column]).unwrap()]).unwrap()
+    }
+
+    fn scan_values(scan: Box<dyn Iterator<Item = Result<RecordBatch, QueryError>>>) -> Vec<Vec<ScalarValue>> {
+        scan.map(|batch| batch.unwrap().columns.remove(0).values).collect()
+    }
+
+    #[test]
+    fn values_must_fit_the_schema() {
+        let table = table(&[1]);
+        let schema = table.schema().clone();
+        let batch = |value| RecordBatch::new(schema.clone(), vec![ColumnVector::new(vec![value])]).unwrap();
+        let Err(QueryError::ValidationError { message }) = table.insert(vec![batch(ScalarValue::Int32(None))]) else {
+            panic!("a NULL was inserted into a NOT NULL column");
+        };
+        assert_eq!(message, "Value Int32(None) does not fit column n of type Int32 NOT NULL");
+        assert!(table.insert(vec![batch(ScalarValue::String(Some("x".to_string())))]).is_err());
+        assert!(matches!(
+            table.insert(vec![RecordBatch::new(Schema::new(Vec::new()), Vec::new()).unwrap()]),
+            Err(QueryError::SchemaCountMismatch { expected: 1, actual: 0 })
+        ));
+        assert_eq!(table.batches().len(), 1);
+    }
+
+    #[test]
+    fn scans_slice_batches_and_see_the_rows_they_started_with() {
+        let table = table(&[1, 2, 3, 4, 5]);
+        let scan = table.scan(&SessionConfig::new().with_batch_size(2), None, &[]);
+        table.overwrite(Vec::new()).unwrap();
+        let int = |n| ScalarValue::Int32(Some(n));
+        assert_eq!(scan_values(scan), vec![vec![int(1), int(2)], vec![int(3), int(4)], vec![int(5)]]);
+        assert_eq!(scan_values(table.scan(&SessionConfig::new(), None, &[])), Vec::<Vec<ScalarValue>>::new());
+    }
+
+    #[test]
+    fn a_zero_batch_size_hands_out_one_row_per_batch() {
+        let config = SessionConfig::new().with_batch_size(0);
+        assert_eq!(config.batch_size(), 1);
+        let int = |n| ScalarValue::Int32(Some(n));
+        assert_eq!(scan_values(table(&[1, 2, 3]).scan(&config, None, &[])), vec![vec![int(1)], vec![int(2)], vec![int(3)]]);
+    }
+}
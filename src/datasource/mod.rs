@@ -1,11 +1,62 @@
-use crate::{RecordBatch, Schema, errors::QueryError};
+use crate::{RecordBatch, Schema, SessionConfig, errors::QueryError, logical_plan::plan::Expression};
+
+/// How well a data source can apply a filter predicate while scanning.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FilterSupport {
+    /// The source returns exactly the rows the predicate accepts.
+    Exact,
+    /// The source may return rows the predicate rejects, so it must be applied again.
+    Inexact,
+    /// The source ignores the predicate.
+    Unsupported,
+}
 
 pub trait DataSource {
     /// The schema method that returns the schema of every data source
     fn schema(&self) -> &Schema;
 
-    /// The scan method that returns the record batch if the data source is scanned successfully or a QueryError
-    fn scan(&self) -> Box<dyn Iterator<Item = Result<RecordBatch, QueryError>>>;
+    /// The scan method that returns the record batch if the data source is scanned successfully or a QueryError.
+    /// `projection` lists the indexes of the schema columns to return, in order; `None` returns all of them.
+    /// `filters` are predicates over the schema columns that `supports_filter` did not reject.
+    /// Batches should hold `config.batch_size()` rows, except for the last one.
+    fn scan(
+        &self,
+        config: &SessionConfig,
+        projection: Option<&[usize]>,
+        filters: &[Expression],
+    ) -> Box<dyn Iterator<Item = Result<RecordBatch, QueryError>>>;
+
+    /// Whether `scan` can apply `filter`. Filters may read columns left out of the projection.
+    fn supports_filter(&self, _filter: &Expression) -> FilterSupport {
+        FilterSupport::Unsupported
+    }
+
+    /// Whether `insert` and `overwrite` can change the rows of this source.
+    fn is_writable(&self) -> bool {
+        false
+    }
+
+    /// Appends `batches`, whose columns already match the schema, for `INSERT`.
+    fn insert(&self, _batches: Vec<RecordBatch>) -> Result<(), QueryError> {
+        Err(QueryError::ValidationError {
+            message: "Only in-memory tables can be changed".to_string(),
+        })
+    }
+
+    /// Replaces every row with those in `batches`, for `UPDATE` and `DELETE`.
+    fn overwrite(&self, _batches: Vec<RecordBatch>) -> Result<(), QueryError> {
+        Err(QueryError::ValidationError {
+            message: "Only in-memory tables can be changed".to_string(),
+        })
+    }
 }
 
-pub mod csv;
\ No newline at end of file
+pub mod compression;
+pub mod csv;
+pub mod csv_parser;
+pub(crate) mod filter;
+pub mod json;
+pub mod listing;
+pub mod memory;
+pub mod parquet;
+pub mod rejected;
\ No newline at end of file
@@ -0,0 +1,413 @@
+use std::{cell::Cell, collections::{HashMap, VecDeque}, fs::File, iter, mem, path::{Path, PathBuf}, rc::Rc, sync::Arc};
+
+use ::parquet::{
+    basic::{ConvertedType, LogicalType, Repetition, Type as PhysicalType},
+    errors::ParquetError,
+    file::{
+        metadata::RowGroupMetaData,
+        reader::{FileReader, SerializedFileReader},
+        statistics::Statistics,
+    },
+    record::Field as ParquetField,
+    schema::types::Type,
+};
+
+use crate::{
+    ColumnVector, DataSource, DataType, Field, RecordBatch, ScalarValue, Schema, SessionConfig,
+    errors::QueryError,
+    logical_plan::plan::Expression,
+    physical_plan::eval::cast_scalar,
+};
+
+use super::FilterSupport;
+use super::filter::ColumnComparison;
+
+/// How one top-level Parquet column is read.
+#[derive(Debug)]
+struct ParquetColumn {
+    /// The column as Parquet describes it, used to ask the reader for it.
+    parquet_type: Arc<Type>,
+    /// Index of the column chunk holding this column's min/max statistics, when
+    /// they can be compared with values of the mapped `DataType`.
+    statistics_column: Option<usize>,
+}
+
+/// Reads a Parquet file one row group at a time.
+///
+/// Physical and logical types are mapped onto `DataType` like this:
+///
+/// - `BOOLEAN` is `Bool`.
+/// - `INT32`, and the signed integer types stored in it, are `Int32`.
+/// - `INT64`, unsigned 32 and 64 bit integers, `FLOAT`, `DOUBLE` and decimals
+///   are `Float64`, since `Int32` cannot hold all of their values.
+/// - Strings, enums and JSON are `String`, and so are dates, times,
+///   timestamps, binary values and nested columns, as their text form.
+///
+/// Only the column chunks of projected columns are read. Comparisons between a
+/// column and a literal are checked against the min/max statistics of each row
+/// group, and row groups that cannot hold a matching row are skipped. Rows of
+/// the row groups that are read are not filtered, so such filters are inexact.
+#[derive(Debug)]
+pub struct ParquetDataSource {
+    file_path: PathBuf,
+    schema: Schema,
+    columns: Vec<ParquetColumn>,
+    pruned_row_groups: Rc<Cell<usize>>,
+}
+
+impl ParquetDataSource {
+    pub fn new(file_path: String) -> Result<Self, QueryError> {
+        let path = Path::new(&file_path);
+        let reader = open_reader(path)?;
+        let schema_descr = reader.metadata().file_metadata().schema_descr_ptr();
+
+        let mut fields = Vec::new();
+        let mut columns = Vec::new();
+        for parquet_type in schema_descr.root_schema().get_fields() {
+            let (field_type, has_statistics) = map_type(parquet_type);
+            let name = parquet_type.name().to_string();
+            let statistics_column = schema_descr
+                .columns()
+                .iter()
+                .position(|column| column.path().parts() == [name.as_str()])
+                .filter(|_| has_statistics);
+            fields.push(Field {
+                name,
+                field_type,
+                is_nullable: parquet_type.get_basic_info().repetition() != Repetition::REQUIRED,
+            });
+            columns.push(ParquetColumn { parquet_type: parquet_type.clone(), statistics_column });
+        }
+
+        Ok(Self {
+            file_path: path.to_path_buf(),
+            schema: Schema::new(fields),
+            columns,
+            pruned_row_groups: Rc::new(Cell::new(0)),
+        })
+    }
+
+    /// How many row groups the latest scan skipped because of their statistics.
+    pub fn pruned_row_group_count(&self) -> usize {
+        self.pruned_row_groups.get()
+    }
+
+    /// Whether the statistics of a row group show that no row in it passes every filter.
+    fn can_skip(&self, row_group: &RowGroupMetaData, filters: &[ColumnComparison]) -> bool {
+        filters.iter().any(|filter| {
+            let Some(chunk) = self.columns[filter.column].statistics_column else {
+                return false;
+            };
+            let Some(statistics) = row_group.column(chunk).statistics() else {
+                return false;
+            };
+            // A chunk of nothing but NULLs has no row that passes a comparison.
+            if statistics.null_count_opt().is_some_and(|nulls| nulls as i64 == row_group.num_rows()) {
+                return true;
+            }
+            match statistics_range(statistics) {
+                Some((min, max)) => !filter.may_match_range(&min, &max),
+                None => false,
+            }
+        })
+    }
+}
+
+fn open_reader(path: &Path) -> Result<SerializedFileReader<File>, QueryError> {
+    let file = File::open(path).map_err(|e| QueryError::DataSourceError {
+        message: format!("Failed to open file: {}", e),
+    })?;
+    SerializedFileReader::new(file).map_err(parquet_error)
+}
+
+fn parquet_error(e: ParquetError) -> QueryError {
+    QueryError::DataSourceError {
+        message: format!("Failed to read Parquet file: {}", e),
+    }
+}
+
+/// Maps a Parquet column onto a `DataType`, and tells whether its min/max
+/// statistics order values the same way as the mapped type does.
+fn map_type(parquet_type: &Type) -> (DataType, bool) {
+    if !parquet_type.is_primitive() || parquet_type.get_basic_info().repetition() == Repetition::REPEATED {
+        return (DataType::String, false);
+    }
+    let info = parquet_type.get_basic_info();
+    let logical = info.logical_type();
+    let converted = info.converted_type();
+    let is_decimal = matches!(logical, Some(LogicalType::Decimal { .. })) || converted == ConvertedType::DECIMAL;
+    let is_unsigned = matches!(logical, Some(LogicalType::Integer { is_signed: false, .. }))
+        || matches!(converted, ConvertedType::UINT_8 | ConvertedType::UINT_16 | ConvertedType::UINT_32 | ConvertedType::UINT_64);
+    let is_temporal = matches!(logical, Some(LogicalType::Date | LogicalType::Time { .. } | LogicalType::Timestamp { .. }))
+        || matches!(
+            converted,
+            ConvertedType::DATE
+                | ConvertedType::TIME_MILLIS
+                | ConvertedType::TIME_MICROS
+                | ConvertedType::TIMESTAMP_MILLIS
+                | ConvertedType::TIMESTAMP_MICROS
+        );
+
+    match parquet_type.get_physical_type() {
+        _ if is_temporal => (DataType::String, false),
+        _ if is_decimal => (DataType::Float64, false),
+        PhysicalType::BOOLEAN => (DataType::Bool, true),
+        PhysicalType::INT32 if is_unsigned => (DataType::Float64, false),
+        PhysicalType::INT32 => (DataType::Int32, true),
+        PhysicalType::INT64 => (DataType::Float64, !is_unsigned),
+        PhysicalType::FLOAT | PhysicalType::DOUBLE => (DataType::Float64, true),
+        PhysicalType::BYTE_ARRAY => (DataType::String, true),
+        PhysicalType::INT96 | PhysicalType::FIXED_LEN_BYTE_ARRAY => (DataType::String, false),
+    }
+}
+
+/// The min and max values in a column chunk's statistics, as values of the mapped `DataType`.
+fn statistics_range(statistics: &Statistics) -> Option<(ScalarValue, ScalarValue)> {
+    fn range<T>(min: Option<&T>, max: Option<&T>, to_scalar: impl Fn(&T) -> Option<ScalarValue>) -> Option<(ScalarValue, ScalarValue)> {
+        Some((to_scalar(min?)?, to_scalar(max?)?))
+    }
+    match statistics {
+        Statistics::Boolean(s) => range(s.min_opt(), s.max_opt(), |v| Some(ScalarValue::Bool(Some(*v)))),
+        Statistics::Int32(s) => range(s.min_opt(), s.max_opt(), |v| Some(ScalarValue::Int32(Some(*v)))),
+        // Values are read as `Float64`, and beyond 2^53 several of them round to
+        // the same float, so such statistics are not used.
+        Statistics::Int64(s) => range(s.min_opt(), s.max_opt(), |v| {
+            (v.unsigned_abs() <= 1 << f64::MANTISSA_DIGITS).then_some(ScalarValue::Float64(Some(*v as f64)))
+        }),
+        Statistics::Float(s) => range(s.min_opt(), s.max_opt(), |v| Some(ScalarValue::Float64(Some(f64::from(*v))))),
+        Statistics::Double(s) => range(s.min_opt(), s.max_opt(), |v| Some(ScalarValue::Float64(Some(*v)))),
+        Statistics::ByteArray(s) => range(s.min_opt(), s.max_opt(), |v| {
+            v.as_utf8().ok().map(|text| ScalarValue::String(Some(text.to_string())))
+        }),
+        Statistics::Int96(_) | Statistics::FixedLenByteArray(_) => None,
+    }
+}
+
+/// Reads one Parquet value as a value of its column's type.
+fn to_scalar(value: &ParquetField, field: &Field) -> Result<ScalarValue, QueryError> {
+    let scalar = match value {
+        ParquetField::Null => return Ok(ScalarValue::null(field.field_type)),
+        ParquetField::Bool(v) => ScalarValue::Bool(Some(*v)),
+        ParquetField::Byte(v) => ScalarValue::Int32(Some(i32::from(*v))),
+        ParquetField::Short(v) => ScalarValue::Int32(Some(i32::from(*v))),
+        ParquetField::Int(v) => ScalarValue::Int32(Some(*v)),
+        ParquetField::UByte(v) => ScalarValue::Int32(Some(i32::from(*v))),
+        ParquetField::UShort(v) => ScalarValue::Int32(Some(i32::from(*v))),
+        ParquetField::Long(v) => ScalarValue::Float64(Some(*v as f64)),
+        ParquetField::UInt(v) => ScalarValue::Float64(Some(f64::from(*v))),
+        ParquetField::ULong(v) => ScalarValue::Float64(Some(*v as f64)),
+        ParquetField::Float16(v) => ScalarValue::Float64(Some(f64::from(*v))),
+        ParquetField::Float(v) => ScalarValue::Float64(Some(f64::from(*v))),
+        ParquetField::Double(v) => ScalarValue::Float64(Some(*v)),
+        ParquetField::Decimal(_) => ScalarValue::Float64(value.to_string().parse::<f64>().ok()),
+        ParquetField::Str(v) => ScalarValue::String(Some(v.clone())),
+        ParquetField::Bytes(v) => ScalarValue::String(Some(String::from_utf8_lossy(v.data()).into_owned())),
+        ParquetField::Date(_)
+        | ParquetField::TimestampMillis(_)
+        | ParquetField::TimestampMicros(_)
+        | ParquetField::Group(_)
+        | ParquetField::ListInternal(_)
+        | ParquetField::MapInternal(_) => ScalarValue::String(Some(value.to_string())),
+    };
+    match (scalar.data_type(), field.field_type) {
+        (actual, expected) if actual == expected => Ok(scalar),
+        (DataType::Int32, DataType::Float64) => Ok(cast_scalar(&scalar, DataType::Float64)),
+        (_, expected) => Err(QueryError::DataSourceError {
+            message: format!("Parquet value {} in column {} is not a {:?}", value, field.name, expected),
+        }),
+    }
+}
+
+/// Reads the row groups that were not pruned, one at a time, and hands their
+/// rows out in batches of at most `batch_size`.
+struct ParquetBatchIterator {
+    reader: SerializedFileReader<File>,
+    row_groups: VecDeque<usize>,
+    /// The projected columns, as one Parquet group type to hand to the row reader.
+    projection: Type,
+    schema: Schema,
+    batch_size: usize,
+    batches: VecDeque<RecordBatch>,
+    finished: bool,
+}
+
+impl ParquetBatchIterator {
+    fn read_row_group(&mut self, index: usize) -> Result<(), QueryError> {
+        let row_group = self.reader.get_row_group(index).map_err(parquet_error)?;
+        let rows = row_group.get_row_iter(Some(self.projection.clone())).map_err(parquet_error)?;
+        let positions: HashMap<&str, usize> =
+            self.schema.fields.iter().enumerate().map(|(i, field)| (field.name.as_str(), i)).collect();
+
+        let mut columns: Vec<Vec<ScalarValue>> = vec![Vec::new(); self.schema.fields.len()];
+        for row in rows {
+            let row = row.map_err(parquet_error)?;
+            for (name, value) in row.get_column_iter() {
+                if let Some(&i) = positions.get(name.as_str()) {
+                    columns[i].push(to_scalar(value, &self.schema.fields[i])?);
+                }
+            }
+            if columns.first().is_some_and(|column| column.len() == self.batch_size) {
+                let full = columns.iter_mut().map(|column| ColumnVector::new(mem::take(column))).collect();
+                self.batches.push_back(RecordBatch::new(self.schema.clone(), full)?);
+            }
+        }
+        if columns.first().is_some_and(|column| !column.is_empty()) {
+            let rest = columns.into_iter().map(ColumnVector::new).collect();
+            self.batches.push_back(RecordBatch::new(self.schema.clone(), rest)?);
+        }
+        Ok(())
+    }
+}
+
+impl Iterator for ParquetBatchIterator {
+    type Item = Result<RecordBatch, QueryError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.batches.is_empty() && !self.finished {
+            let Some(index) = self.row_groups.pop_front() else {
+                self.finished = true;
+                break;
+            };
+            if let Err(e) = self.read_row_group(index) {
+                self.finished = true;
+                return Some(Err(e));
+            }
+        }
+        self.batches.pop_front().map(Ok)
+    }
+}
+
+impl DataSource for ParquetDataSource {
+    fn schema(&self) -> &Schema {
+        &self.schema
+    }
+
+    /// Comparisons with a literal on columns that have usable statistics prune
+    /// row groups, but the rows that are read still need filtering.
+    fn supports_filter(&self, filter: &Expression) -> FilterSupport {
+        match ColumnComparison::from_expression(filter, &self.schema) {
+            Some(comparison) if self.columns[comparison.column].statistics_column.is_some() => FilterSupport::Inexact,
+            _ => FilterSupport::Unsupported,
+        }
+    }
+
+    fn scan(
+        &self,
+        config: &SessionConfig,
+        projection: Option<&[usize]>,
+        filters: &[Expression],
+    ) -> Box<dyn Iterator<Item = Result<RecordBatch, QueryError>>> {
+        let reader = match open_reader(&self.file_path) {
+            Ok(reader) => reader,
+            Err(e) => return Box::new(iter::once(Err(e))),
+        };
+        let filters: Vec<ColumnComparison> = filters
+            .iter()
+            .filter_map(|filter| ColumnComparison::from_expression(filter, &self.schema))
+            .collect();
+        let row_groups: VecDeque<usize> = reader
+            .metadata()
+            .row_groups()
+            .iter()
+            .enumerate()
+            .filter(|(_, row_group)| !self.can_skip(row_group, &filters))
+            .map(|(i, _)| i)
+            .collect();
+        self.pruned_row_groups.set(reader.num_row_groups() - row_groups.len());
+
+        let projection = projection.map_or_else(|| (0..self.schema.fields.len()).collect(), <[usize]>::to_vec);
+        // The reader only accepts a projection named like the file's root schema.
+        let root_name = reader.metadata().file_metadata().schema().name().to_string();
+        let parquet_projection = Type::group_type_builder(&root_name)
+            .with_fields(projection.iter().map(|&i| self.columns[i].parquet_type.clone()).collect())
+            .build();
+        let parquet_projection = match parquet_projection {
+            Ok(parquet_projection) => parquet_projection,
+            Err(e) => return Box::new(iter::once(Err(parquet_error(e)))),
+        };
+
+        Box::new(ParquetBatchIterator {
+            reader,
+            row_groups,
+            projection: parquet_projection,
+            schema: self.schema.project(&projection),
+            batch_size: config.batch_size(),
+            batches: VecDeque::new(),
+            finished: false,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ::parquet::{
+        data_type::Int64Type,
+        file::{properties::WriterProperties, writer::SerializedFileWriter},
+        schema::parser::parse_message_type,
+    };
+
+    use crate::logical_plan::plan::Catalog;
+    use crate::test_util::{float, int, rows, temp_dir};
+
+    use super::*;
+
+    /// Registers `path` as table `t`, keeping a handle to the source to ask how
+    /// many row groups it pruned.
+    fn register(path: &str) -> (Catalog, Rc<ParquetDataSource>) {
+        let source = Rc::new(ParquetDataSource::new(path.to_string()).unwrap());
+        let mut catalog = Catalog::new();
+        catalog.register_table("t".to_string(), source.clone());
+        (catalog, source)
+    }
+
+    #[test]
+    fn row_groups_outside_a_comparison_are_skipped() {
+        // test/sales.parquet holds ids 1 to 12 in three row groups of four.
+        let (mut catalog, source) = register("test/sales.parquet");
+        assert_eq!(rows(&mut catalog, "SELECT id FROM t WHERE id > 8"), (9..=12).map(|id| vec![int(id)]).collect::<Vec<_>>());
+        assert_eq!(source.pruned_row_group_count(), 2);
+
+        // `quantity` is INT64, and `amount` has a NULL in the last row group.
+        assert_eq!(rows(&mut catalog, "SELECT id FROM t WHERE quantity <= 6"), vec![vec![int(1)], vec![int(2)]]);
+        assert_eq!(source.pruned_row_group_count(), 2);
+        assert_eq!(rows(&mut catalog, "SELECT id, amount FROM t WHERE amount >= 120"), vec![
+            vec![int(10), float(125.0)],
+            vec![int(12), float(150.0)],
+        ]);
+        assert_eq!(source.pruned_row_group_count(), 2);
+
+        assert_eq!(rows(&mut catalog, "SELECT id FROM t WHERE region = 'central'"), Vec::<Vec<ScalarValue>>::new());
+        assert_eq!(source.pruned_row_group_count(), 3);
+    }
+
+    #[test]
+    fn row_groups_are_read_when_statistics_cannot_rule_them_out() {
+        let (mut catalog, source) = register("test/sales.parquet");
+        assert_eq!(rows(&mut catalog, "SELECT COUNT(*) FROM t WHERE shipped = true"), vec![vec![int(8)]]);
+        assert_eq!(source.pruned_row_group_count(), 0);
+        // Dates are read as text, so their INT32 statistics do not order them.
+        assert_eq!(rows(&mut catalog, "SELECT id FROM t WHERE order_date = '2025-01-13'"), vec![vec![int(12)]]);
+        assert_eq!(source.pruned_row_group_count(), 0);
+    }
+
+    #[test]
+    fn int64_statistics_beyond_float_precision_do_not_prune() {
+        let path = temp_dir("parquet-int64").join("big.parquet");
+        let schema = Arc::new(parse_message_type("message t { REQUIRED INT64 big; }").unwrap());
+        let mut writer = SerializedFileWriter::new(File::create(&path).unwrap(), schema, Arc::new(WriterProperties::builder().build())).unwrap();
+        for values in [[1, 2], [(1 << 60) + 1, (1 << 60) + 3]] {
+            let mut row_group = writer.next_row_group().unwrap();
+            let mut column = row_group.next_column().unwrap().unwrap();
+            column.typed::<Int64Type>().write_batch(&values, None, None).unwrap();
+            column.close().unwrap();
+            row_group.close().unwrap();
+        }
+        writer.close().unwrap();
+
+        let (mut catalog, source) = register(path.to_str().unwrap());
+        assert_eq!(rows(&mut catalog, "SELECT big FROM t WHERE big < 10"), vec![vec![float(1.0)], vec![float(2.0)]]);
+        assert_eq!(source.pruned_row_group_count(), 0);
+        assert_eq!(rows(&mut catalog, "SELECT COUNT(*) FROM t WHERE big > 10"), vec![vec![int(2)]]);
+        assert_eq!(source.pruned_row_group_count(), 1);
+    }
+}
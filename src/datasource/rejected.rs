@@ -0,0 +1,194 @@
+use std::{cell::RefCell, fs::File, io::{BufWriter, Write}, iter, path::PathBuf, rc::Rc};
+
+use crate::{ColumnVector, DataSource, DataType, Field, RecordBatch, ScalarValue, Schema, SessionConfig, errors::QueryError, logical_plan::plan::Expression};
+
+/// A record that a scan could not read as-is, with the line it starts on and why.
+#[derive(Debug, Clone)]
+pub struct RejectedRow {
+    pub line: usize,
+    pub reason: String,
+    pub record: String,
+}
+
+/// Where a scan reports the rows it rejected. Both kinds are cleared when a
+/// new scan of the source starts, so they always describe the latest scan.
+#[derive(Debug, Clone)]
+pub enum RejectedRowsSink {
+    /// A CSV file with the columns `line`, `reason` and `record`.
+    File(PathBuf),
+    /// A table that can be registered in the catalog and queried afterwards.
+    Table(Rc<RejectedRowsTable>),
+}
+
+impl RejectedRowsSink {
+    /// Starts receiving the rejected rows of a new scan.
+    pub(crate) fn open(&self) -> Result<RejectedRowsWriter, QueryError> {
+        match self {
+            RejectedRowsSink::File(path) => {
+                let file = File::create(path).map_err(|e| QueryError::DataSourceError {
+                    message: format!("Failed to create rejected rows file {}: {}", path.display(), e),
+                })?;
+                let mut writer = BufWriter::new(file);
+                writeln!(writer, "line,reason,record").map_err(write_error)?;
+                Ok(RejectedRowsWriter::File(writer))
+            }
+            RejectedRowsSink::Table(table) => {
+                table.rows.borrow_mut().clear();
+                Ok(RejectedRowsWriter::Table(table.clone()))
+            }
+        }
+    }
+}
+
+pub(crate) enum RejectedRowsWriter {
+    File(BufWriter<File>),
+    Table(Rc<RejectedRowsTable>),
+}
+
+impl RejectedRowsWriter {
+    pub(crate) fn write(&mut self, row: RejectedRow) -> Result<(), QueryError> {
+        match self {
+            RejectedRowsWriter::File(writer) => {
+                writeln!(writer, "{},{},{}", row.line, quote(&row.reason), quote(&row.record)).map_err(write_error)?;
+                // Flushing each row keeps the file complete even if the scan is dropped early.
+                writer.flush().map_err(write_error)
+            }
+            RejectedRowsWriter::Table(table) => {
+                table.rows.borrow_mut().push(row);
+                Ok(())
+            }
+        }
+    }
+}
+
+fn write_error(e: std::io::Error) -> QueryError {
+    QueryError::DataSourceError {
+        message: format!("Failed to write rejected row: {}", e),
+    }
+}
+
+/// Quotes a CSV field, doubling the quotes inside it.
+fn quote(value: &str) -> String {
+    format!("\"{}\"", value.replace('"', "\"\""))
+}
+
+/// An in-memory table of rejected rows with the columns `line`, `reason` and `record`.
+#[derive(Debug)]
+pub struct RejectedRowsTable {
+    schema: Schema,
+    rows: RefCell<Vec<RejectedRow>>,
+}
+
+impl Default for RejectedRowsTable {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl RejectedRowsTable {
+    pub fn new() -> Self {
+        let field = |name: &str, field_type| Field { name: name.to_string(), field_type, is_nullable: false };
+        Self {
+            schema: Schema::new(vec![
+                field("line", DataType::Int32),
+                field("reason", DataType::String),
+                field("record", DataType::String),
+            ]),
+            rows: RefCell::new(Vec::new()),
+        }
+    }
+
+    pub fn rows(&self) -> Vec<RejectedRow> {
+        self.rows.borrow().clone()
+    }
+}
+
+impl DataSource for RejectedRowsTable {
+    fn schema(&self) -> &Schema {
+        &self.schema
+    }
+
+    fn scan(
+        &self,
+        config: &SessionConfig,
+        projection: Option<&[usize]>,
+        _filters: &[Expression],
+    ) -> Box<dyn Iterator<Item = Result<RecordBatch, QueryError>>> {
+        let rows = self.rows.borrow().clone();
+        let projection = projection.map_or_else(|| (0..self.schema.fields.len()).collect(), <[usize]>::to_vec);
+        let schema = self.schema.project(&projection);
+        let batch_size = config.batch_size();
+        if rows.is_empty() {
+            return Box::new(iter::once(RecordBatch::new(schema, vec![ColumnVector::new(Vec::new()); projection.len()])));
+        }
+        Box::new((0..rows.len()).step_by(batch_size).map(move |start| {
+            let chunk = &rows[start..(start + batch_size).min(rows.len())];
+            let columns = projection
+                .iter()
+                .map(|&index| {
+                    ColumnVector::new(chunk.iter().map(|row| match index {
+                        0 => ScalarValue::Int32(Some(row.line as i32)),
+                        1 => ScalarValue::String(Some(row.reason.clone())),
+                        _ => ScalarValue::String(Some(row.record.clone())),
+                    }).collect())
+                })
+                .collect();
+            RecordBatch::new(schema.clone(), columns)
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use crate::datasource::csv::{CsvDataSource, CsvReadOptions, MalformedRowPolicy};
+    use crate::test_util::temp_dir;
+
+    use super::*;
+
+    /// Scans `source` to the end, returning how many rows it gave.
+    fn scan(source: &CsvDataSource) -> usize {
+        source.scan(&SessionConfig::new(), None, &[]).map(|batch| batch.unwrap().columns[0].values.len()).sum()
+    }
+
+    #[test]
+    fn the_file_sink_quotes_each_rejected_record() {
+        let dir = temp_dir("rejected-file");
+        let input = dir.join("t.csv");
+        let rejected = dir.join("rejected.csv");
+        fs::write(&input, "id,note\n1,ok\n\"two\",\"say \"\"hi\"\"\nthere\"\n3\n").unwrap();
+        let options = CsvReadOptions::new()
+            .schema(Schema::new(vec![
+                Field { name: "id".to_string(), field_type: DataType::Int32, is_nullable: false },
+                Field { name: "note".to_string(), field_type: DataType::String, is_nullable: true },
+            ]))
+            .malformed_row_policy(MalformedRowPolicy::Skip)
+            .rejected_rows(RejectedRowsSink::File(rejected.clone()));
+        let source = CsvDataSource::with_options(input.to_string_lossy().into_owned(), options).unwrap();
+
+        // A second scan writes the file afresh rather than adding to it.
+        for _ in 0..2 {
+            assert_eq!(scan(&source), 1);
+            assert_eq!(source.rejected_row_count(), 2);
+            assert_eq!(
+                fs::read_to_string(&rejected).unwrap(),
+                "line,reason,record\n\
+                 3,\"Invalid Int32 value 'two' in column id\",\"\"\"two\"\",\"\"say \"\"\"\"hi\"\"\"\"\nthere\"\"\"\n\
+                 5,\"Expected 2 fields, found 1\",\"3\"\n",
+            );
+        }
+    }
+
+    #[test]
+    fn the_table_sink_has_a_row_per_rejected_record() {
+        let table = Rc::new(RejectedRowsTable::new());
+        let options = CsvReadOptions::new().malformed_row_policy(MalformedRowPolicy::SetToNull).rejected_rows(RejectedRowsSink::Table(table.clone()));
+        let source = CsvDataSource::from_reader(Box::new("a,b\n1,2\n3\n".as_bytes()), options).unwrap();
+        assert_eq!(scan(&source), 2);
+        let batches: Vec<RecordBatch> = table.scan(&SessionConfig::new(), None, &[]).map(Result::unwrap).collect();
+        assert_eq!(batches[0].columns[0].values, vec![ScalarValue::Int32(Some(3))]);
+        assert_eq!(batches[0].columns[1].values, vec![ScalarValue::String(Some("Expected 2 fields, found 1".to_string()))]);
+        assert_eq!(batches[0].columns[2].values, vec![ScalarValue::String(Some("3".to_string()))]);
+    }
+}
@@ -1,3 +1,5 @@
+use crate::DataType;
+
 #[derive(Debug)]
 pub enum QueryError {
     SchemaCountMismatch {
@@ -17,6 +19,25 @@ pub enum QueryError {
     DataSourceError {
         message: String,
     },
+    /// A value in the input could not be read as its column's type. `row` is the
+    /// line of the input file the record starts on.
+    InvalidValue {
+        row: usize,
+        column_name: String,
+        expected: DataType,
+        value: String,
+    },
+    /// An empty or NULL value in a column that is not nullable.
+    UnexpectedNull {
+        row: usize,
+        column_name: String,
+    },
+    /// A record with a different number of fields than the schema has columns.
+    FieldCountMismatch {
+        row: usize,
+        expected: usize,
+        actual: usize,
+    },
     ValidationError {
         message: String,
     }
@@ -1,13 +1,20 @@
 pub mod types;
+pub mod config;
 pub mod errors;
 pub mod datasource;
 pub mod cli;
 pub mod logical_plan;
 pub mod sql_support;
 pub mod physical_plan;
+pub mod optimizer;
+pub mod writers;
+#[cfg(test)]
+mod test_util;
 
 pub use types::scalar::{ScalarValue, ColumnVector};
 pub use types::datatypes::DataType;
 pub use types::schema::{Field, RecordBatch, Schema};
 
-pub use datasource::DataSource;
\ No newline at end of file
+pub use config::SessionConfig;
+pub use datasource::DataSource;
+pub use logical_plan::udaf::AggregateUdf;
@@ -0,0 +1,33 @@
+use crate::Schema;
+
+/// The file format of an external table.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExternalFormat {
+    Csv,
+    Json,
+    Parquet,
+}
+
+/// Where the files of an external table are and how to read them, as given to
+/// `CREATE EXTERNAL TABLE`.
+#[derive(Debug, Clone)]
+pub struct ExternalTable {
+    pub format: ExternalFormat,
+    /// A file, a directory or a glob pattern.
+    pub location: String,
+    /// The declared columns. Without them the schema is read from the files.
+    pub schema: Option<Schema>,
+    /// Read options as key/value pairs, such as `delimiter` for CSV.
+    pub options: Vec<(String, String)>,
+}
+
+/// How a table or view was created, kept so the catalog can be saved and built
+/// again in a later session. In-memory tables have no definition.
+#[derive(Debug, Clone)]
+pub enum Definition {
+    ExternalTable(ExternalTable),
+    /// A view's query as SQL text, and the names it gives the query's columns.
+    /// The query's table names are resolved in `database` with `search_path`,
+    /// the current database and search path when the view was created.
+    View { query: String, columns: Vec<String>, database: String, search_path: Vec<String> },
+}
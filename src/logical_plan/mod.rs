@@ -1 +1,4 @@
-pub mod plan;
\ No newline at end of file
+pub mod definition;
+pub mod names;
+pub mod plan;
+pub mod udaf;
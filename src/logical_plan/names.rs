@@ -0,0 +1,194 @@
+use std::fmt::{Display, Formatter};
+
+/// The database that tables go in unless a name says otherwise.
+pub const DEFAULT_DATABASE: &str = "main";
+/// The schema that tables go in unless a name or `USE` says otherwise.
+pub const DEFAULT_SCHEMA: &str = "public";
+
+/// One part of a name as written in SQL. An unquoted part matches names in any
+/// case, while a quoted part only matches the exact name.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NamePart {
+    pub value: String,
+    pub quoted: bool,
+}
+
+impl NamePart {
+    pub fn new(value: impl Into<String>, quoted: bool) -> Self {
+        Self { value: value.into(), quoted }
+    }
+}
+
+/// A table or view name as written: `table`, `schema.table` or
+/// `database.schema.table`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TableReference {
+    pub parts: Vec<NamePart>,
+}
+
+impl TableReference {
+    /// Splits `name` into its parts at dots outside quotes. Parts may be quoted
+    /// with `"`, `` ` `` or `'`, and a doubled quote inside one stands for itself.
+    pub fn parse(name: &str) -> Self {
+        let mut parts = Vec::new();
+        let mut chars = name.chars().peekable();
+        loop {
+            let mut value = String::new();
+            let quoted = match chars.peek() {
+                Some(&quote @ ('"' | '`' | '\'')) => {
+                    chars.next();
+                    while let Some(c) = chars.next() {
+                        if c == quote {
+                            if chars.peek() != Some(&quote) {
+                                break;
+                            }
+                            chars.next();
+                        }
+                        value.push(c);
+                    }
+                    true
+                },
+                _ => false,
+            };
+            while let Some(c) = chars.next_if(|&c| c != '.') {
+                value.push(c);
+            }
+            parts.push(NamePart { value, quoted });
+            if chars.next().is_none() {
+                return Self { parts };
+            }
+        }
+    }
+
+    /// The table part, which is always the last.
+    pub fn table(&self) -> &NamePart {
+        self.parts.last().expect("a table reference has at least one part")
+    }
+}
+
+/// Writes the parts as written, with quoted ones in double quotes.
+impl Display for TableReference {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        for (i, part) in self.parts.iter().enumerate() {
+            if i > 0 {
+                write!(f, ".")?;
+            }
+            match part.quoted {
+                true => write!(f, "\"{}\"", part.value.replace('"', "\"\""))?,
+                false => write!(f, "{}", part.value)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The full name of a table or view in the catalog, with the exact case of
+/// each part.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct QualifiedName {
+    pub database: String,
+    pub schema: String,
+    pub table: String,
+}
+
+impl QualifiedName {
+    /// The name with every part quoted, which the catalog resolves to exactly
+    /// this table or view, whatever else differs from it only in case.
+    pub fn reference(&self) -> String {
+        [&self.database, &self.schema, &self.table]
+            .map(|part| format!("\"{}\"", part.replace('"', "\"\"")))
+            .join(".")
+    }
+}
+
+/// Writes `database.schema.table` for messages, quoting parts that are not
+/// plain identifiers.
+impl Display for QualifiedName {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}.{}.{}", quote_if_needed(&self.database), quote_if_needed(&self.schema), quote_if_needed(&self.table))
+    }
+}
+
+/// `name`, in double quotes if it is not a plain identifier.
+pub fn quote_if_needed(name: &str) -> String {
+    let plain = name.chars().next().is_some_and(|c| c.is_alphabetic() || c == '_')
+        && name.chars().all(|c| c.is_alphanumeric() || c == '_');
+    match plain {
+        true => name.to_string(),
+        false => format!("\"{}\"", name.replace('"', "\"\"")),
+    }
+}
+
+/// Finds the name among `names` that `part` refers to. An exact match wins;
+/// otherwise an unquoted part matches a name that differs only in case, as long
+/// as only one does.
+pub fn find_name<'a>(names: impl IntoIterator<Item = &'a str>, part: &NamePart) -> Result<Option<&'a str>, String> {
+    let mut matches = Vec::new();
+    for name in names {
+        if name == part.value {
+            return Ok(Some(name));
+        }
+        if !part.quoted && name.to_lowercase() == part.value.to_lowercase() && !matches.contains(&name) {
+            matches.push(name);
+        }
+    }
+    match matches.as_slice() {
+        [] => Ok(None),
+        [name] => Ok(Some(name)),
+        _ => {
+            matches.sort_unstable();
+            Err(format!("'{}' is ambiguous: it could be any of {}; quote it to pick one", part.value, matches.join(", ")))
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parts(name: &str) -> Vec<(String, bool)> {
+        TableReference::parse(name).parts.into_iter().map(|part| (part.value, part.quoted)).collect()
+    }
+
+    #[test]
+    fn references_split_at_dots_outside_quotes() {
+        assert_eq!(parts("Sales"), [("Sales".to_string(), false)]);
+        assert_eq!(parts("main.\"My.Schema\".t"), [("main".to_string(), false), ("My.Schema".to_string(), true), ("t".to_string(), false)]);
+        assert_eq!(parts("`a``b`.'c''d'"), [("a`b".to_string(), true), ("c'd".to_string(), true)]);
+        assert_eq!(parts("\"say \"\"hi\"\"\""), [("say \"hi\"".to_string(), true)]);
+    }
+
+    #[test]
+    fn references_display_as_written() {
+        for name in ["t", "s.\"T\"", "main.\"a.b\".\"say \"\"hi\"\"\""] {
+            assert_eq!(TableReference::parse(name).to_string(), name);
+        }
+        assert_eq!(TableReference::parse("`x`").to_string(), "\"x\"");
+    }
+
+    #[test]
+    fn qualified_names_quote_what_needs_it() {
+        let name = QualifiedName { database: "main".to_string(), schema: "my schema".to_string(), table: "Orders_2".to_string() };
+        assert_eq!(name.to_string(), "main.\"my schema\".Orders_2");
+        assert_eq!(name.reference(), "\"main\".\"my schema\".\"Orders_2\"");
+        assert_eq!(quote_if_needed("2fast"), "\"2fast\"");
+        assert_eq!(quote_if_needed(""), "\"\"");
+    }
+
+    #[test]
+    fn unquoted_names_match_in_any_case_unless_ambiguous() {
+        let names = ["Orders", "orders", "Items"];
+        let unquoted = |value: &str| NamePart::new(value, false);
+        let quoted = |value: &str| NamePart::new(value, true);
+        assert_eq!(find_name(names, &unquoted("orders")), Ok(Some("orders")));
+        assert_eq!(find_name(names, &unquoted("ITEMS")), Ok(Some("Items")));
+        assert_eq!(find_name(names, &quoted("ITEMS")), Ok(None));
+        assert_eq!(find_name(names, &quoted("Orders")), Ok(Some("Orders")));
+        assert_eq!(find_name(names, &unquoted("missing")), Ok(None));
+        assert_eq!(
+            find_name(names, &unquoted("ORDERS")),
+            Err("'ORDERS' is ambiguous: it could be any of Orders, orders; quote it to pick one".to_string())
+        );
+        assert_eq!(find_name(["t", "t"], &unquoted("T")), Ok(Some("t")));
+    }
+}
@@ -1,6 +1,10 @@
-use std::{collections::HashMap, fmt::{Display, Formatter}, rc::Rc};
+use std::{collections::{BTreeSet, HashMap, HashSet}, fmt::{Display, Formatter}, path::{Path, PathBuf}, rc::Rc};
 
-use crate::{DataSource, DataType, Field, ScalarValue, Schema, datasource::csv::{CsvDataSource}, errors::QueryError};
+use crate::{DataSource, DataType, Field, RecordBatch, ScalarValue, Schema, SessionConfig, datasource::{csv::CsvDataSource, memory::MemTable}, errors::QueryError};
+
+use super::definition::Definition;
+use super::names::{DEFAULT_DATABASE, DEFAULT_SCHEMA, NamePart, QualifiedName, TableReference, find_name};
+use super::udaf::AggregateUdf;
 
 
 #[derive(Debug, Clone)]
@@ -8,7 +12,10 @@ pub enum LogicalPlan {
     Scan {
         path: String,
         schema: Schema,
-        projection: Option<Vec<String>>
+        projection: Option<Vec<String>>,
+        /// Predicates the data source applies while scanning. Unless the source
+        /// evaluates them exactly, a `Filter` above the scan repeats them.
+        filters: Vec<Expression>,
     },
     Filter {
         input: Box<LogicalPlan>,
@@ -17,6 +24,28 @@ pub enum LogicalPlan {
     Projection {
         input: Box<LogicalPlan>,
         columns: Vec<Expression>
+    },
+    /// Appends one column per window expression to every input row.
+    /// Each window expression is an `Expression::Alias` naming its output column.
+    Window {
+        input: Box<LogicalPlan>,
+        window_exprs: Vec<Expression>
+    },
+    /// Groups rows by `group_by` and computes each aggregate per group. The output
+    /// has the grouping columns followed by one column per aggregate expression.
+    Aggregate {
+        input: Box<LogicalPlan>,
+        group_by: Vec<Expression>,
+        aggr_exprs: Vec<Expression>
+    },
+    /// Removes duplicate rows.
+    Distinct {
+        input: Box<LogicalPlan>
+    },
+    /// Rows given inline, as in `VALUES (1, 'a'), (2, 'b')`.
+    Values {
+        schema: Schema,
+        rows: Vec<Vec<ScalarValue>>
     }
 }
 
@@ -31,11 +60,15 @@ impl LogicalPlan {
     fn fmt_with_indent(&self, f: &mut Formatter<'_>, indent: usize) -> std::fmt::Result {
         let indent_str = "  ".repeat(indent);
         match self {
-            LogicalPlan::Scan { path, projection, .. } => {
-                write!(f, "{}Scan: {} (columns: {:?})", indent_str, path, projection)
+            LogicalPlan::Scan { path, projection, filters, .. } => {
+                write!(f, "{}Scan: {} (columns: {:?})", indent_str, path, projection)?;
+                if !filters.is_empty() {
+                    write!(f, " (filters: {:?})", filters)?;
+                }
+                Ok(())
             },
             LogicalPlan::Filter { input, predicate } => {
-                write!(f, "{}Filter: {:?}\n", indent_str, predicate)?;
+                writeln!(f, "{}Filter: {:?}", indent_str, predicate)?;
                 input.fmt_with_indent(f, indent + 1)
             },
             LogicalPlan::Projection { input, columns } => {
@@ -46,11 +79,69 @@ impl LogicalPlan {
                         format!("{:?}", c)
                     }
                 }).collect();
-                write!(f, "{}Projection: {:?}\n", indent_str, col_names)?;
+                writeln!(f, "{}Projection: {:?}", indent_str, col_names)?;
+                input.fmt_with_indent(f, indent + 1)
+            },
+            LogicalPlan::Window { input, window_exprs } => {
+                let names: Vec<String> = window_exprs.iter().map(|e| e.output_name()).collect();
+                writeln!(f, "{}Window: {:?}", indent_str, names)?;
+                input.fmt_with_indent(f, indent + 1)
+            },
+            LogicalPlan::Aggregate { input, group_by, aggr_exprs } => {
+                let groups: Vec<String> = group_by.iter().map(|e| e.output_name()).collect();
+                let aggrs: Vec<String> = aggr_exprs.iter().map(|e| e.output_name()).collect();
+                writeln!(f, "{}Aggregate: group_by={:?} aggr={:?}", indent_str, groups, aggrs)?;
                 input.fmt_with_indent(f, indent + 1)
+            },
+            LogicalPlan::Distinct { input } => {
+                writeln!(f, "{}Distinct", indent_str)?;
+                input.fmt_with_indent(f, indent + 1)
+            },
+            LogicalPlan::Values { rows, .. } => {
+                write!(f, "{}Values: {} rows", indent_str, rows.len())
             }
         }
     }
+
+    /// Works out the output schema of this plan node.
+    pub fn schema(&self) -> Result<Schema, QueryError> {
+        match self {
+            LogicalPlan::Scan { schema, projection: None, .. } => Ok(schema.clone()),
+            LogicalPlan::Scan { schema, projection: Some(columns), .. } => {
+                let fields = columns
+                    .iter()
+                    .map(|name| schema.column_exists(name).cloned())
+                    .collect::<Result<Vec<Field>, String>>()
+                    .map_err(|message| QueryError::ValidationError { message })?;
+                Ok(Schema::new(fields))
+            },
+            LogicalPlan::Filter { input, .. } => input.schema(),
+            LogicalPlan::Projection { input, columns } => {
+                let input = input.schema()?;
+                let fields = columns.iter().map(|expr| expr.to_field(&input)).collect::<Result<Vec<Field>, QueryError>>()?;
+                Ok(Schema::new(fields))
+            },
+            LogicalPlan::Window { input, window_exprs } => {
+                let input = input.schema()?;
+                let mut fields = input.fields.clone();
+                for expr in window_exprs {
+                    fields.push(expr.to_field(&input)?);
+                }
+                Ok(Schema::new(fields))
+            },
+            LogicalPlan::Aggregate { input, group_by, aggr_exprs } => {
+                let input = input.schema()?;
+                let fields = group_by
+                    .iter()
+                    .chain(aggr_exprs)
+                    .map(|expr| expr.to_field(&input))
+                    .collect::<Result<Vec<Field>, QueryError>>()?;
+                Ok(Schema::new(fields))
+            },
+            LogicalPlan::Distinct { input } => input.schema(),
+            LogicalPlan::Values { schema, .. } => Ok(schema.clone())
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -75,7 +166,281 @@ pub enum UnaryOperator {
     Negate,
 }
 
- 
+/// Null-handling functions. Their arguments are unified to a single type,
+/// widening Int32 to Float64 when the two are mixed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScalarFunction {
+    Coalesce,
+    NullIf,
+    Greatest,
+    Least,
+    IfNull,
+}
+
+impl ScalarFunction {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_uppercase().as_str() {
+            "COALESCE" => Some(ScalarFunction::Coalesce),
+            "NULLIF" => Some(ScalarFunction::NullIf),
+            "GREATEST" => Some(ScalarFunction::Greatest),
+            "LEAST" => Some(ScalarFunction::Least),
+            "IFNULL" => Some(ScalarFunction::IfNull),
+            _ => None,
+        }
+    }
+
+    pub fn check_arity(&self, arg_count: usize) -> Result<(), String> {
+        let valid = match self {
+            ScalarFunction::NullIf | ScalarFunction::IfNull => arg_count == 2,
+            ScalarFunction::Coalesce | ScalarFunction::Greatest | ScalarFunction::Least => arg_count >= 1,
+        };
+        if valid {
+            Ok(())
+        } else {
+            Err(format!("Wrong number of arguments for {:?}: got {}", self, arg_count))
+        }
+    }
+}
+
+/// Functions evaluated over a window of rows with `OVER (PARTITION BY ... ORDER BY ...)`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WindowFunction {
+    RowNumber,
+    Rank,
+    DenseRank,
+    Lag,
+    Lead,
+    FirstValue,
+    Sum,
+    Avg,
+}
+
+impl WindowFunction {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_uppercase().as_str() {
+            "ROW_NUMBER" => Some(WindowFunction::RowNumber),
+            "RANK" => Some(WindowFunction::Rank),
+            "DENSE_RANK" => Some(WindowFunction::DenseRank),
+            "LAG" => Some(WindowFunction::Lag),
+            "LEAD" => Some(WindowFunction::Lead),
+            "FIRST_VALUE" => Some(WindowFunction::FirstValue),
+            "SUM" => Some(WindowFunction::Sum),
+            "AVG" => Some(WindowFunction::Avg),
+            _ => None,
+        }
+    }
+
+    pub fn return_type(&self, args: &[Expression]) -> Result<DataType, String> {
+        let valid_arity = match self {
+            WindowFunction::RowNumber | WindowFunction::Rank | WindowFunction::DenseRank => args.is_empty(),
+            WindowFunction::Lag | WindowFunction::Lead => (1..=3).contains(&args.len()),
+            WindowFunction::FirstValue | WindowFunction::Sum | WindowFunction::Avg => args.len() == 1,
+        };
+        if !valid_arity {
+            return Err(format!("Wrong number of arguments for {:?}: got {}", self, args.len()));
+        }
+
+        match self {
+            WindowFunction::RowNumber | WindowFunction::Rank | WindowFunction::DenseRank => Ok(DataType::Int32),
+            WindowFunction::FirstValue => args[0].get_data_type(),
+            WindowFunction::Lag | WindowFunction::Lead => {
+                if let Some(offset) = args.get(1) {
+                    let offset_type = offset.get_data_type()?;
+                    if offset_type != DataType::Int32 {
+                        return Err(format!("{:?} offset must be Int32, got {:?}", self, offset_type));
+                    }
+                }
+                match args.get(2) {
+                    Some(default) => Expression::unify_types(&[args[0].clone(), default.clone()]),
+                    None => args[0].get_data_type(),
+                }
+            },
+            WindowFunction::Sum | WindowFunction::Avg => {
+                let arg_type = args[0].get_data_type()?;
+                match (self, arg_type) {
+                    (WindowFunction::Sum, DataType::Int32 | DataType::Float64) => Ok(arg_type),
+                    (WindowFunction::Avg, DataType::Int32 | DataType::Float64) => Ok(DataType::Float64),
+                    _ => Err(format!("{:?} requires a numeric argument, got {:?}", self, arg_type)),
+                }
+            },
+        }
+    }
+}
+
+/// Functions that fold every row of a group into a single value.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AggregateFunction {
+    Count,
+    Sum,
+    Min,
+    Max,
+    Avg,
+    /// Estimates the number of distinct values with HyperLogLog in bounded memory.
+    ApproxCountDistinct,
+    StddevSamp,
+    StddevPop,
+    VarSamp,
+    VarPop,
+    /// Pearson correlation coefficient of two numeric arguments.
+    Corr,
+    Median,
+    /// `PERCENTILE_CONT(fraction) WITHIN GROUP (ORDER BY x)`, interpolating between values.
+    PercentileCont,
+    /// `PERCENTILE_DISC(fraction) WITHIN GROUP (ORDER BY x)`, always returning an input value.
+    PercentileDisc,
+    /// `STRING_AGG(value, separator [ORDER BY ...])`.
+    StringAgg,
+    BoolAnd,
+    BoolOr,
+    /// `ARRAY_AGG(value [ORDER BY ...])`, returned as array text such as `{1,2,3}`.
+    ArrayAgg,
+}
+
+impl AggregateFunction {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_uppercase().as_str() {
+            "COUNT" => Some(AggregateFunction::Count),
+            "SUM" => Some(AggregateFunction::Sum),
+            "MIN" => Some(AggregateFunction::Min),
+            "MAX" => Some(AggregateFunction::Max),
+            "AVG" => Some(AggregateFunction::Avg),
+            "APPROX_COUNT_DISTINCT" => Some(AggregateFunction::ApproxCountDistinct),
+            "STDDEV" | "STDDEV_SAMP" => Some(AggregateFunction::StddevSamp),
+            "STDDEV_POP" => Some(AggregateFunction::StddevPop),
+            "VARIANCE" | "VAR_SAMP" => Some(AggregateFunction::VarSamp),
+            "VAR_POP" => Some(AggregateFunction::VarPop),
+            "CORR" => Some(AggregateFunction::Corr),
+            "MEDIAN" => Some(AggregateFunction::Median),
+            "PERCENTILE_CONT" => Some(AggregateFunction::PercentileCont),
+            "PERCENTILE_DISC" => Some(AggregateFunction::PercentileDisc),
+            "STRING_AGG" => Some(AggregateFunction::StringAgg),
+            "BOOL_AND" => Some(AggregateFunction::BoolAnd),
+            "BOOL_OR" => Some(AggregateFunction::BoolOr),
+            "ARRAY_AGG" => Some(AggregateFunction::ArrayAgg),
+            _ => None,
+        }
+    }
+
+    /// Ordered-set aggregates take their input from `WITHIN GROUP (ORDER BY ...)`.
+    pub fn is_ordered_set(&self) -> bool {
+        matches!(self, AggregateFunction::PercentileCont | AggregateFunction::PercentileDisc)
+    }
+
+    /// Whether an `ORDER BY` inside the call changes the result.
+    pub fn accepts_order_by(&self) -> bool {
+        matches!(self, AggregateFunction::StringAgg | AggregateFunction::ArrayAgg)
+    }
+
+    pub fn return_type(&self, args: &[Expression], order_by: &[SortExpr]) -> Result<DataType, String> {
+        let expected_args = match self {
+            AggregateFunction::Corr | AggregateFunction::StringAgg => 2,
+            _ => 1,
+        };
+        if args.len() != expected_args {
+            return Err(format!("Wrong number of arguments for {:?}: got {}", self, args.len()));
+        }
+        if self.is_ordered_set() {
+            if order_by.len() != 1 {
+                return Err(format!("{:?} requires WITHIN GROUP (ORDER BY ...) with exactly one key", self));
+            }
+            let fraction = match &args[0] {
+                Expression::Literal(ScalarValue::Float64(Some(v))) => Some(*v),
+                Expression::Literal(ScalarValue::Int32(Some(v))) => Some(f64::from(*v)),
+                _ => None,
+            };
+            if !fraction.is_some_and(|p| (0.0..=1.0).contains(&p)) {
+                return Err(format!("{:?} requires a constant fraction between 0 and 1", self));
+            }
+        } else if !order_by.is_empty() && !self.accepts_order_by() {
+            return Err(format!("{:?} does not accept ORDER BY", self));
+        }
+
+        let numeric = |expr: &Expression| match expr.get_data_type()? {
+            DataType::Int32 | DataType::Float64 => Ok(DataType::Float64),
+            other => Err(format!("{:?} requires a numeric argument, got {:?}", self, other)),
+        };
+        let arg_type = args[0].get_data_type()?;
+        match self {
+            AggregateFunction::Count | AggregateFunction::ApproxCountDistinct => Ok(DataType::Int32),
+            AggregateFunction::Min | AggregateFunction::Max => Ok(arg_type),
+            AggregateFunction::Sum | AggregateFunction::Avg => match arg_type {
+                DataType::Int32 | DataType::Float64 if *self == AggregateFunction::Sum => Ok(arg_type),
+                DataType::Int32 | DataType::Float64 => Ok(DataType::Float64),
+                _ => Err(format!("{:?} requires a numeric argument, got {:?}", self, arg_type)),
+            },
+            AggregateFunction::StddevSamp
+            | AggregateFunction::StddevPop
+            | AggregateFunction::VarSamp
+            | AggregateFunction::VarPop
+            | AggregateFunction::Median => numeric(&args[0]),
+            AggregateFunction::Corr => {
+                numeric(&args[0])?;
+                numeric(&args[1])
+            },
+            AggregateFunction::PercentileCont => numeric(&order_by[0].expr),
+            AggregateFunction::PercentileDisc => order_by[0].expr.get_data_type(),
+            AggregateFunction::StringAgg => match (arg_type, args[1].get_data_type()?) {
+                (DataType::String, DataType::String) => Ok(DataType::String),
+                (value, separator) => Err(format!(
+                    "StringAgg requires String value and separator, got {:?} and {:?}",
+                    value, separator
+                )),
+            },
+            AggregateFunction::BoolAnd | AggregateFunction::BoolOr => match arg_type {
+                DataType::Bool => Ok(DataType::Bool),
+                _ => Err(format!("{:?} requires a Bool argument, got {:?}", self, arg_type)),
+            },
+            AggregateFunction::ArrayAgg => Ok(DataType::String),
+        }
+    }
+}
+
+/// An ORDER BY key: the expression plus its direction and null placement.
+#[derive(Debug, Clone)]
+pub struct SortExpr {
+    pub expr: Expression,
+    pub asc: bool,
+    pub nulls_first: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WindowFrameUnits {
+    Rows,
+    Range,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WindowFrameBound {
+    UnboundedPreceding,
+    Preceding(u64),
+    CurrentRow,
+    Following(u64),
+    UnboundedFollowing,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WindowFrame {
+    pub units: WindowFrameUnits,
+    pub start: WindowFrameBound,
+    pub end: WindowFrameBound,
+}
+
+impl WindowFrame {
+    /// The frame used when the query does not spell one out: the whole partition
+    /// without ORDER BY, otherwise everything up to and including the current row's peers.
+    pub fn default_for(has_order_by: bool) -> Self {
+        Self {
+            units: WindowFrameUnits::Range,
+            start: WindowFrameBound::UnboundedPreceding,
+            end: if has_order_by {
+                WindowFrameBound::CurrentRow
+            } else {
+                WindowFrameBound::UnboundedFollowing
+            },
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub enum Expression {
     Column {
@@ -91,25 +456,45 @@ pub enum Expression {
     Unary {
         operand: Box<Expression>,
         operator: UnaryOperator
+    },
+    ScalarFunction {
+        func: ScalarFunction,
+        args: Vec<Expression>,
+    },
+    WindowFunction {
+        func: WindowFunction,
+        args: Vec<Expression>,
+        partition_by: Vec<Expression>,
+        order_by: Vec<SortExpr>,
+        frame: WindowFrame,
+    },
+    AggregateFunction {
+        func: AggregateFunction,
+        args: Vec<Expression>,
+        distinct: bool,
+        /// `ORDER BY` inside the call, or the `WITHIN GROUP` key of an ordered-set aggregate.
+        order_by: Vec<SortExpr>,
+    },
+    /// A call to an aggregate registered with `Catalog::register_udaf`.
+    AggregateUdf {
+        udaf: Rc<dyn AggregateUdf>,
+        args: Vec<Expression>,
+    },
+    Alias {
+        expr: Box<Expression>,
+        name: String,
     }
 }
 
 impl Expression {
 
-    fn get_data_type(&self, schema: &Schema) -> Result<DataType, String> {
+    pub fn get_data_type(&self) -> Result<DataType, String> {
         match self {
             Expression::Column { data_type, .. } => Ok(*data_type),
-            Expression::Literal(scalar) => {
-                match scalar {
-                    ScalarValue::Int32(_) => Ok(DataType::Int32),
-                    ScalarValue::String(_) => Ok(DataType::String),
-                    ScalarValue::Bool(_) => Ok(DataType::Bool),
-                    ScalarValue::Float64(_) => Ok(DataType::Float64),
-                }
-            },
+            Expression::Literal(scalar) => Ok(scalar.data_type()),
             Expression::Binary { left, right, operator } => {
-                let left_type = left.get_data_type(schema)?;
-                let right_type = right.get_data_type(schema)?;
+                let left_type = left.get_data_type()?;
+                let right_type = right.get_data_type()?;
                 
                 match operator {
                     Operator::Gt | Operator::Lt | Operator::Eq | Operator::NotEq | Operator::GtEq | Operator::LtEq => {
@@ -139,7 +524,7 @@ impl Expression {
                 }
             },
             Expression::Unary { operand, operator } => {
-                let operand_type = operand.get_data_type(schema)?;
+                let operand_type = operand.get_data_type()?;
                 match operator {
                     UnaryOperator::Not => {
                         if operand_type == DataType::Bool {
@@ -157,33 +542,152 @@ impl Expression {
                     },
                 }
             },
+            Expression::ScalarFunction { func, args } => {
+                func.check_arity(args.len())?;
+                match func {
+                    ScalarFunction::NullIf => {
+                        let left_type = args[0].get_data_type()?;
+                        let right_type = args[1].get_data_type()?;
+                        if args[1].is_null_literal() || Self::are_compatible_for_comparison(&left_type, &right_type) {
+                            Ok(left_type)
+                        } else {
+                            Err(format!(
+                                "Incompatible types for NullIf: {:?} and {:?}",
+                                left_type, right_type
+                            ))
+                        }
+                    },
+                    _ => Self::unify_types(args),
+                }
+            },
+            Expression::WindowFunction { func, args, .. } => func.return_type(args),
+            Expression::AggregateFunction { func, args, order_by, .. } => func.return_type(args, order_by),
+            Expression::AggregateUdf { udaf, args } => {
+                let arg_types = args.iter().map(|arg| arg.get_data_type()).collect::<Result<Vec<_>, _>>()?;
+                udaf.return_type(&arg_types)
+            },
+            Expression::Alias { expr, .. } => expr.get_data_type(),
+        }
+    }
+
+    /// The column name this expression gets when it appears in a projection.
+    pub fn output_name(&self) -> String {
+        match self {
+            Expression::Column { name, .. } | Expression::Alias { name, .. } => name.clone(),
+            Expression::Literal(scalar) => format!("{:?}", scalar),
+            _ => format!("{:?}", self),
         }
     }
 
+    /// The output column of this expression over rows of `input`. A column keeps
+    /// the nullability it has in `input`, even under an alias; anything else may be NULL.
+    pub fn to_field(&self, input: &Schema) -> Result<Field, QueryError> {
+        let is_nullable = match self {
+            Expression::Column { name, .. } => input.column_exists(name).is_ok_and(|field| field.is_nullable),
+            Expression::Alias { expr, .. } => expr.to_field(input)?.is_nullable,
+            _ => true,
+        };
+        Ok(Field {
+            name: self.output_name(),
+            field_type: self.get_data_type().map_err(|message| QueryError::ValidationError { message })?,
+            is_nullable,
+        })
+    }
 
-    fn are_compatible_for_comparison(left: &DataType, right: &DataType) -> bool {
-        match (left, right) {
-            (DataType::Int32, DataType::Int32) => true,
-            (DataType::Float64, DataType::Float64) => true,
-            (DataType::Int32, DataType::Float64) => true,
-            (DataType::Float64, DataType::Int32) => true,
-            (DataType::String, DataType::String) => true,
-            (DataType::Bool, DataType::Bool) => true,
-            _ => false,
+    pub fn contains_window_function(&self) -> bool {
+        self.any_node(&|expr| matches!(expr, Expression::WindowFunction { .. }))
+    }
+
+    pub fn contains_aggregate_function(&self) -> bool {
+        self.any_node(&|expr| expr.is_aggregate_call())
+    }
+
+    /// True for a built-in or user-defined aggregate call itself, not one nested inside.
+    pub fn is_aggregate_call(&self) -> bool {
+        matches!(self, Expression::AggregateFunction { .. } | Expression::AggregateUdf { .. })
+    }
+
+    /// Returns true if `predicate` holds for this expression or any expression nested in it.
+    fn any_node(&self, predicate: &dyn Fn(&Expression) -> bool) -> bool {
+        predicate(self) || self.children().into_iter().any(|child| child.any_node(predicate))
+    }
+
+    /// The expressions directly nested in this one.
+    fn children(&self) -> Vec<&Expression> {
+        match self {
+            Expression::Column { .. } | Expression::Literal(_) => vec![],
+            Expression::Binary { left, right, .. } => vec![left, right],
+            Expression::Unary { operand, .. } => vec![operand],
+            Expression::ScalarFunction { args, .. } | Expression::AggregateUdf { args, .. } => args.iter().collect(),
+            Expression::AggregateFunction { args, order_by, .. } => args
+                .iter()
+                .chain(order_by.iter().map(|sort| &sort.expr))
+                .collect(),
+            Expression::WindowFunction { args, partition_by, order_by, .. } => args
+                .iter()
+                .chain(partition_by)
+                .chain(order_by.iter().map(|sort| &sort.expr))
+                .collect(),
+            Expression::Alias { expr, .. } => vec![expr],
         }
     }
 
-    fn are_compatible_for_arithmetic(left: &DataType, right: &DataType) -> bool {
-        match (left, right) {
-            (DataType::Int32, DataType::Int32) => true,
-            (DataType::Float64, DataType::Float64) => true,
-            (DataType::Int32, DataType::Float64) => true,
-            (DataType::Float64, DataType::Int32) => true,
-            _ => false,
+    /// Adds the name of every column this expression reads to `columns`.
+    pub fn collect_columns(&self, columns: &mut HashSet<String>) {
+        if let Expression::Column { name, .. } = self {
+            columns.insert(name.clone());
+        }
+        for child in self.children() {
+            child.collect_columns(columns);
         }
     }
 
-    fn is_valid(&self, schema: &Schema) -> Result<(), String> {
+    fn is_null_literal(&self) -> bool {
+        matches!(self, Expression::Literal(scalar) if scalar.is_null())
+    }
+
+    /// Finds the common type of a list of arguments. NULL literals take on
+    /// whatever type the other arguments agree on.
+    fn unify_types(args: &[Expression]) -> Result<DataType, String> {
+        let mut unified: Option<DataType> = None;
+        for arg in args {
+            if arg.is_null_literal() {
+                continue;
+            }
+            let arg_type = arg.get_data_type()?;
+            unified = Some(match unified {
+                None => arg_type,
+                Some(current) if current == arg_type => current,
+                Some(current) if Self::are_compatible_for_arithmetic(&current, &arg_type) => DataType::Float64,
+                Some(current) => {
+                    return Err(format!(
+                        "Incompatible argument types: {:?} and {:?}",
+                        current, arg_type
+                    ))
+                }
+            });
+        }
+        Ok(unified.unwrap_or(DataType::String))
+    }
+
+
+    fn are_compatible_for_comparison(left: &DataType, right: &DataType) -> bool {
+        matches!(
+            (left, right),
+            (DataType::Int32 | DataType::Float64, DataType::Int32 | DataType::Float64)
+                | (DataType::String, DataType::String)
+                | (DataType::Bool, DataType::Bool)
+        )
+    }
+
+    fn are_compatible_for_arithmetic(left: &DataType, right: &DataType) -> bool {
+        matches!(
+            (left, right),
+            (DataType::Int32 | DataType::Float64, DataType::Int32 | DataType::Float64)
+        )
+    }
+
+    pub fn is_valid(&self, schema: &Schema) -> Result<(), String> {
         match self {
             Expression::Column { name, data_type } => {
                 let field = schema.column_exists(name)?;
@@ -196,25 +700,25 @@ impl Expression {
                 Ok(())
             },
             Expression::Binary { left, right, operator } => {
-                left.is_valid(&schema)?;
-                right.is_valid(&schema)?;                
-                let left_type = left.get_data_type(schema)?;
-                let right_type = right.get_data_type(schema)?;
+                left.is_valid(schema)?;
+                right.is_valid(schema)?;                
+                let left_type = left.get_data_type()?;
+                let right_type = right.get_data_type()?;
                 
                 match operator {
                     Operator::Gt | Operator::Lt | Operator::Eq | Operator::NotEq | Operator::GtEq | Operator::LtEq => {
                         if !Self::are_compatible_for_comparison(&left_type, &right_type) {
                             return Err(format!(
-                                "Incompatible types for {} operator: {:?} and {:?}",
-                                format!("{:?}", operator), left_type, right_type
+                                "Incompatible types for {:?} operator: {:?} and {:?}",
+                                operator, left_type, right_type
                             ));
                         }
                     },
                     Operator::Add | Operator::Subtract | Operator::Multiply | Operator::Divide => {
                         if !Self::are_compatible_for_arithmetic(&left_type, &right_type) {
                             return Err(format!(
-                                "Incompatible types for {} operator: {:?} and {:?}",
-                                format!("{:?}", operator), left_type, right_type
+                                "Incompatible types for {:?} operator: {:?} and {:?}",
+                                operator, left_type, right_type
                             ));
                         }
                     },
@@ -222,32 +726,341 @@ impl Expression {
                 
                 Ok(())
             },
-            Expression::Unary { operand, operator } => {
+            Expression::Unary { operand, .. } => {
                 operand.is_valid(schema)?;
                 Ok(())
             },
+            Expression::ScalarFunction { args, .. } | Expression::AggregateUdf { args, .. } => {
+                for arg in args {
+                    arg.is_valid(schema)?;
+                }
+                self.get_data_type()?;
+                Ok(())
+            },
+            Expression::WindowFunction { args, partition_by, order_by, .. } => {
+                for expr in args.iter().chain(partition_by).chain(order_by.iter().map(|sort| &sort.expr)) {
+                    expr.is_valid(schema)?;
+                }
+                self.get_data_type()?;
+                Ok(())
+            },
+            Expression::AggregateFunction { args, order_by, .. } => {
+                for expr in args.iter().chain(order_by.iter().map(|sort| &sort.expr)) {
+                    expr.is_valid(schema)?;
+                }
+                self.get_data_type()?;
+                Ok(())
+            },
+            Expression::Alias { expr, .. } => expr.is_valid(schema),
             _ => Ok(())
         }
     }
 }
 
+/// The tables and views queries can name, and the user-defined aggregates they
+/// can call.
+///
+/// Tables and views live in schemas, which live in databases, and share one
+/// namespace within a schema. A name gives the table alone, `schema.table` or
+/// `database.schema.table`. A table alone is looked for in the schemas of the
+/// search path in turn, in the current database; the first schema on the path
+/// is the current schema, where new tables go. An unquoted part of a name also
+/// matches a name that differs only in case, as long as just one does.
 pub struct Catalog {
-    tables: HashMap<String, Rc<dyn DataSource>>,
+    tables: HashMap<QualifiedName, Rc<dyn DataSource>>,
+    /// The unoptimized plan of each view, which replaces the view wherever a query names it.
+    views: HashMap<QualifiedName, LogicalPlan>,
+    /// How the tables and views created from SQL were defined, for saving the catalog.
+    definitions: HashMap<QualifiedName, Definition>,
+    /// Every schema as (database, schema), including empty ones.
+    schemas: BTreeSet<(String, String)>,
+    /// The database that names without one refer to.
+    database: String,
+    /// The schemas of `database` that a table name alone is looked for in.
+    search_path: Vec<String>,
+    /// The catalog file that DDL statements save their changes to, if any.
+    file_path: Option<PathBuf>,
+    /// User-defined aggregates keyed by upper-cased name.
+    udafs: HashMap<String, Rc<dyn AggregateUdf>>,
+}
+
+impl Default for Catalog {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Catalog {
+    /// An empty catalog whose only schema, `main.public`, is the current one.
     pub fn new() -> Self {
         Self {
-            tables: HashMap::new()
+            tables: HashMap::new(),
+            views: HashMap::new(),
+            definitions: HashMap::new(),
+            schemas: BTreeSet::from([(DEFAULT_DATABASE.to_string(), DEFAULT_SCHEMA.to_string())]),
+            database: DEFAULT_DATABASE.to_string(),
+            search_path: vec![DEFAULT_SCHEMA.to_string()],
+            file_path: None,
+            udafs: HashMap::new(),
         }
     }
 
+    /// Registers `source` as table `name`, replacing any table or view of that
+    /// name. The schema the name puts it in is created if it does not exist.
     pub fn register_table(&mut self, name: String, source: Rc<dyn DataSource>) {
+        let name = self.target_name(&name);
+        self.views.remove(&name);
+        self.definitions.remove(&name);
+        self.schemas.insert((name.database.clone(), name.schema.clone()));
         self.tables.insert(name, source);
     }
 
+    /// Registers `plan` as view `name`, replacing any table or view of that
+    /// name. The schema the name puts it in is created if it does not exist.
+    pub fn register_view(&mut self, name: String, plan: LogicalPlan) {
+        let name = self.target_name(&name);
+        self.tables.remove(&name);
+        self.definitions.remove(&name);
+        self.schemas.insert((name.database.clone(), name.schema.clone()));
+        self.views.insert(name, plan);
+    }
+
+    /// Removes table `name`, returning its source if there was one.
+    pub fn deregister_table(&mut self, name: &str) -> Option<Rc<dyn DataSource>> {
+        let name = self.resolve(name).ok().flatten()?;
+        let source = self.tables.remove(&name)?;
+        self.definitions.remove(&name);
+        Some(source)
+    }
+
+    /// Removes view `name`, returning its plan if there was one.
+    pub fn deregister_view(&mut self, name: &str) -> Option<LogicalPlan> {
+        let name = self.resolve(name).ok().flatten()?;
+        let plan = self.views.remove(&name)?;
+        self.definitions.remove(&name);
+        Some(plan)
+    }
+
+    /// Whether `name` refers to a table or a view.
+    pub fn contains(&self, name: &str) -> bool {
+        self.resolve(name).is_ok_and(|name| name.is_some())
+    }
+
+    /// The names of the registered tables, in order.
+    pub fn table_names(&self) -> Vec<&QualifiedName> {
+        let mut names: Vec<&QualifiedName> = self.tables.keys().collect();
+        names.sort_unstable();
+        names
+    }
+
+    /// The names of the registered views, in order.
+    pub fn view_names(&self) -> Vec<&QualifiedName> {
+        let mut names: Vec<&QualifiedName> = self.views.keys().collect();
+        names.sort_unstable();
+        names
+    }
+
+    pub fn get_view(&self, name: &str) -> Option<&LogicalPlan> {
+        self.views.get(&self.resolve(name).ok().flatten()?)
+    }
+
+    /// Records how the table or view already registered as `name` was created.
+    pub fn define(&mut self, name: String, definition: Definition) {
+        let name = self.target_name(&name);
+        self.definitions.insert(name, definition);
+    }
+
+    /// The recorded definitions, by name.
+    pub fn definitions(&self) -> Vec<(&QualifiedName, &Definition)> {
+        let mut definitions: Vec<(&QualifiedName, &Definition)> = self.definitions.iter().collect();
+        definitions.sort_unstable_by_key(|(name, _)| *name);
+        definitions
+    }
+
+    /// Finds the table or view that `name` refers to, if there is one. Naming
+    /// a schema or database that does not exist is an error.
+    pub fn resolve(&self, name: &str) -> Result<Option<QualifiedName>, QueryError> {
+        self.resolve_from(name, &self.database, &self.search_path)
+    }
+
+    /// Finds the table or view that `name` refers to as if `database` were the
+    /// current database and `search_path` the search path, such as those a view
+    /// was created with.
+    pub fn resolve_from(&self, name: &str, database: &str, search_path: &[String]) -> Result<Option<QualifiedName>, QueryError> {
+        let reference = TableReference::parse(name);
+        for (database, schema) in self.schemas_to_search(&reference, database, search_path)? {
+            let table = find_name(self.relations_in(&database, &schema), reference.table())
+                .map_err(|message| QueryError::ValidationError { message })?
+                .map(str::to_string);
+            if let Some(table) = table {
+                return Ok(Some(QualifiedName { database, schema, table }));
+            }
+        }
+        Ok(None)
+    }
+
+    /// The full name that a table or view created as `name` gets: that of the
+    /// one it would replace, if its schema has one, or else `name` in that
+    /// schema. A table name alone goes in the first schema of the search path
+    /// that exists.
+    pub fn qualify(&self, name: &str) -> Result<QualifiedName, QueryError> {
+        let reference = TableReference::parse(name);
+        let Some((database, schema)) = self.schemas_to_search(&reference, &self.database, &self.search_path)?.into_iter().next() else {
+            return Err(QueryError::ValidationError {
+                message: format!("No schema on the search path exists to put '{}' in; create one or USE another", name),
+            });
+        };
+        let table = find_name(self.relations_in(&database, &schema), reference.table())
+            .map_err(|message| QueryError::ValidationError { message })?
+            .unwrap_or(&reference.table().value)
+            .to_string();
+        Ok(QualifiedName { database, schema, table })
+    }
+
+    /// The name `register_table` and `register_view` use for `name`: the one
+    /// `qualify` gives, or else the name as written, completed with the
+    /// current database and schema.
+    fn target_name(&self, name: &str) -> QualifiedName {
+        if let Ok(name) = self.qualify(name) {
+            return name;
+        }
+        let reference = TableReference::parse(name);
+        let mut parts = reference.parts.into_iter().rev().map(|part| part.value);
+        QualifiedName {
+            table: parts.next().unwrap_or_default(),
+            schema: parts.next().unwrap_or_else(|| self.current_schema().to_string()),
+            database: parts.next().unwrap_or_else(|| self.database.clone()),
+        }
+    }
+
+    /// The schemas to look for the table of `reference` in, in order, as
+    /// (database, schema).
+    fn schemas_to_search(&self, reference: &TableReference, database: &str, search_path: &[String]) -> Result<Vec<(String, String)>, QueryError> {
+        match reference.parts.as_slice() {
+            [_] => Ok(search_path
+                .iter()
+                .filter(|schema| self.schemas.contains(&(database.to_string(), schema.to_string())))
+                .map(|schema| (database.to_string(), schema.clone()))
+                .collect()),
+            [schema, _] => Ok(vec![(database.to_string(), self.existing_schema(database, schema)?)]),
+            [database, schema, _] => {
+                let database = self.find_database(database)?.ok_or_else(|| QueryError::ValidationError {
+                    message: format!("Database '{}' does not exist", database.value),
+                })?;
+                let schema = self.existing_schema(&database, schema)?;
+                Ok(vec![(database, schema)])
+            },
+            _ => Err(QueryError::ValidationError {
+                message: format!("'{}' has too many parts for a table name, which is [database.][schema.]table", reference),
+            }),
+        }
+    }
+
+    /// The names of the tables and views in a schema.
+    fn relations_in<'a>(&'a self, database: &'a str, schema: &'a str) -> impl Iterator<Item = &'a str> {
+        self.tables
+            .keys()
+            .chain(self.views.keys())
+            .filter(move |name| name.database == database && name.schema == schema)
+            .map(|name| name.table.as_str())
+    }
+
+    fn find_database(&self, part: &NamePart) -> Result<Option<String>, QueryError> {
+        let databases = self.schemas.iter().map(|(database, _)| database.as_str());
+        let database = find_name(databases, part).map_err(|message| QueryError::ValidationError { message })?;
+        Ok(database.map(str::to_string))
+    }
+
+    fn find_schema(&self, database: &str, part: &NamePart) -> Result<Option<String>, QueryError> {
+        let schemas = self.schemas.iter().filter(|(schema_database, _)| schema_database == database).map(|(_, schema)| schema.as_str());
+        let schema = find_name(schemas, part).map_err(|message| QueryError::ValidationError { message })?;
+        Ok(schema.map(str::to_string))
+    }
+
+    fn existing_schema(&self, database: &str, part: &NamePart) -> Result<String, QueryError> {
+        self.find_schema(database, part)?.ok_or_else(|| QueryError::ValidationError {
+            message: format!("Schema '{}' does not exist in database '{}'", part.value, database),
+        })
+    }
+
+    /// The database and schema that `name`, given as `schema` or
+    /// `database.schema`, refers to. Parts that match no existing database or
+    /// schema are taken as written, so the schema may not exist yet.
+    pub fn resolve_schema(&self, name: &str) -> Result<(String, String), QueryError> {
+        let reference = TableReference::parse(name);
+        let (database, schema) = match reference.parts.as_slice() {
+            [schema] => (self.database.clone(), schema),
+            [database, schema] => (self.find_database(database)?.unwrap_or_else(|| database.value.clone()), schema),
+            _ => return Err(QueryError::ValidationError {
+                message: format!("'{}' is not a schema name, which is [database.]schema", name),
+            }),
+        };
+        let schema = self.find_schema(&database, schema)?.unwrap_or_else(|| schema.value.clone());
+        Ok((database, schema))
+    }
+
+    pub fn has_schema(&self, database: &str, schema: &str) -> bool {
+        self.schemas.contains(&(database.to_string(), schema.to_string()))
+    }
+
+    /// Every schema as (database, schema), in order.
+    pub fn schema_names(&self) -> Vec<(&str, &str)> {
+        self.schemas.iter().map(|(database, schema)| (database.as_str(), schema.as_str())).collect()
+    }
+
+    /// Adds an empty schema, returning false if it already exists.
+    pub fn register_schema(&mut self, database: String, schema: String) -> bool {
+        self.schemas.insert((database, schema))
+    }
+
+    /// Removes a schema along with its tables and views, returning false if it
+    /// did not exist.
+    pub fn deregister_schema(&mut self, database: &str, schema: &str) -> bool {
+        let outside = |name: &QualifiedName| name.database != database || name.schema != schema;
+        self.tables.retain(|name, _| outside(name));
+        self.views.retain(|name, _| outside(name));
+        self.definitions.retain(|name, _| outside(name));
+        self.schemas.remove(&(database.to_string(), schema.to_string()))
+    }
+
+    pub fn current_database(&self) -> &str {
+        &self.database
+    }
+
+    /// The schema new tables and views go in, which is the first on the search path.
+    pub fn current_schema(&self) -> &str {
+        self.search_path.first().map_or(DEFAULT_SCHEMA, String::as_str)
+    }
+
+    pub fn search_path(&self) -> &[String] {
+        &self.search_path
+    }
+
+    /// Makes `database` the current database and `search_path` the schemas of
+    /// it that table names alone are looked for in. Schemas that do not exist
+    /// are skipped until they are created.
+    pub fn set_search_path(&mut self, database: String, search_path: Vec<String>) {
+        self.database = database;
+        self.search_path = search_path;
+    }
+
+    pub fn file_path(&self) -> Option<&Path> {
+        self.file_path.as_deref()
+    }
+
+    /// Makes DDL statements save the catalog to `path`.
+    pub fn set_file_path(&mut self, path: PathBuf) {
+        self.file_path = Some(path);
+    }
+
+    /// Registers record batches held in memory as a table.
+    pub fn register_batches(&mut self, name: String, schema: Schema, batches: Vec<RecordBatch>) -> Result<(), QueryError> {
+        self.register_table(name, Rc::new(MemTable::try_new(schema, batches)?));
+        Ok(())
+    }
+
     pub fn get_schema(&self, table_name: &str) -> Option<&Schema> {
-        let table = self.tables.get(table_name);
+        let table = self.tables.get(&self.resolve(table_name).ok().flatten()?);
         match table {
             Some(source) => {
                 Some(source.schema())
@@ -257,28 +1070,69 @@ impl Catalog {
     }
 
     pub fn get_source(&self, table_name: &str) -> Result<Rc<dyn DataSource>, QueryError> {
-        self.tables.get(table_name)
+        self.resolve(table_name)?
+            .and_then(|name| self.tables.get(&name))
             .cloned()
             .ok_or_else(|| QueryError::ValidationError {
                 message: format!("Table '{}' not found in catalog", table_name),
             })
     }
+
+    /// Makes `udaf` callable from SQL, replacing any aggregate already registered
+    /// under its name. The name of a built-in function such as `SUM` is refused,
+    /// so queries that call it keep their meaning.
+    pub fn register_udaf(&mut self, udaf: Rc<dyn AggregateUdf>) -> Result<(), QueryError> {
+        let name = udaf.name();
+        if AggregateFunction::from_name(name).is_some() || ScalarFunction::from_name(name).is_some() {
+            return Err(QueryError::ValidationError {
+                message: format!("Cannot register aggregate '{}': a built-in function has that name", name),
+            });
+        }
+        self.udafs.insert(name.to_uppercase(), udaf);
+        Ok(())
+    }
+
+    pub fn get_udaf(&self, name: &str) -> Option<Rc<dyn AggregateUdf>> {
+        self.udafs.get(&name.to_uppercase()).cloned()
+    }
 }
 
 #[allow(dead_code)]
 pub struct ExecutionContext {
-    catalog: Catalog
+    catalog: Catalog,
+    config: SessionConfig,
+}
+
+impl Default for ExecutionContext {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl ExecutionContext {
     pub fn new() -> Self {
+        Self::with_config(SessionConfig::new())
+    }
+
+    pub fn with_config(config: SessionConfig) -> Self {
         Self {
-            catalog: Catalog {
-                tables: HashMap::new()
-            }
+            catalog: Catalog::new(),
+            config,
         }
     }
 
+    pub fn config(&self) -> &SessionConfig {
+        &self.config
+    }
+
+    pub fn register_udaf(&mut self, udaf: Rc<dyn AggregateUdf>) -> Result<(), QueryError> {
+        self.catalog.register_udaf(udaf)
+    }
+
+    pub fn catalog(&self) -> &Catalog {
+        &self.catalog
+    }
+
     pub fn csv(&self, file_path: String) -> Result<DataFrame, QueryError> {
 
         let data_source = CsvDataSource::new(file_path.clone())?;
@@ -287,7 +1141,7 @@ impl ExecutionContext {
         let schema = data_source.schema();
 
         let plan = LogicalPlan::Scan {
-            path: file_path, schema: schema.clone(), projection: None,
+            path: file_path, schema: schema.clone(), projection: None, filters: Vec::new(),
         };
         Ok(DataFrame { plan })
     }
@@ -300,7 +1154,7 @@ pub struct DataFrame {
 impl DataFrame {
     pub fn project(self, columns: Vec<Expression>) -> Self {
         Self {
-            plan: LogicalPlan::Projection { input: Box::new(self.plan), columns: columns }
+            plan: LogicalPlan::Projection { input: Box::new(self.plan), columns }
         }
     }
 
@@ -310,41 +1164,8 @@ impl DataFrame {
         }
     }
 
-    pub fn schema(&self) -> Schema {
-        match &self.plan {
-            LogicalPlan::Scan { schema, .. } => {
-                schema.clone()
-            },
-            LogicalPlan::Filter { input, .. } => {
-                DataFrame { plan: *input.clone() }.schema()
-            },
-            LogicalPlan::Projection { input, columns } => {
-                let input_schema = DataFrame { plan: *input.clone() }.schema();
-                
-                let new_fields: Vec<Field> = columns.iter().map(|expr| {
-                    let data_type = expr.get_data_type(&input_schema).unwrap();
-
-                    match expr {
-                        Expression::Column { name, .. } => {
-                            Field {
-                                name: name.clone(),
-                                field_type: data_type,
-                                is_nullable: true,
-                            }
-                        },
-                        _ => {
-                            Field {
-                                name: format!("{:?}", expr),
-                                field_type: data_type,
-                                is_nullable: true,
-                            }
-                        }
-                    }
-                }).collect();
-                
-                Schema::new(new_fields)
-            }
-        }
+    pub fn schema(&self) -> Result<Schema, QueryError> {
+        self.plan.schema()
     }
 
     pub fn plan(&self) -> LogicalPlan {
@@ -361,21 +1182,21 @@ pub struct PlanBuilder<'a> {
 impl<'a> PlanBuilder<'a> {
     pub fn new(catalog: &'a Catalog) -> Self {
         Self {
-            catalog: catalog,
+            catalog,
             current_plan: None,
             current_schema: Schema::new(Vec::new())
         }
     }
 
     pub fn scan(self, table_name: &str) -> Result<PlanBuilder<'a>, QueryError> {
-        let source = self.catalog.tables.get(table_name);
+        let source = self.catalog.get_source(table_name).ok();
         match source {
             Some(source) => {
                 let schema = source.schema();
                 let projection = schema.fields.iter().map(|f| {
                     f.name.clone()
                 }).collect::<Vec<String>>();
-                let current_plan = LogicalPlan::Scan { path: table_name.to_string() , schema: schema.clone(), projection: Some(projection) };
+                let current_plan = LogicalPlan::Scan { path: table_name.to_string() , schema: schema.clone(), projection: Some(projection), filters: Vec::new() };
                 Ok(PlanBuilder {  current_schema: schema.clone(), current_plan: Some(current_plan), ..self  })
             },
             None => Err(QueryError::ValidationError { message: "Table not found".to_string() })
@@ -388,7 +1209,7 @@ impl<'a> PlanBuilder<'a> {
             QueryError::ValidationError { message: "Filter did not receive current plan".to_string() }
         })?;
         expression.is_valid(&self.current_schema).map_err(|err| {
-            return QueryError::ValidationError { message: err };
+            QueryError::ValidationError { message: err }
         })?;
         if !matches!(expression, Expression::Binary { .. } | Expression::Unary { .. }) {
             return Err(QueryError::ValidationError { message: "Expression Type not supported for filtering".to_string() })
@@ -435,8 +1256,99 @@ impl<'a> PlanBuilder<'a> {
 
     pub fn build(self) -> Result<LogicalPlan, QueryError> {
         let current_plan = self.current_plan.ok_or_else(|| {
-            return QueryError::DataSourceError { message: format!("Failed to build logical plan") }
+            QueryError::DataSourceError { message: "Failed to build logical plan".to_string() }
         })?;
         Ok(current_plan)
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn table() -> Rc<dyn DataSource> {
+        let schema = Schema::new(vec![Field { name: "id".to_string(), field_type: DataType::Int32, is_nullable: false }]);
+        Rc::new(MemTable::try_new(schema, Vec::new()).unwrap())
+    }
+
+    fn resolved(catalog: &Catalog, name: &str) -> Option<String> {
+        catalog.resolve(name).unwrap_or_else(|e| panic!("{} failed to resolve: {:?}", name, e)).map(|name| name.to_string())
+    }
+
+    fn resolve_error(catalog: &Catalog, name: &str) -> String {
+        match catalog.resolve(name) {
+            Err(QueryError::ValidationError { message }) => message,
+            other => panic!("{} did not fail to resolve: {:?}", name, other),
+        }
+    }
+
+    #[test]
+    fn table_names_resolve_in_any_case_unless_quoted() {
+        let mut catalog = Catalog::new();
+        catalog.register_table("Orders".to_string(), table());
+        assert_eq!(resolved(&catalog, "orders").as_deref(), Some("main.public.Orders"));
+        assert_eq!(resolved(&catalog, "MAIN.Public.ORDERS").as_deref(), Some("main.public.Orders"));
+        assert_eq!(resolved(&catalog, "\"Orders\"").as_deref(), Some("main.public.Orders"));
+        assert_eq!(resolved(&catalog, "\"orders\""), None);
+
+        // Registering a name that differs only in case replaces the table.
+        catalog.register_table("ORDERS".to_string(), table());
+        assert_eq!(catalog.table_names().len(), 1);
+    }
+
+    #[test]
+    fn names_differing_only_in_case_must_be_quoted() {
+        let mut catalog = Catalog::new();
+        catalog.register_table("Orders".to_string(), table());
+        catalog.register_table("\"orders\"".to_string(), table());
+        assert_eq!(catalog.table_names().len(), 2);
+        assert_eq!(resolved(&catalog, "orders").as_deref(), Some("main.public.orders"));
+        assert_eq!(resolved(&catalog, "Orders").as_deref(), Some("main.public.Orders"));
+        assert_eq!(resolve_error(&catalog, "ORDERS"), "'ORDERS' is ambiguous: it could be any of Orders, orders; quote it to pick one");
+        assert!(!catalog.contains("ORDERS"));
+        assert!(matches!(catalog.qualify("ORDERS"), Err(QueryError::ValidationError { .. })));
+    }
+
+    #[test]
+    fn schemas_and_databases_resolve_like_tables() {
+        let mut catalog = Catalog::new();
+        catalog.register_schema("main".to_string(), "Sales".to_string());
+        catalog.register_schema("main".to_string(), "sales".to_string());
+        catalog.register_table("\"Sales\".t".to_string(), table());
+        assert_eq!(resolved(&catalog, "\"Sales\".T").as_deref(), Some("main.Sales.t"));
+        assert_eq!(resolved(&catalog, "MAIN.\"Sales\".t").as_deref(), Some("main.Sales.t"));
+        assert_eq!(resolved(&catalog, "sales.t"), None);
+        assert_eq!(resolve_error(&catalog, "SALES.t"), "'SALES' is ambiguous: it could be any of Sales, sales; quote it to pick one");
+        assert_eq!(resolve_error(&catalog, "ghost.t"), "Schema 'ghost' does not exist in database 'main'");
+        assert_eq!(resolve_error(&catalog, "other.public.t"), "Database 'other' does not exist");
+        assert_eq!(resolve_error(&catalog, "a.b.c.d"), "'a.b.c.d' has too many parts for a table name, which is [database.][schema.]table");
+
+        assert_eq!(catalog.resolve_schema("\"Sales\"").unwrap(), ("main".to_string(), "Sales".to_string()));
+        assert_eq!(catalog.resolve_schema("Main.New").unwrap(), ("main".to_string(), "New".to_string()));
+    }
+
+    #[test]
+    fn table_names_alone_follow_the_search_path() {
+        let mut catalog = Catalog::new();
+        catalog.register_schema("main".to_string(), "staging".to_string());
+        catalog.register_table("public.t".to_string(), table());
+        catalog.register_table("public.u".to_string(), table());
+        catalog.register_table("staging.t".to_string(), table());
+
+        catalog.set_search_path("main".to_string(), vec!["staging".to_string(), "public".to_string()]);
+        assert_eq!(resolved(&catalog, "t").as_deref(), Some("main.staging.t"));
+        assert_eq!(resolved(&catalog, "U").as_deref(), Some("main.public.u"));
+        assert_eq!(catalog.qualify("new").unwrap().to_string(), "main.staging.new");
+
+        // Schemas that do not exist are skipped, and new tables need one that does.
+        catalog.set_search_path("main".to_string(), vec!["ghost".to_string(), "public".to_string()]);
+        assert_eq!(resolved(&catalog, "t").as_deref(), Some("main.public.t"));
+        assert_eq!(catalog.qualify("new").unwrap().to_string(), "main.public.new");
+        catalog.set_search_path("main".to_string(), vec!["ghost".to_string()]);
+        assert_eq!(resolved(&catalog, "t"), None);
+        assert!(matches!(
+            catalog.qualify("new"),
+            Err(QueryError::ValidationError { message }) if message == "No schema on the search path exists to put 'new' in; create one or USE another"
+        ));
+    }
+}
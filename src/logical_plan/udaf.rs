@@ -0,0 +1,157 @@
+use std::fmt::{Debug, Formatter};
+
+use crate::errors::QueryError;
+use crate::{ColumnVector, DataType, ScalarValue};
+
+/// A user-defined aggregate function that SQL can call by name once it is
+/// registered with `Catalog::register_udaf` or `ExecutionContext::register_udaf`.
+///
+/// The function itself holds no per-group data. Each group keeps a state of
+/// scalar values created by `init`, and the engine passes that state back into
+/// every other call. One group can be aggregated as several partial states that
+/// are later combined with `merge`.
+///
+/// A weighted average could keep `[sum of weight * value, sum of weight]` as its
+/// state, add to both in `update` and `merge`, and divide them in `finalize`.
+pub trait AggregateUdf {
+    /// The name SQL uses to call the function, matched case-insensitively.
+    fn name(&self) -> &str;
+
+    /// Checks the argument types and returns the type of the result.
+    fn return_type(&self, arg_types: &[DataType]) -> Result<DataType, String>;
+
+    /// The state of a group that has seen no rows.
+    fn init(&self) -> Vec<ScalarValue>;
+
+    /// Folds one batch of rows into `state`. `batch` holds one column per argument.
+    fn update(&self, state: &mut [ScalarValue], batch: &[ColumnVector]) -> Result<(), QueryError>;
+
+    /// Folds `other`, a partial state of the same group, into `state`.
+    fn merge(&self, state: &mut [ScalarValue], other: &[ScalarValue]) -> Result<(), QueryError>;
+
+    /// Produces the group's result from its final state.
+    fn finalize(&self, state: &[ScalarValue]) -> Result<ScalarValue, QueryError>;
+}
+
+impl Debug for dyn AggregateUdf {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "AggregateUdf({})", self.name())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::rc::Rc;
+
+    use super::*;
+    use crate::logical_plan::plan::Catalog;
+    use crate::physical_plan::accumulator::create_udaf_accumulator;
+    use crate::test_util::{error_message, float, int, rows, text};
+
+    /// `WAVG(value, weight)`, the weighted average from the trait's documentation.
+    struct WeightedAvg {
+        name: &'static str,
+    }
+
+    fn number(value: &ScalarValue) -> Option<f64> {
+        match value {
+            ScalarValue::Int32(v) => v.map(f64::from),
+            ScalarValue::Float64(v) => *v,
+            _ => None,
+        }
+    }
+
+    impl AggregateUdf for WeightedAvg {
+        fn name(&self) -> &str {
+            self.name
+        }
+
+        fn return_type(&self, arg_types: &[DataType]) -> Result<DataType, String> {
+            match arg_types {
+                [DataType::Int32 | DataType::Float64, DataType::Int32 | DataType::Float64] => Ok(DataType::Float64),
+                _ => Err(format!("{} takes a value and a weight", self.name)),
+            }
+        }
+
+        fn init(&self) -> Vec<ScalarValue> {
+            vec![ScalarValue::Float64(Some(0.0)), ScalarValue::Float64(Some(0.0))]
+        }
+
+        fn update(&self, state: &mut [ScalarValue], batch: &[ColumnVector]) -> Result<(), QueryError> {
+            let (mut total, mut weights) = (number(&state[0]).unwrap_or(0.0), number(&state[1]).unwrap_or(0.0));
+            for (value, weight) in batch[0].values.iter().zip(&batch[1].values) {
+                if let (Some(value), Some(weight)) = (number(value), number(weight)) {
+                    total += value * weight;
+                    weights += weight;
+                }
+            }
+            state[0] = ScalarValue::Float64(Some(total));
+            state[1] = ScalarValue::Float64(Some(weights));
+            Ok(())
+        }
+
+        fn merge(&self, state: &mut [ScalarValue], other: &[ScalarValue]) -> Result<(), QueryError> {
+            for (mine, theirs) in state.iter_mut().zip(other) {
+                *mine = ScalarValue::Float64(Some(number(mine).unwrap_or(0.0) + number(theirs).unwrap_or(0.0)));
+            }
+            Ok(())
+        }
+
+        fn finalize(&self, state: &[ScalarValue]) -> Result<ScalarValue, QueryError> {
+            Ok(match (number(&state[0]), number(&state[1])) {
+                (Some(total), Some(weights)) if weights != 0.0 => ScalarValue::Float64(Some(total / weights)),
+                _ => ScalarValue::Float64(None),
+            })
+        }
+    }
+
+    fn catalog_with_scores() -> Catalog {
+        let mut catalog = Catalog::new();
+        rows(&mut catalog, "CREATE TABLE scores (team VARCHAR, score INT, weight INT)");
+        rows(&mut catalog, "INSERT INTO scores VALUES ('a', 10, 1), ('a', 40, 2), ('b', 5, 3), ('b', NULL, 1)");
+        catalog
+    }
+
+    #[test]
+    fn registered_aggregate_is_callable_from_sql() {
+        let mut catalog = catalog_with_scores();
+        catalog.register_udaf(Rc::new(WeightedAvg { name: "wavg" })).unwrap();
+
+        let result = rows(&mut catalog, "SELECT team, WAVG(score, weight) FROM scores GROUP BY team ORDER BY team");
+        assert_eq!(result, vec![vec![text("a"), float(30.0)], vec![text("b"), float(5.0)]]);
+        assert_eq!(rows(&mut catalog, "SELECT wavg(score, weight) FROM scores WHERE team = 'x'"), vec![vec![ScalarValue::Float64(None)]]);
+    }
+
+    #[test]
+    fn registered_aggregate_checks_its_arguments() {
+        let mut catalog = catalog_with_scores();
+        catalog.register_udaf(Rc::new(WeightedAvg { name: "wavg" })).unwrap();
+
+        assert_eq!(error_message(&mut catalog, "SELECT WAVG(team, weight) FROM scores"), "wavg takes a value and a weight");
+        assert!(error_message(&mut catalog, "SELECT WAVG(SUM(score), weight) FROM scores").contains("cannot be nested"));
+    }
+
+    #[test]
+    fn names_of_builtin_functions_cannot_be_registered() {
+        let mut catalog = catalog_with_scores();
+
+        let Err(QueryError::ValidationError { message }) = catalog.register_udaf(Rc::new(WeightedAvg { name: "sum" })) else {
+            panic!("an aggregate named SUM was registered");
+        };
+        assert_eq!(message, "Cannot register aggregate 'sum': a built-in function has that name");
+        assert!(catalog.register_udaf(Rc::new(WeightedAvg { name: "Coalesce" })).is_err());
+        assert_eq!(rows(&mut catalog, "SELECT SUM(score) FROM scores"), vec![vec![int(55)]]);
+    }
+
+    #[test]
+    fn partial_states_merge() {
+        let udaf: Rc<dyn AggregateUdf> = Rc::new(WeightedAvg { name: "wavg" });
+        let mut first = create_udaf_accumulator(udaf.clone());
+        let mut second = create_udaf_accumulator(udaf);
+        first.update_batch(&[ColumnVector::new(vec![int(10)]), ColumnVector::new(vec![int(1)])]).unwrap();
+        second.update_batch(&[ColumnVector::new(vec![int(40)]), ColumnVector::new(vec![int(2)])]).unwrap();
+
+        first.merge(second.as_ref()).unwrap();
+        assert_eq!(first.evaluate().unwrap(), float(30.0));
+    }
+}
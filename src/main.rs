@@ -1,45 +1,29 @@
-use std::rc::Rc;
+use std::{fs, io::{self, BufWriter}, path::Path, rc::Rc};
 
+use clap::Parser;
 use vektur::{
-    RecordBatch, ScalarValue,
-    datasource::csv::CsvDataSource,
+    DataSource, SessionConfig,
+    cli::arg::Args,
+    datasource::{
+        compression::data_extension,
+        csv::{CsvDataSource, CsvReadOptions},
+        json::JsonDataSource,
+        parquet::ParquetDataSource,
+    },
+    errors::QueryError,
     errors::LexerError,
     logical_plan::plan::Catalog,
-    physical_plan::planner::create_physical_plan,
-    sql_support::sql::{parse_sql, sql_to_logical_plan},
+    sql_support::sql::{Statement, StatementResult, execute_statement, open_catalog_file, parse_sql},
+    writers::OutputFormat,
 };
 
-fn print_batch(batch: &RecordBatch, print_header: bool) {
-    let headers: Vec<&str> = batch.schema.fields.iter().map(|f| f.name.as_str()).collect();
-
-    if print_header {
-        println!("{}", headers.join(" | "));
-        println!("{}", "-".repeat(headers.join(" | ").len()));
-    }
-
-    if batch.columns.is_empty() {
-        return;
-    }
-
-    let row_count = batch.columns[0].values.len();
-    for row_i in 0..row_count {
-        let row: Vec<String> = batch
-            .columns
-            .iter()
-            .map(|col| match &col.values[row_i] {
-                ScalarValue::Int32(Some(v)) => v.to_string(),
-                ScalarValue::Float64(Some(v)) => v.to_string(),
-                ScalarValue::Bool(Some(v)) => v.to_string(),
-                ScalarValue::String(Some(v)) => v.clone(),
-                _ => "NULL".to_string(),
-            })
-            .collect();
-        println!("{}", row.join(" | "));
+/// Runs the SQL statements in `sql` one after another and writes the result of
+/// each query to stdout in `format`. With `verbose` the SQL and the row counts
+/// are printed around the results.
+fn execute_sql(sql: &str, catalog: &mut Catalog, format: OutputFormat, verbose: bool) {
+    if verbose {
+        println!("SQL: {}", sql);
     }
-}
-
-fn execute_sql(sql: &str, catalog: &Catalog) {
-    println!("SQL: {}", sql);
 
     let statements = match parse_sql(sql) {
         Ok(s) => s,
@@ -49,54 +33,126 @@ fn execute_sql(sql: &str, catalog: &Catalog) {
         }
     };
 
-    let statement = match statements.first() {
-        Some(s) => s,
-        None => {
-            println!("No statements found\n");
+    if statements.is_empty() {
+        println!("No statements found\n");
+    }
+    for statement in &statements {
+        run_statement(statement, catalog, format, verbose);
+    }
+}
+
+fn run_statement(statement: &Statement, catalog: &mut Catalog, format: OutputFormat, verbose: bool) {
+    let physical_plan = match execute_statement(statement, catalog, &SessionConfig::new()) {
+        Ok(StatementResult::Rows(plan)) => plan,
+        // COPY TO STDOUT output must stay clean of anything else.
+        Ok(StatementResult::Done(_)) if matches!(statement, Statement::Copy { .. }) && !verbose => return,
+        Ok(StatementResult::Done(message)) => {
+            println!("{}\n", message);
+            return;
+        },
+        Err(e) => {
+            println!("Error: {:?}\n", e);
             return;
         }
     };
 
-    let logical_plan = match sql_to_logical_plan(statement, catalog) {
-        Ok(p) => p,
-        Err(e) => {
-            println!("Logical plan error: {:?}\n", e);
+    let mut writer = format.writer(BufWriter::new(io::stdout()), physical_plan.schema());
+    let mut row_count = 0;
+    for batch_result in physical_plan.execute() {
+        let written = batch_result.and_then(|batch| {
+            row_count += batch.columns.first().map_or(0, |column| column.values.len());
+            writer.write(&batch)
+        });
+        if let Err(e) = written {
+            println!("Execution error: {:?}", e);
             return;
         }
-    };
+    }
+    if let Err(e) = writer.finish() {
+        println!("Execution error: {:?}", e);
+        return;
+    }
+    if verbose {
+        println!("({} rows)\n", row_count);
+    }
+}
 
-    let physical_plan = match create_physical_plan(&logical_plan, catalog) {
-        Ok(p) => p,
-        Err(e) => {
-            println!("Physical plan error: {:?}\n", e);
+/// Opens the data source named on the command line, reading CSV from stdin for `-`.
+fn open_source(file_path: &str) -> Result<Rc<dyn DataSource>, QueryError> {
+    if file_path == "-" {
+        return Ok(Rc::new(CsvDataSource::from_reader(Box::new(io::stdin()), CsvReadOptions::new())?));
+    }
+    Ok(match data_extension(Path::new(file_path)).as_deref() {
+        Some("json" | "jsonl" | "ndjson") => Rc::new(JsonDataSource::new(file_path.to_string())?),
+        Some("parquet") => Rc::new(ParquetDataSource::new(file_path.to_string())?),
+        _ => Rc::new(CsvDataSource::with_options(file_path.to_string(), CsvReadOptions::new().any_file_extension())?),
+    })
+}
+
+fn main() {
+    let args = Args::parse();
+    let query = match (&args.query, &args.query_path) {
+        (Some(query), _) => query.clone(),
+        (None, Some(query_path)) => match fs::read_to_string(query_path) {
+            Ok(query) => query.trim().to_string(),
+            Err(err) => {
+                println!("Error reading query file: {}", err);
+                return;
+            }
+        },
+        (None, None) if args.file_path.is_none() && args.catalog.is_none() => return run_demo(args.format),
+        (None, None) => {
+            println!("No query given: pass one with --query or --query-path");
             return;
         }
     };
 
-    let mut row_count = 0;
-    let mut first_batch = true;
-    for batch_result in physical_plan.execute() {
-        match batch_result {
-            Ok(batch) => {
-                let batch_rows = if batch.columns.is_empty() {
-                    0
-                } else {
-                    batch.columns[0].values.len()
-                };
-                print_batch(&batch, first_batch);
-                first_batch = false;
-                row_count += batch_rows;
+    // With a catalog the tables come from it, and stdin is only read when asked for.
+    // The file's table is registered first, so views in the catalog can read it.
+    let mut catalog = Catalog::new();
+    let file_path = match (&args.file_path, &args.catalog) {
+        (Some(file_path), _) => Some(file_path.as_str()),
+        (None, None) => Some("-"),
+        (None, Some(_)) => None,
+    };
+    let mut file_table = None;
+    if let Some(file_path) = file_path {
+        let table_name = args.table_name.clone().unwrap_or_else(|| match file_path {
+            "-" => "stdin".to_string(),
+            _ => {
+                let file_name = Path::new(file_path).file_name().map_or(String::new(), |name| name.to_string_lossy().into_owned());
+                file_name.split('.').next().unwrap_or_default().to_string()
+            }
+        });
+        match open_source(file_path) {
+            Ok(source) => {
+                file_table = catalog.qualify(&table_name).ok();
+                catalog.register_table(table_name, source);
             }
-            Err(e) => {
-                println!("Execution error: {:?}", e);
+            Err(err) => {
+                println!("Error loading {}: {:?}", file_path, err);
                 return;
             }
         }
     }
-    println!("({} rows)\n", row_count);
+    if let Some(catalog_path) = &args.catalog
+        && let Err(err) = open_catalog_file(Path::new(catalog_path), &mut catalog)
+    {
+        // The catalog may define a table of the same name as the file's.
+        let clash = file_table.filter(|name| {
+            matches!(&err, QueryError::ValidationError { message } if message.ends_with(&format!("'{}' is already registered", name)))
+        });
+        match clash {
+            Some(name) => println!("Error loading catalog: it also defines '{}', the table of --file-path; name that one with --table-name", name),
+            None => println!("Error loading catalog: {:?}", err),
+        }
+        return;
+    }
+    execute_sql(&query, &mut catalog, args.format, false);
 }
 
-fn main() {
+/// Runs a few queries against `test/students.csv`.
+fn run_demo(format: OutputFormat) {
     let mut catalog = Catalog::new();
     match CsvDataSource::new("test/students.csv".to_string()) {
         Ok(csv_source) => {
@@ -110,27 +166,39 @@ fn main() {
 
     execute_sql(
         "SELECT Name, Email FROM students WHERE IsVerified = true",
-        &catalog,
+        &mut catalog,
+        format,
+        true,
     );
-    execute_sql("SELECT * FROM students", &catalog);
+    execute_sql("SELECT * FROM students", &mut catalog, format, true);
     execute_sql(
         "SELECT Name FROM students WHERE \"S/N\" > 50",
-        &catalog,
+        &mut catalog,
+        format,
+        true,
     );
     execute_sql(
         "SELECT Name FROM students WHERE \"S/N\" >= 50",
-        &catalog,
+        &mut catalog,
+        format,
+        true,
     );
     execute_sql(
         "SELECT Name FROM students WHERE \"S/N\" <= 10",
-        &catalog,
+        &mut catalog,
+        format,
+        true,
     );
     execute_sql(
         "SELECT Name FROM students WHERE IsVerified != true",
-        &catalog,
+        &mut catalog,
+        format,
+        true,
     );
     execute_sql(
         "SELECT Name, \"S/N\" FROM students WHERE \"S/N\" > 0",
-        &catalog,
+        &mut catalog,
+        format,
+        true,
     );
 }
@@ -0,0 +1,47 @@
+use crate::{errors::QueryError, logical_plan::plan::{Catalog, LogicalPlan}};
+
+pub mod predicate_pushdown;
+pub mod projection_pushdown;
+
+use predicate_pushdown::PredicatePushdown;
+use projection_pushdown::ProjectionPushdown;
+
+/// A rewrite of a logical plan into an equivalent plan that is cheaper to execute.
+pub trait OptimizerRule {
+    /// A short name identifying the rule.
+    fn name(&self) -> &str;
+
+    /// Rewrites `plan`. The catalog gives access to the tables the plan scans.
+    fn optimize(&self, plan: &LogicalPlan, catalog: &Catalog) -> Result<LogicalPlan, QueryError>;
+}
+
+/// Runs a list of rules over a logical plan, each one on the output of the previous.
+pub struct Optimizer {
+    rules: Vec<Box<dyn OptimizerRule>>,
+}
+
+impl Default for Optimizer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Optimizer {
+    /// An optimizer with every built-in rule. Predicates are pushed first, so
+    /// projection pushdown sees which columns the remaining filters still read.
+    pub fn new() -> Self {
+        Self::with_rules(vec![Box::new(PredicatePushdown), Box::new(ProjectionPushdown)])
+    }
+
+    pub fn with_rules(rules: Vec<Box<dyn OptimizerRule>>) -> Self {
+        Self { rules }
+    }
+
+    pub fn optimize(&self, plan: &LogicalPlan, catalog: &Catalog) -> Result<LogicalPlan, QueryError> {
+        let mut plan = plan.clone();
+        for rule in &self.rules {
+            plan = rule.optimize(&plan, catalog)?;
+        }
+        Ok(plan)
+    }
+}
@@ -0,0 +1,116 @@
+use crate::{
+    datasource::FilterSupport,
+    errors::QueryError,
+    logical_plan::plan::{Catalog, LogicalPlan},
+};
+
+use super::OptimizerRule;
+
+/// Hands `Filter` predicates to the scan below them, so data sources that can
+/// evaluate a predicate drop rows before they are turned into batches. The
+/// `Filter` is removed when the source evaluates its predicate exactly.
+pub struct PredicatePushdown;
+
+impl OptimizerRule for PredicatePushdown {
+    fn name(&self) -> &str {
+        "predicate_pushdown"
+    }
+
+    fn optimize(&self, plan: &LogicalPlan, catalog: &Catalog) -> Result<LogicalPlan, QueryError> {
+        let optimize_input = |input: &LogicalPlan| self.optimize(input, catalog).map(Box::new);
+        Ok(match plan {
+            LogicalPlan::Filter { input, predicate } => {
+                let input = optimize_input(input)?;
+                let LogicalPlan::Scan { path, schema, projection, filters } = *input else {
+                    return Ok(LogicalPlan::Filter { input, predicate: predicate.clone() });
+                };
+                let support = catalog.get_source(&path)?.supports_filter(predicate);
+                let mut filters = filters;
+                if support != FilterSupport::Unsupported {
+                    filters.push(predicate.clone());
+                }
+                let scan = LogicalPlan::Scan { path, schema, projection, filters };
+                if support == FilterSupport::Exact {
+                    scan
+                } else {
+                    LogicalPlan::Filter { input: Box::new(scan), predicate: predicate.clone() }
+                }
+            },
+            LogicalPlan::Scan { .. } | LogicalPlan::Values { .. } => plan.clone(),
+            LogicalPlan::Projection { input, columns } => {
+                LogicalPlan::Projection { input: optimize_input(input)?, columns: columns.clone() }
+            },
+            LogicalPlan::Window { input, window_exprs } => {
+                LogicalPlan::Window { input: optimize_input(input)?, window_exprs: window_exprs.clone() }
+            },
+            LogicalPlan::Aggregate { input, group_by, aggr_exprs } => LogicalPlan::Aggregate {
+                input: optimize_input(input)?,
+                group_by: group_by.clone(),
+                aggr_exprs: aggr_exprs.clone(),
+            },
+            LogicalPlan::Distinct { input } => LogicalPlan::Distinct { input: optimize_input(input)? },
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::rc::Rc;
+
+    use crate::datasource::{csv::CsvDataSource, parquet::ParquetDataSource};
+    use crate::test_util::{int, optimized_plan, rows, text};
+
+    use super::*;
+
+    fn catalog() -> Catalog {
+        let mut catalog = Catalog::new();
+        catalog.register_table("students".to_string(), Rc::new(CsvDataSource::new("test/students.csv".to_string()).unwrap()));
+        catalog.register_table("sales".to_string(), Rc::new(ParquetDataSource::new("test/sales.parquet".to_string()).unwrap()));
+        catalog
+    }
+
+    /// Whether `plan` filters rows above its scan, and how many filters the scan holds.
+    fn filtering(plan: &LogicalPlan) -> (bool, usize) {
+        match plan {
+            LogicalPlan::Scan { filters, .. } => (false, filters.len()),
+            LogicalPlan::Filter { input, .. } => (true, filtering(input).1),
+            LogicalPlan::Projection { input, .. } | LogicalPlan::Aggregate { input, .. } => filtering(input),
+            other => panic!("unexpected node in {}", other),
+        }
+    }
+
+    #[test]
+    fn exact_sources_take_the_filter_over() {
+        let mut catalog = catalog();
+        let sql = "SELECT COUNT(*) FROM students WHERE Gender = 'Female'";
+        let plan = optimized_plan(&catalog, sql);
+        assert_eq!(filtering(&plan), (false, 1), "{}", plan);
+        let expected = rows(&mut catalog, "SELECT Gender FROM students").iter().filter(|row| row[0] == text("Female")).count();
+        assert_eq!(rows(&mut catalog, sql), vec![vec![int(expected as i32)]]);
+    }
+
+    #[test]
+    fn inexact_sources_keep_the_filter_above_the_scan() {
+        let mut catalog = catalog();
+        let plan = optimized_plan(&catalog, "SELECT id FROM sales WHERE id >= 8");
+        assert_eq!(filtering(&plan), (true, 1), "{}", plan);
+        // Row 8 shares a row group with rows 5 to 7, which the filter still drops.
+        assert_eq!(rows(&mut catalog, "SELECT id FROM sales WHERE id >= 8"), (8..=12).map(|id| vec![int(id)]).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn unsupported_predicates_stay_where_they_are() {
+        let mut catalog = catalog();
+        let plan = optimized_plan(&catalog, "SELECT Name FROM students WHERE Status = Gender");
+        assert_eq!(filtering(&plan), (true, 0));
+        // A filter on aggregated rows is not moved below the aggregate.
+        rows(&mut catalog, "CREATE VIEW counts AS SELECT Gender, COUNT(*) AS n FROM students GROUP BY Gender");
+        let plan = optimized_plan(&catalog, "SELECT Gender FROM counts WHERE n > 1");
+        assert!(
+            matches!(&plan, LogicalPlan::Projection { input, .. } if matches!(**input, LogicalPlan::Filter { .. })),
+            "{}",
+            plan
+        );
+        assert_eq!(filtering(&plan), (true, 0));
+    }
+}
@@ -0,0 +1,154 @@
+use std::collections::HashSet;
+
+use crate::{errors::QueryError, logical_plan::plan::{Catalog, Expression, LogicalPlan}};
+
+use super::OptimizerRule;
+
+/// Narrows every scan to the columns the rest of the plan reads, so data
+/// sources can skip the others. Filters held by a scan are evaluated by its
+/// source, which reads their columns itself.
+pub struct ProjectionPushdown;
+
+impl OptimizerRule for ProjectionPushdown {
+    fn name(&self) -> &str {
+        "projection_pushdown"
+    }
+
+    fn optimize(&self, plan: &LogicalPlan, _catalog: &Catalog) -> Result<LogicalPlan, QueryError> {
+        let required = plan.schema()?.fields.into_iter().map(|field| field.name).collect();
+        push_down(plan, required)
+    }
+}
+
+/// Rewrites `plan` so its scans only read the columns in `required` and the
+/// columns the plan itself uses.
+fn push_down(plan: &LogicalPlan, mut required: HashSet<String>) -> Result<LogicalPlan, QueryError> {
+    match plan {
+        LogicalPlan::Scan { path, schema, projection, filters } => {
+            let mut columns: Vec<String> = schema
+                .fields
+                .iter()
+                .map(|field| field.name.clone())
+                .filter(|name| required.contains(name))
+                .filter(|name| projection.as_ref().is_none_or(|projection| projection.contains(name)))
+                .collect();
+            if columns.is_empty() {
+                // Plans like `SELECT COUNT(*)` read no column but still need the
+                // row count, so keep the cheapest guess at one column.
+                let first = projection.as_ref().and_then(|p| p.first()).or(schema.fields.first().map(|f| &f.name));
+                columns.extend(first.cloned());
+            }
+            Ok(LogicalPlan::Scan {
+                path: path.clone(),
+                schema: schema.clone(),
+                projection: Some(columns),
+                filters: filters.clone(),
+            })
+        },
+        LogicalPlan::Filter { input, predicate } => {
+            predicate.collect_columns(&mut required);
+            Ok(LogicalPlan::Filter {
+                input: Box::new(push_down(input, required)?),
+                predicate: predicate.clone(),
+            })
+        },
+        LogicalPlan::Projection { input, columns } => {
+            Ok(LogicalPlan::Projection {
+                input: Box::new(push_down(input, columns_of(columns))?),
+                columns: columns.clone(),
+            })
+        },
+        LogicalPlan::Window { input, window_exprs } => {
+            for expr in window_exprs {
+                expr.collect_columns(&mut required);
+            }
+            Ok(LogicalPlan::Window {
+                input: Box::new(push_down(input, required)?),
+                window_exprs: window_exprs.clone(),
+            })
+        },
+        LogicalPlan::Aggregate { input, group_by, aggr_exprs } => {
+            let required = columns_of(group_by.iter().chain(aggr_exprs));
+            Ok(LogicalPlan::Aggregate {
+                input: Box::new(push_down(input, required)?),
+                group_by: group_by.clone(),
+                aggr_exprs: aggr_exprs.clone(),
+            })
+        },
+        LogicalPlan::Distinct { input } => {
+            // Every output column takes part in the comparison, and they are all required already.
+            Ok(LogicalPlan::Distinct {
+                input: Box::new(push_down(input, required)?),
+            })
+        },
+        LogicalPlan::Values { .. } => Ok(plan.clone()),
+    }
+}
+
+fn columns_of<'a>(exprs: impl IntoIterator<Item = &'a Expression>) -> HashSet<String> {
+    let mut columns = HashSet::new();
+    for expr in exprs {
+        expr.collect_columns(&mut columns);
+    }
+    columns
+}
+
+#[cfg(test)]
+mod tests {
+    use std::rc::Rc;
+
+    use crate::datasource::csv::CsvDataSource;
+    use crate::test_util::optimized_plan;
+
+    use super::*;
+
+    fn catalog() -> Catalog {
+        let mut catalog = Catalog::new();
+        catalog.register_table("students".to_string(), Rc::new(CsvDataSource::new("test/students.csv".to_string()).unwrap()));
+        catalog
+    }
+
+    /// The columns the scan at the bottom of `plan` reads.
+    fn scanned_columns(plan: &LogicalPlan) -> Vec<&str> {
+        match plan {
+            LogicalPlan::Scan { projection: Some(columns), .. } => columns.iter().map(String::as_str).collect(),
+            LogicalPlan::Filter { input, .. }
+            | LogicalPlan::Projection { input, .. }
+            | LogicalPlan::Window { input, .. }
+            | LogicalPlan::Aggregate { input, .. }
+            | LogicalPlan::Distinct { input } => scanned_columns(input),
+            other => panic!("no narrowed scan in {}", other),
+        }
+    }
+
+    #[test]
+    fn scans_read_only_the_columns_the_query_uses() {
+        let catalog = catalog();
+        // Columns come in the order of the table, not of the query.
+        assert_eq!(scanned_columns(&optimized_plan(&catalog, "SELECT Email, Name FROM students")), ["Name", "Email"]);
+        assert_eq!(
+            scanned_columns(&optimized_plan(&catalog, "SELECT Gender, COUNT(Name) FROM students GROUP BY Gender")),
+            ["Name", "Gender"],
+        );
+        assert_eq!(
+            scanned_columns(&optimized_plan(&catalog, "SELECT Name, ROW_NUMBER() OVER (PARTITION BY Gender ORDER BY \"Join Date\") FROM students")),
+            ["Name", "Gender", "Join Date"],
+        );
+    }
+
+    #[test]
+    fn filters_the_scan_holds_do_not_widen_it() {
+        let catalog = catalog();
+        // The CSV source evaluates the comparison itself, so `Status` is not passed up.
+        let plan = optimized_plan(&catalog, "SELECT Name FROM students WHERE Status = 'Verified'");
+        assert_eq!(scanned_columns(&plan), ["Name"]);
+        // Filters left in the plan need their columns.
+        let plan = optimized_plan(&catalog, "SELECT Name FROM students WHERE Status = Gender");
+        assert_eq!(scanned_columns(&plan), ["Name", "Gender", "Status"]);
+    }
+
+    #[test]
+    fn queries_that_read_no_column_still_scan_one() {
+        assert_eq!(scanned_columns(&optimized_plan(&catalog(), "SELECT COUNT(*) FROM students")), ["S/N"]);
+    }
+}
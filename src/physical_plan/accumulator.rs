@@ -0,0 +1,777 @@
+use std::any::Any;
+use std::cmp::Ordering;
+use std::collections::HashSet;
+use std::rc::Rc;
+
+use crate::errors::QueryError;
+use crate::logical_plan::plan::AggregateFunction;
+use crate::logical_plan::udaf::AggregateUdf;
+use crate::{ColumnVector, DataType, ScalarValue};
+
+use super::eval::{compare_sort_values, compare_values};
+use super::hyperloglog::HyperLogLog;
+
+/// Folds batches of argument values into a single result.
+///
+/// `values` holds one column per aggregate argument followed by one column per
+/// ORDER BY key, all of the same length. Accumulators of the same kind can be
+/// merged, so a group can be aggregated in several independent parts and then combined.
+pub trait Accumulator {
+    fn update_batch(&mut self, values: &[ColumnVector]) -> Result<(), QueryError>;
+    /// Folds the partial state of `other`, which must be the same kind of accumulator, into this one.
+    fn merge(&mut self, other: &dyn Accumulator) -> Result<(), QueryError>;
+    fn evaluate(&self) -> Result<ScalarValue, QueryError>;
+    fn as_any(&self) -> &dyn Any;
+}
+
+/// Direction and null placement of one ORDER BY key of an ordered aggregate.
+#[derive(Debug, Clone, Copy)]
+pub struct SortOrder {
+    pub asc: bool,
+    pub nulls_first: bool,
+}
+
+pub fn create_accumulator(
+    func: AggregateFunction,
+    distinct: bool,
+    return_type: DataType,
+    order_by: &[SortOrder],
+) -> Result<Box<dyn Accumulator>, QueryError> {
+    if distinct && func != AggregateFunction::Count {
+        return Err(QueryError::ValidationError {
+            message: format!("DISTINCT is not supported for {:?}", func),
+        });
+    }
+    let asc = order_by.first().is_none_or(|order| order.asc);
+    Ok(match func {
+        AggregateFunction::Count if distinct => Box::new(DistinctCountAccumulator::default()),
+        AggregateFunction::Count => Box::new(CountAccumulator::default()),
+        AggregateFunction::Sum => Box::new(SumAccumulator::new(return_type)),
+        AggregateFunction::Avg => Box::new(AvgAccumulator::default()),
+        AggregateFunction::Min => Box::new(MinMaxAccumulator { current: None, keep: Ordering::Less }),
+        AggregateFunction::Max => Box::new(MinMaxAccumulator { current: None, keep: Ordering::Greater }),
+        AggregateFunction::ApproxCountDistinct => Box::new(ApproxCountDistinctAccumulator::default()),
+        AggregateFunction::StddevSamp => Box::new(VarianceAccumulator::new(true, true)),
+        AggregateFunction::StddevPop => Box::new(VarianceAccumulator::new(false, true)),
+        AggregateFunction::VarSamp => Box::new(VarianceAccumulator::new(true, false)),
+        AggregateFunction::VarPop => Box::new(VarianceAccumulator::new(false, false)),
+        AggregateFunction::Corr => Box::new(CorrelationAccumulator::default()),
+        AggregateFunction::Median => Box::new(PercentileAccumulator::new(Percentile::Median, true)),
+        AggregateFunction::PercentileCont => Box::new(PercentileAccumulator::new(Percentile::Continuous, asc)),
+        AggregateFunction::PercentileDisc => Box::new(PercentileAccumulator::new(Percentile::Discrete, asc)),
+        AggregateFunction::StringAgg => Box::new(StringAggAccumulator { rows: OrderedRows::new(order_by) }),
+        AggregateFunction::ArrayAgg => Box::new(ArrayAggAccumulator { rows: OrderedRows::new(order_by) }),
+        AggregateFunction::BoolAnd => Box::new(BoolAccumulator { result: None, is_and: true }),
+        AggregateFunction::BoolOr => Box::new(BoolAccumulator { result: None, is_and: false }),
+    })
+}
+
+/// Creates an accumulator that keeps the state of a user-defined aggregate.
+pub fn create_udaf_accumulator(udaf: Rc<dyn AggregateUdf>) -> Box<dyn Accumulator> {
+    let state = udaf.init();
+    Box::new(UdafAccumulator { udaf, state })
+}
+
+/// Downcasts the accumulator handed to `merge` to the caller's own type.
+fn downcast<T: 'static>(other: &dyn Accumulator) -> Result<&T, QueryError> {
+    other.as_any().downcast_ref::<T>().ok_or_else(|| QueryError::ValidationError {
+        message: "Cannot merge accumulators of different kinds".to_string(),
+    })
+}
+
+fn count_to_scalar(count: u64) -> Result<ScalarValue, QueryError> {
+    let count = i32::try_from(count).map_err(|_| QueryError::ValidationError {
+        message: "COUNT overflowed Int32".to_string(),
+    })?;
+    Ok(ScalarValue::Int32(Some(count)))
+}
+
+fn as_f64(value: &ScalarValue) -> Option<f64> {
+    match value {
+        ScalarValue::Int32(Some(v)) => Some(f64::from(*v)),
+        ScalarValue::Float64(Some(v)) => Some(*v),
+        _ => None,
+    }
+}
+
+/// Counts non-null values.
+#[derive(Default)]
+struct CountAccumulator {
+    count: u64,
+}
+
+impl Accumulator for CountAccumulator {
+    fn update_batch(&mut self, values: &[ColumnVector]) -> Result<(), QueryError> {
+        self.count += values[0].values.iter().filter(|v| !v.is_null()).count() as u64;
+        Ok(())
+    }
+
+    fn merge(&mut self, other: &dyn Accumulator) -> Result<(), QueryError> {
+        self.count += downcast::<Self>(other)?.count;
+        Ok(())
+    }
+
+    fn evaluate(&self) -> Result<ScalarValue, QueryError> {
+        count_to_scalar(self.count)
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+/// Counts distinct non-null values exactly by remembering every one of them.
+#[derive(Default)]
+struct DistinctCountAccumulator {
+    seen: HashSet<ScalarValue>,
+}
+
+impl Accumulator for DistinctCountAccumulator {
+    fn update_batch(&mut self, values: &[ColumnVector]) -> Result<(), QueryError> {
+        for value in values[0].values.iter().filter(|v| !v.is_null()) {
+            if !self.seen.contains(value) {
+                self.seen.insert(value.clone());
+            }
+        }
+        Ok(())
+    }
+
+    fn merge(&mut self, other: &dyn Accumulator) -> Result<(), QueryError> {
+        self.seen.extend(downcast::<Self>(other)?.seen.iter().cloned());
+        Ok(())
+    }
+
+    fn evaluate(&self) -> Result<ScalarValue, QueryError> {
+        count_to_scalar(self.seen.len() as u64)
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+/// Estimates distinct non-null values with a fixed-size HyperLogLog sketch.
+#[derive(Default)]
+struct ApproxCountDistinctAccumulator {
+    sketch: HyperLogLog,
+}
+
+impl Accumulator for ApproxCountDistinctAccumulator {
+    fn update_batch(&mut self, values: &[ColumnVector]) -> Result<(), QueryError> {
+        for value in values[0].values.iter().filter(|v| !v.is_null()) {
+            self.sketch.add(value);
+        }
+        Ok(())
+    }
+
+    fn merge(&mut self, other: &dyn Accumulator) -> Result<(), QueryError> {
+        self.sketch.merge(&downcast::<Self>(other)?.sketch);
+        Ok(())
+    }
+
+    fn evaluate(&self) -> Result<ScalarValue, QueryError> {
+        count_to_scalar(self.sketch.count())
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+/// Sums non-null values. Int32 input is summed in i64 and must fit back into Int32.
+struct SumAccumulator {
+    int_sum: i64,
+    float_sum: f64,
+    seen_value: bool,
+    return_type: DataType,
+}
+
+impl SumAccumulator {
+    fn new(return_type: DataType) -> Self {
+        Self {
+            int_sum: 0,
+            float_sum: 0.0,
+            seen_value: false,
+            return_type,
+        }
+    }
+}
+
+impl Accumulator for SumAccumulator {
+    fn update_batch(&mut self, values: &[ColumnVector]) -> Result<(), QueryError> {
+        for value in &values[0].values {
+            match value {
+                ScalarValue::Int32(Some(v)) => self.int_sum += i64::from(*v),
+                ScalarValue::Float64(Some(v)) => self.float_sum += v,
+                _ => continue,
+            }
+            self.seen_value = true;
+        }
+        Ok(())
+    }
+
+    fn merge(&mut self, other: &dyn Accumulator) -> Result<(), QueryError> {
+        let other = downcast::<Self>(other)?;
+        self.int_sum += other.int_sum;
+        self.float_sum += other.float_sum;
+        self.seen_value |= other.seen_value;
+        Ok(())
+    }
+
+    fn evaluate(&self) -> Result<ScalarValue, QueryError> {
+        if !self.seen_value {
+            return Ok(ScalarValue::null(self.return_type));
+        }
+        match self.return_type {
+            DataType::Int32 => {
+                let sum = i32::try_from(self.int_sum).map_err(|_| QueryError::ValidationError {
+                    message: "SUM overflowed Int32".to_string(),
+                })?;
+                Ok(ScalarValue::Int32(Some(sum)))
+            }
+            _ => Ok(ScalarValue::Float64(Some(self.float_sum + self.int_sum as f64))),
+        }
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+#[derive(Default)]
+struct AvgAccumulator {
+    sum: f64,
+    count: u64,
+}
+
+impl Accumulator for AvgAccumulator {
+    fn update_batch(&mut self, values: &[ColumnVector]) -> Result<(), QueryError> {
+        for value in values[0].values.iter().filter_map(as_f64) {
+            self.sum += value;
+            self.count += 1;
+        }
+        Ok(())
+    }
+
+    fn merge(&mut self, other: &dyn Accumulator) -> Result<(), QueryError> {
+        let other = downcast::<Self>(other)?;
+        self.sum += other.sum;
+        self.count += other.count;
+        Ok(())
+    }
+
+    fn evaluate(&self) -> Result<ScalarValue, QueryError> {
+        if self.count == 0 {
+            Ok(ScalarValue::Float64(None))
+        } else {
+            Ok(ScalarValue::Float64(Some(self.sum / self.count as f64)))
+        }
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+/// Keeps the smallest (`keep: Less`) or largest (`keep: Greater`) non-null value.
+struct MinMaxAccumulator {
+    current: Option<ScalarValue>,
+    keep: Ordering,
+}
+
+impl MinMaxAccumulator {
+    fn offer(&mut self, value: &ScalarValue) {
+        let replace = match &self.current {
+            None => true,
+            Some(current) => compare_values(value, current) == self.keep,
+        };
+        if replace {
+            self.current = Some(value.clone());
+        }
+    }
+}
+
+impl Accumulator for MinMaxAccumulator {
+    fn update_batch(&mut self, values: &[ColumnVector]) -> Result<(), QueryError> {
+        for value in values[0].values.iter().filter(|v| !v.is_null()) {
+            self.offer(value);
+        }
+        Ok(())
+    }
+
+    fn merge(&mut self, other: &dyn Accumulator) -> Result<(), QueryError> {
+        if let Some(value) = &downcast::<Self>(other)?.current {
+            self.offer(value);
+        }
+        Ok(())
+    }
+
+    fn evaluate(&self) -> Result<ScalarValue, QueryError> {
+        Ok(self.current.clone().unwrap_or(ScalarValue::Int32(None)))
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+/// Running count, mean and sum of squared deviations (Welford's method), which
+/// stays numerically stable and merges with Chan's formula.
+#[derive(Default, Clone, Copy)]
+struct Moments {
+    count: f64,
+    mean: f64,
+    m2: f64,
+}
+
+impl Moments {
+    fn add(&mut self, value: f64) {
+        self.count += 1.0;
+        let delta = value - self.mean;
+        self.mean += delta / self.count;
+        self.m2 += delta * (value - self.mean);
+    }
+
+    fn merge(&mut self, other: &Moments) {
+        if other.count == 0.0 {
+            return;
+        }
+        let count = self.count + other.count;
+        let delta = other.mean - self.mean;
+        self.m2 += other.m2 + delta * delta * self.count * other.count / count;
+        self.mean += delta * other.count / count;
+        self.count = count;
+    }
+}
+
+/// VAR_SAMP, VAR_POP and their square roots STDDEV_SAMP and STDDEV_POP.
+struct VarianceAccumulator {
+    moments: Moments,
+    sample: bool,
+    sqrt: bool,
+}
+
+impl VarianceAccumulator {
+    fn new(sample: bool, sqrt: bool) -> Self {
+        Self {
+            moments: Moments::default(),
+            sample,
+            sqrt,
+        }
+    }
+}
+
+impl Accumulator for VarianceAccumulator {
+    fn update_batch(&mut self, values: &[ColumnVector]) -> Result<(), QueryError> {
+        for value in values[0].values.iter().filter_map(as_f64) {
+            self.moments.add(value);
+        }
+        Ok(())
+    }
+
+    fn merge(&mut self, other: &dyn Accumulator) -> Result<(), QueryError> {
+        self.moments.merge(&downcast::<Self>(other)?.moments);
+        Ok(())
+    }
+
+    fn evaluate(&self) -> Result<ScalarValue, QueryError> {
+        let divisor = if self.sample { self.moments.count - 1.0 } else { self.moments.count };
+        if divisor <= 0.0 {
+            return Ok(ScalarValue::Float64(None));
+        }
+        let variance = self.moments.m2 / divisor;
+        Ok(ScalarValue::Float64(Some(if self.sqrt { variance.sqrt() } else { variance })))
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+/// Pearson correlation of two columns over the rows where both are non-null.
+#[derive(Default)]
+struct CorrelationAccumulator {
+    x: Moments,
+    y: Moments,
+    /// Sum of (x - mean_x) * (y - mean_y).
+    co_moment: f64,
+}
+
+impl Accumulator for CorrelationAccumulator {
+    fn update_batch(&mut self, values: &[ColumnVector]) -> Result<(), QueryError> {
+        for (x, y) in values[0].values.iter().zip(&values[1].values) {
+            if let (Some(x), Some(y)) = (as_f64(x), as_f64(y)) {
+                let delta_x = x - self.x.mean;
+                self.x.add(x);
+                self.y.add(y);
+                self.co_moment += delta_x * (y - self.y.mean);
+            }
+        }
+        Ok(())
+    }
+
+    fn merge(&mut self, other: &dyn Accumulator) -> Result<(), QueryError> {
+        let other = downcast::<Self>(other)?;
+        if other.x.count == 0.0 {
+            return Ok(());
+        }
+        let count = self.x.count + other.x.count;
+        let delta_x = other.x.mean - self.x.mean;
+        let delta_y = other.y.mean - self.y.mean;
+        self.co_moment += other.co_moment + delta_x * delta_y * self.x.count * other.x.count / count;
+        self.x.merge(&other.x);
+        self.y.merge(&other.y);
+        Ok(())
+    }
+
+    fn evaluate(&self) -> Result<ScalarValue, QueryError> {
+        let denominator = (self.x.m2 * self.y.m2).sqrt();
+        if self.x.count < 2.0 || denominator == 0.0 {
+            return Ok(ScalarValue::Float64(None));
+        }
+        Ok(ScalarValue::Float64(Some(self.co_moment / denominator)))
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Percentile {
+    /// MEDIAN(x): the continuous 0.5 percentile of its only argument.
+    Median,
+    /// PERCENTILE_CONT(p) WITHIN GROUP (ORDER BY x): interpolates between neighbouring values.
+    Continuous,
+    /// PERCENTILE_DISC(p) WITHIN GROUP (ORDER BY x): the first value whose position reaches p.
+    Discrete,
+}
+
+/// Buffers every non-null value of the group and picks the percentile once all are known.
+struct PercentileAccumulator {
+    kind: Percentile,
+    asc: bool,
+    fraction: Option<f64>,
+    values: Vec<ScalarValue>,
+}
+
+impl PercentileAccumulator {
+    fn new(kind: Percentile, asc: bool) -> Self {
+        Self {
+            kind,
+            asc,
+            fraction: if kind == Percentile::Median { Some(0.5) } else { None },
+            values: Vec::new(),
+        }
+    }
+}
+
+impl Accumulator for PercentileAccumulator {
+    fn update_batch(&mut self, values: &[ColumnVector]) -> Result<(), QueryError> {
+        // For the percentile functions the first column is the constant fraction
+        // and the second is the WITHIN GROUP ordering key.
+        let column = if self.kind == Percentile::Median {
+            &values[0]
+        } else {
+            if self.fraction.is_none() {
+                self.fraction = values[0].values.first().and_then(as_f64);
+            }
+            &values[1]
+        };
+        self.values.extend(column.values.iter().filter(|v| !v.is_null()).cloned());
+        Ok(())
+    }
+
+    fn merge(&mut self, other: &dyn Accumulator) -> Result<(), QueryError> {
+        let other = downcast::<Self>(other)?;
+        self.fraction = self.fraction.or(other.fraction);
+        self.values.extend(other.values.iter().cloned());
+        Ok(())
+    }
+
+    fn evaluate(&self) -> Result<ScalarValue, QueryError> {
+        let fraction = match self.fraction {
+            Some(fraction) => fraction,
+            None => return Ok(ScalarValue::Float64(None)),
+        };
+        if self.values.is_empty() {
+            return Ok(ScalarValue::Float64(None));
+        }
+        let mut sorted = self.values.clone();
+        sorted.sort_by(|a, b| compare_sort_values(a, b, self.asc, false));
+
+        let last = sorted.len() - 1;
+        match self.kind {
+            Percentile::Discrete => {
+                let position = (fraction * sorted.len() as f64).ceil() as usize;
+                Ok(sorted[position.saturating_sub(1).min(last)].clone())
+            }
+            Percentile::Median | Percentile::Continuous => {
+                let position = fraction * last as f64;
+                let (lower, upper) = (position.floor() as usize, position.ceil() as usize);
+                match (as_f64(&sorted[lower]), as_f64(&sorted[upper])) {
+                    (Some(low), Some(high)) => {
+                        Ok(ScalarValue::Float64(Some(low + (high - low) * (position - lower as f64))))
+                    }
+                    _ => Ok(ScalarValue::Float64(None)),
+                }
+            }
+        }
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+/// Buffered rows of an ordered aggregate: the argument values plus the ORDER BY keys.
+struct OrderedRows {
+    order_by: Vec<SortOrder>,
+    rows: Vec<(Vec<ScalarValue>, Vec<ScalarValue>)>,
+}
+
+impl OrderedRows {
+    fn new(order_by: &[SortOrder]) -> Self {
+        Self {
+            order_by: order_by.to_vec(),
+            rows: Vec::new(),
+        }
+    }
+
+    /// Buffers every row; the first `arg_count` columns are arguments, the rest are keys.
+    fn update(&mut self, values: &[ColumnVector], arg_count: usize) {
+        let row_count = values.first().map_or(0, |col| col.values.len());
+        for row in 0..row_count {
+            let args = values[..arg_count].iter().map(|col| col.values[row].clone()).collect();
+            let keys = values[arg_count..].iter().map(|col| col.values[row].clone()).collect();
+            self.rows.push((args, keys));
+        }
+    }
+
+    /// Argument rows in ORDER BY order, or in arrival order without one.
+    fn sorted(&self) -> Vec<&Vec<ScalarValue>> {
+        let mut rows: Vec<&(Vec<ScalarValue>, Vec<ScalarValue>)> = self.rows.iter().collect();
+        rows.sort_by(|(_, a), (_, b)| {
+            a.iter()
+                .zip(b)
+                .zip(&self.order_by)
+                .map(|((a, b), order)| compare_sort_values(a, b, order.asc, order.nulls_first))
+                .find(|o| *o != Ordering::Equal)
+                .unwrap_or(Ordering::Equal)
+        });
+        rows.into_iter().map(|(args, _)| args).collect()
+    }
+}
+
+/// STRING_AGG(value, separator [ORDER BY ...]): joins non-null values, putting
+/// each row's separator in front of every value but the first.
+struct StringAggAccumulator {
+    rows: OrderedRows,
+}
+
+impl Accumulator for StringAggAccumulator {
+    fn update_batch(&mut self, values: &[ColumnVector]) -> Result<(), QueryError> {
+        self.rows.update(values, 2);
+        Ok(())
+    }
+
+    fn merge(&mut self, other: &dyn Accumulator) -> Result<(), QueryError> {
+        self.rows.rows.extend(downcast::<Self>(other)?.rows.rows.iter().cloned());
+        Ok(())
+    }
+
+    fn evaluate(&self) -> Result<ScalarValue, QueryError> {
+        let mut result: Option<String> = None;
+        for args in self.rows.sorted() {
+            if let ScalarValue::String(Some(value)) = &args[0] {
+                match &mut result {
+                    None => result = Some(value.clone()),
+                    Some(joined) => {
+                        if let ScalarValue::String(Some(separator)) = &args[1] {
+                            joined.push_str(separator);
+                        }
+                        joined.push_str(value);
+                    }
+                }
+            }
+        }
+        Ok(ScalarValue::String(result))
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+/// ARRAY_AGG(value [ORDER BY ...]). Vektur has no array type, so the result is
+/// the PostgreSQL text form of the array, e.g. `{1,2,NULL}` or `{"a b",c}`.
+struct ArrayAggAccumulator {
+    rows: OrderedRows,
+}
+
+impl Accumulator for ArrayAggAccumulator {
+    fn update_batch(&mut self, values: &[ColumnVector]) -> Result<(), QueryError> {
+        self.rows.update(values, 1);
+        Ok(())
+    }
+
+    fn merge(&mut self, other: &dyn Accumulator) -> Result<(), QueryError> {
+        self.rows.rows.extend(downcast::<Self>(other)?.rows.rows.iter().cloned());
+        Ok(())
+    }
+
+    fn evaluate(&self) -> Result<ScalarValue, QueryError> {
+        let sorted = self.rows.sorted();
+        if sorted.is_empty() {
+            return Ok(ScalarValue::String(None));
+        }
+        let elements: Vec<String> = sorted.iter().map(|args| array_element_text(&args[0])).collect();
+        Ok(ScalarValue::String(Some(format!("{{{}}}", elements.join(",")))))
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+fn array_element_text(value: &ScalarValue) -> String {
+    match value {
+        ScalarValue::Int32(Some(v)) => v.to_string(),
+        ScalarValue::Float64(Some(v)) => v.to_string(),
+        ScalarValue::Bool(Some(v)) => if *v { "t".to_string() } else { "f".to_string() },
+        ScalarValue::String(Some(v)) => {
+            let needs_quotes = v.is_empty()
+                || v.eq_ignore_ascii_case("NULL")
+                || v.chars().any(|c| c.is_whitespace() || matches!(c, ',' | '{' | '}' | '"' | '\\'));
+            if needs_quotes {
+                format!("\"{}\"", v.replace('\\', "\\\\").replace('"', "\\\""))
+            } else {
+                v.clone()
+            }
+        }
+        _ => "NULL".to_string(),
+    }
+}
+
+/// BOOL_AND / BOOL_OR over the non-null values.
+struct BoolAccumulator {
+    result: Option<bool>,
+    is_and: bool,
+}
+
+impl BoolAccumulator {
+    fn combine(&mut self, value: bool) {
+        self.result = Some(match self.result {
+            None => value,
+            Some(current) if self.is_and => current && value,
+            Some(current) => current || value,
+        });
+    }
+}
+
+impl Accumulator for BoolAccumulator {
+    fn update_batch(&mut self, values: &[ColumnVector]) -> Result<(), QueryError> {
+        for value in &values[0].values {
+            if let ScalarValue::Bool(Some(value)) = value {
+                self.combine(*value);
+            }
+        }
+        Ok(())
+    }
+
+    fn merge(&mut self, other: &dyn Accumulator) -> Result<(), QueryError> {
+        if let Some(value) = downcast::<Self>(other)?.result {
+            self.combine(value);
+        }
+        Ok(())
+    }
+
+    fn evaluate(&self) -> Result<ScalarValue, QueryError> {
+        Ok(ScalarValue::Bool(self.result))
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+/// Holds one group's state for a user-defined aggregate and forwards every call to it.
+struct UdafAccumulator {
+    udaf: Rc<dyn AggregateUdf>,
+    state: Vec<ScalarValue>,
+}
+
+impl Accumulator for UdafAccumulator {
+    fn update_batch(&mut self, values: &[ColumnVector]) -> Result<(), QueryError> {
+        self.udaf.update(&mut self.state, values)
+    }
+
+    fn merge(&mut self, other: &dyn Accumulator) -> Result<(), QueryError> {
+        let other = downcast::<Self>(other)?;
+        self.udaf.merge(&mut self.state, &other.state)
+    }
+
+    fn evaluate(&self) -> Result<ScalarValue, QueryError> {
+        self.udaf.finalize(&self.state)
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::logical_plan::plan::Catalog;
+    use crate::test_util::{error_message, float, int, rows, text};
+
+    use super::*;
+
+    /// A catalog with table `s`, in which `y` is always twice `x`.
+    fn catalog() -> Catalog {
+        let mut catalog = Catalog::new();
+        rows(&mut catalog, "CREATE TABLE s (g TEXT, n TEXT, x INT, y FLOAT, ok BOOLEAN); INSERT INTO s VALUES \
+            ('a', 'p', 2, 4.0, true), ('a', 'q', 4, 8.0, true), ('a', 'r', 4, 8.0, false), ('b', 's', 4, 8.0, true), \
+            ('b', 't', 5, 10.0, true), ('b', 'u', 5, 10.0, true), ('b', 'v', 7, 14.0, true), ('b', 'w', 9, 18.0, true), \
+            ('b', NULL, NULL, NULL, NULL)");
+        catalog
+    }
+
+    #[test]
+    fn statistical_aggregates_skip_nulls() {
+        let mut catalog = catalog();
+        let row = rows(&mut catalog, "SELECT STDDEV_POP(x), VAR_POP(x), VAR_SAMP(x), STDDEV(x), CORR(x, y) FROM s").remove(0);
+        let values: Vec<f64> = row.iter().map(|value| as_f64(value).unwrap()).collect();
+        let expected = [2.0, 4.0, 32.0 / 7.0, (32.0f64 / 7.0).sqrt(), 1.0];
+        for (value, expected) in values.iter().zip(expected) {
+            assert!((value - expected).abs() < 1e-9, "{:?} is not {:?}", values, expected);
+        }
+        // A sample of one value has no variance.
+        assert_eq!(rows(&mut catalog, "SELECT STDDEV(x), VAR_POP(x) FROM s WHERE x = 2"), vec![vec![ScalarValue::Float64(None), float(0.0)]]);
+    }
+
+    #[test]
+    fn ordered_set_aggregates_interpolate_or_pick_a_value() {
+        let mut catalog = catalog();
+        let sql = "SELECT MEDIAN(x), PERCENTILE_CONT(0.1) WITHIN GROUP (ORDER BY x), PERCENTILE_DISC(0.1) WITHIN GROUP (ORDER BY x), \
+                   PERCENTILE_DISC(0.5) WITHIN GROUP (ORDER BY x DESC) FROM s";
+        let row = rows(&mut catalog, sql).remove(0);
+        assert_eq!(row[0], float(4.5));
+        assert!((as_f64(&row[1]).unwrap() - 3.4).abs() < 1e-9, "{:?}", row);
+        assert_eq!(row[2..], [int(2), int(5)]);
+        assert_eq!(
+            error_message(&mut catalog, "SELECT COUNT(x) WITHIN GROUP (ORDER BY x) FROM s"),
+            "WITHIN GROUP is only supported for ordered-set aggregates: COUNT(x) WITHIN GROUP (ORDER BY x)",
+        );
+    }
+
+    #[test]
+    fn collecting_aggregates_follow_their_order_by() {
+        let mut catalog = catalog();
+        let sql = "SELECT g, STRING_AGG(n, '-' ORDER BY x DESC, n), ARRAY_AGG(x ORDER BY x DESC), BOOL_AND(ok), BOOL_OR(ok) FROM s GROUP BY g";
+        assert_eq!(rows(&mut catalog, sql), vec![
+            vec![text("a"), text("q-r-p"), text("{4,4,2}"), ScalarValue::Bool(Some(false)), ScalarValue::Bool(Some(true))],
+            vec![text("b"), text("w-v-t-u-s"), text("{NULL,9,7,5,5,4}"), ScalarValue::Bool(Some(true)), ScalarValue::Bool(Some(true))],
+        ]);
+    }
+}
@@ -0,0 +1,192 @@
+use std::collections::HashMap;
+use std::iter;
+use std::rc::Rc;
+
+use crate::errors::QueryError;
+use crate::logical_plan::plan::AggregateFunction;
+use crate::logical_plan::udaf::AggregateUdf;
+use crate::{ColumnVector, DataType, RecordBatch, ScalarValue, Schema};
+
+use super::accumulator::{Accumulator, SortOrder, create_accumulator, create_udaf_accumulator};
+use super::eval::{PhysicalExpr, PhysicalSortExpr};
+use super::plan::PhysicalPlan;
+
+type Accumulators = Vec<Box<dyn Accumulator>>;
+
+/// Where an aggregate call gets its accumulators from.
+pub enum AggregateKind {
+    Builtin { func: AggregateFunction, distinct: bool },
+    Udf(Rc<dyn AggregateUdf>),
+}
+
+/// One aggregate call: which function, its argument expressions, the ORDER BY
+/// keys its accumulator sees after the arguments, and the result type.
+pub struct AggregateExpr {
+    pub kind: AggregateKind,
+    pub args: Vec<Box<dyn PhysicalExpr>>,
+    pub order_by: Vec<PhysicalSortExpr>,
+    pub return_type: DataType,
+}
+
+/// Groups rows by hashing the grouping key and feeds each group's rows into its
+/// own accumulators. Groups are emitted in the order they were first seen.
+pub struct HashAggregateExec {
+    pub input: Box<dyn PhysicalPlan>,
+    pub schema: Schema,
+    pub group_by: Vec<Box<dyn PhysicalExpr>>,
+    pub aggr_exprs: Vec<AggregateExpr>,
+}
+
+impl PhysicalPlan for HashAggregateExec {
+    fn schema(&self) -> &Schema {
+        &self.schema
+    }
+
+    fn execute(&self) -> Box<dyn Iterator<Item = Result<RecordBatch, QueryError>> + '_> {
+        match self.compute() {
+            Ok(batch) => Box::new(iter::once(Ok(batch))),
+            Err(e) => Box::new(iter::once(Err(e))),
+        }
+    }
+
+    fn children(&self) -> Vec<&dyn PhysicalPlan> {
+        vec![self.input.as_ref()]
+    }
+}
+
+impl HashAggregateExec {
+    fn new_accumulators(&self) -> Result<Accumulators, QueryError> {
+        self.aggr_exprs
+            .iter()
+            .map(|aggr| match &aggr.kind {
+                AggregateKind::Builtin { func, distinct } => {
+                    let order: Vec<SortOrder> = aggr
+                        .order_by
+                        .iter()
+                        .map(|sort| SortOrder { asc: sort.asc, nulls_first: sort.nulls_first })
+                        .collect();
+                    create_accumulator(*func, *distinct, aggr.return_type, &order)
+                }
+                AggregateKind::Udf(udaf) => Ok(create_udaf_accumulator(udaf.clone())),
+            })
+            .collect()
+    }
+
+    fn compute(&self) -> Result<RecordBatch, QueryError> {
+        let mut group_index: HashMap<Vec<ScalarValue>, usize> = HashMap::new();
+        let mut groups: Vec<(Vec<ScalarValue>, Accumulators)> = Vec::new();
+
+        for batch in self.input.execute() {
+            let batch = batch?;
+            let keys = self
+                .group_by
+                .iter()
+                .map(|expr| expr.evaluate(&batch))
+                .collect::<Result<Vec<ColumnVector>, QueryError>>()?;
+            let args = self
+                .aggr_exprs
+                .iter()
+                .map(|aggr| {
+                    aggr.args
+                        .iter()
+                        .chain(aggr.order_by.iter().map(|sort| &sort.expr))
+                        .map(|expr| expr.evaluate(&batch))
+                        .collect()
+                })
+                .collect::<Result<Vec<Vec<ColumnVector>>, QueryError>>()?;
+
+            // Collect the rows of this batch that belong to each group.
+            let row_count = batch.columns.first().map_or(0, |col| col.values.len());
+            let mut batch_groups: Vec<(usize, Vec<usize>)> = Vec::new();
+            let mut batch_group_pos: HashMap<usize, usize> = HashMap::new();
+            for row in 0..row_count {
+                let key: Vec<ScalarValue> = keys.iter().map(|col| col.values[row].clone()).collect();
+                let group = match group_index.get(&key) {
+                    Some(&group) => group,
+                    None => {
+                        groups.push((key.clone(), self.new_accumulators()?));
+                        group_index.insert(key, groups.len() - 1);
+                        groups.len() - 1
+                    }
+                };
+                let pos = *batch_group_pos.entry(group).or_insert_with(|| {
+                    batch_groups.push((group, Vec::new()));
+                    batch_groups.len() - 1
+                });
+                batch_groups[pos].1.push(row);
+            }
+
+            for (group, rows) in batch_groups {
+                for (accumulator, arg_cols) in groups[group].1.iter_mut().zip(&args) {
+                    let values: Vec<ColumnVector> = arg_cols
+                        .iter()
+                        .map(|col| ColumnVector::new(rows.iter().map(|&row| col.values[row].clone()).collect()))
+                        .collect();
+                    accumulator.update_batch(&values)?;
+                }
+            }
+        }
+
+        // Without GROUP BY, an empty input still produces a single row.
+        if groups.is_empty() && self.group_by.is_empty() {
+            groups.push((Vec::new(), self.new_accumulators()?));
+        }
+
+        let mut columns: Vec<Vec<ScalarValue>> = vec![Vec::with_capacity(groups.len()); self.schema.fields.len()];
+        for (key, accumulators) in &groups {
+            for (i, value) in key.iter().enumerate() {
+                columns[i].push(value.clone());
+            }
+            for (i, (accumulator, aggr)) in accumulators.iter().zip(&self.aggr_exprs).enumerate() {
+                let value = accumulator.evaluate()?;
+                let value = if value.is_null() { ScalarValue::null(aggr.return_type) } else { value };
+                columns[key.len() + i].push(value);
+            }
+        }
+
+        RecordBatch::new(
+            self.schema.clone(),
+            columns.into_iter().map(ColumnVector::new).collect(),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::logical_plan::plan::Catalog;
+    use crate::test_util::{error_message, int, rows, text};
+
+    use super::*;
+
+    fn catalog() -> Catalog {
+        let mut catalog = Catalog::new();
+        rows(&mut catalog, "CREATE TABLE s (g TEXT, x INT); INSERT INTO s VALUES ('a', 1), ('a', 2), ('a', 2), ('a', NULL), ('b', 5), ('b', 5), (NULL, 7)");
+        catalog
+    }
+
+    #[test]
+    fn distinct_keeps_the_first_of_each_row_with_nulls_alike() {
+        let mut catalog = catalog();
+        let null = ScalarValue::String(None);
+        assert_eq!(rows(&mut catalog, "SELECT DISTINCT g FROM s"), vec![vec![text("a")], vec![text("b")], vec![null.clone()]]);
+        assert_eq!(rows(&mut catalog, "SELECT DISTINCT g, x FROM s"), vec![
+            vec![text("a"), int(1)],
+            vec![text("a"), int(2)],
+            vec![text("a"), ScalarValue::Int32(None)],
+            vec![text("b"), int(5)],
+            vec![null, int(7)],
+        ]);
+    }
+
+    #[test]
+    fn distinct_counts_skip_nulls_and_repeats() {
+        let mut catalog = catalog();
+        assert_eq!(rows(&mut catalog, "SELECT g, COUNT(DISTINCT x), COUNT(x), APPROX_COUNT_DISTINCT(x) FROM s GROUP BY g"), vec![
+            vec![text("a"), int(2), int(3), int(2)],
+            vec![text("b"), int(1), int(2), int(1)],
+            vec![ScalarValue::String(None), int(1), int(1), int(1)],
+        ]);
+        assert_eq!(rows(&mut catalog, "SELECT COUNT(DISTINCT x), APPROX_COUNT_DISTINCT(g) FROM s"), vec![vec![int(4), int(2)]]);
+        assert_eq!(error_message(&mut catalog, "SELECT SUM(DISTINCT x) FROM s"), "DISTINCT is only supported for COUNT: SUM(DISTINCT x)");
+    }
+}
@@ -1,11 +1,20 @@
+use std::cmp::Ordering;
+
 use crate::errors::QueryError;
-use crate::logical_plan::plan::{Operator, UnaryOperator};
-use crate::{ColumnVector, RecordBatch, ScalarValue};
+use crate::logical_plan::plan::{Operator, ScalarFunction, UnaryOperator};
+use crate::{ColumnVector, DataType, RecordBatch, ScalarValue};
 
 pub trait PhysicalExpr {
     fn evaluate(&self, batch: &RecordBatch) -> Result<ColumnVector, QueryError>;
 }
 
+/// A physical ORDER BY key.
+pub struct PhysicalSortExpr {
+    pub expr: Box<dyn PhysicalExpr>,
+    pub asc: bool,
+    pub nulls_first: bool,
+}
+
 /// Retrieves a column from the batch by its position index.
 pub struct ColumnExpr {
     pub index: usize,
@@ -71,14 +80,32 @@ impl PhysicalExpr for UnaryExpr {
     }
 }
 
-fn is_null(v: &ScalarValue) -> bool {
-    matches!(
-        v,
-        ScalarValue::Int32(None)
-            | ScalarValue::Float64(None)
-            | ScalarValue::String(None)
-            | ScalarValue::Bool(None)
-    )
+/// Evaluates every argument and applies a null-handling function row by row.
+/// `return_type` is the unified argument type worked out by the logical planner.
+pub struct ScalarFunctionExpr {
+    pub func: ScalarFunction,
+    pub args: Vec<Box<dyn PhysicalExpr>>,
+    pub return_type: DataType,
+}
+
+impl PhysicalExpr for ScalarFunctionExpr {
+    fn evaluate(&self, batch: &RecordBatch) -> Result<ColumnVector, QueryError> {
+        let arg_cols = self
+            .args
+            .iter()
+            .map(|arg| arg.evaluate(batch))
+            .collect::<Result<Vec<ColumnVector>, QueryError>>()?;
+
+        let row_count = arg_cols.first().map_or(0, |col| col.values.len());
+        let values = (0..row_count)
+            .map(|row| {
+                let row_args: Vec<&ScalarValue> = arg_cols.iter().map(|col| &col.values[row]).collect();
+                apply_scalar_function(&self.func, &row_args, self.return_type)
+            })
+            .collect::<Result<Vec<ScalarValue>, QueryError>>()?;
+
+        Ok(ColumnVector::new(values))
+    }
 }
 
 fn apply_unary(op: &UnaryOperator, value: &ScalarValue) -> ScalarValue {
@@ -86,12 +113,88 @@ fn apply_unary(op: &UnaryOperator, value: &ScalarValue) -> ScalarValue {
         (UnaryOperator::Not, ScalarValue::Bool(b)) => ScalarValue::Bool(b.map(|x| !x)),
         (UnaryOperator::Negate, ScalarValue::Int32(n)) => ScalarValue::Int32(n.map(|x| -x)),
         (UnaryOperator::Negate, ScalarValue::Float64(n)) => ScalarValue::Float64(n.map(|x| -x)),
-        (UnaryOperator::IsNull, v) => ScalarValue::Bool(Some(is_null(v))),
-        (UnaryOperator::IsNotNull, v) => ScalarValue::Bool(Some(!is_null(v))),
+        (UnaryOperator::IsNull, v) => ScalarValue::Bool(Some(v.is_null())),
+        (UnaryOperator::IsNotNull, v) => ScalarValue::Bool(Some(!v.is_null())),
         _ => value.clone(),
     }
 }
 
+fn apply_scalar_function(
+    func: &ScalarFunction,
+    args: &[&ScalarValue],
+    return_type: DataType,
+) -> Result<ScalarValue, QueryError> {
+    match func {
+        ScalarFunction::Coalesce | ScalarFunction::IfNull => Ok(args
+            .iter()
+            .find(|v| !v.is_null())
+            .map_or_else(|| ScalarValue::null(return_type), |v| cast_scalar(v, return_type))),
+        ScalarFunction::NullIf => {
+            let (value, other) = (args[0], args[1]);
+            if value.is_null() || other.is_null() {
+                return Ok(value.clone());
+            }
+            match apply_operator(value, other, &Operator::Eq)? {
+                ScalarValue::Bool(Some(true)) => Ok(ScalarValue::null(return_type)),
+                _ => Ok(value.clone()),
+            }
+        }
+        ScalarFunction::Greatest | ScalarFunction::Least => {
+            // NULL arguments are ignored; the result is NULL only when every argument is.
+            let op = if *func == ScalarFunction::Greatest { Operator::Gt } else { Operator::Lt };
+            let mut best: Option<ScalarValue> = None;
+            for value in args.iter().filter(|v| !v.is_null()) {
+                let value = cast_scalar(value, return_type);
+                let replace = match &best {
+                    None => true,
+                    Some(current) => matches!(apply_operator(&value, current, &op)?, ScalarValue::Bool(Some(true))),
+                };
+                if replace {
+                    best = Some(value);
+                }
+            }
+            Ok(best.unwrap_or_else(|| ScalarValue::null(return_type)))
+        }
+    }
+}
+
+/// Converts a value to the given type. Only Int32 -> Float64 widening and
+/// re-typing of NULLs are needed, since the planner has already unified types.
+pub fn cast_scalar(value: &ScalarValue, target: DataType) -> ScalarValue {
+    match (value, target) {
+        (ScalarValue::Int32(v), DataType::Float64) => ScalarValue::Float64(v.map(f64::from)),
+        (v, target) if v.is_null() => ScalarValue::null(target),
+        _ => value.clone(),
+    }
+}
+
+/// Orders two non-null values. Int32 and Float64 compare numerically;
+/// values of unrelated types are treated as equal.
+pub fn compare_values(left: &ScalarValue, right: &ScalarValue) -> Ordering {
+    match (left, right) {
+        (ScalarValue::Int32(Some(l)), ScalarValue::Int32(Some(r))) => l.cmp(r),
+        (ScalarValue::Float64(Some(l)), ScalarValue::Float64(Some(r))) => l.total_cmp(r),
+        (ScalarValue::Int32(Some(l)), ScalarValue::Float64(Some(r))) => f64::from(*l).total_cmp(r),
+        (ScalarValue::Float64(Some(l)), ScalarValue::Int32(Some(r))) => l.total_cmp(&f64::from(*r)),
+        (ScalarValue::String(Some(l)), ScalarValue::String(Some(r))) => l.cmp(r),
+        (ScalarValue::Bool(Some(l)), ScalarValue::Bool(Some(r))) => l.cmp(r),
+        _ => Ordering::Equal,
+    }
+}
+
+/// Orders two values for an ORDER BY key, placing NULLs according to `nulls_first`.
+pub fn compare_sort_values(left: &ScalarValue, right: &ScalarValue, asc: bool, nulls_first: bool) -> Ordering {
+    match (left.is_null(), right.is_null()) {
+        (true, true) => Ordering::Equal,
+        (true, false) => if nulls_first { Ordering::Less } else { Ordering::Greater },
+        (false, true) => if nulls_first { Ordering::Greater } else { Ordering::Less },
+        (false, false) => {
+            let ordering = compare_values(left, right);
+            if asc { ordering } else { ordering.reverse() }
+        }
+    }
+}
+
 fn apply_operator(
     left: &ScalarValue,
     right: &ScalarValue,
@@ -210,3 +313,41 @@ fn apply_bool(l: bool, r: bool, op: &Operator) -> Result<ScalarValue, QueryError
         }),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::logical_plan::plan::Catalog;
+    use crate::test_util::{error_message, float, int, query, rows, text};
+
+    use super::*;
+
+    fn catalog() -> Catalog {
+        let mut catalog = Catalog::new();
+        rows(&mut catalog, "CREATE TABLE s (a INT, b FLOAT, t TEXT); INSERT INTO s VALUES (1, NULL, 'x'), (NULL, 2.5, NULL), (3, 3.0, 'z')");
+        catalog
+    }
+
+    #[test]
+    fn null_handling_functions_widen_their_arguments() {
+        let mut catalog = catalog();
+        let sql = "SELECT COALESCE(a, b), NULLIF(a, 3), GREATEST(a, b, 2), LEAST(a, b), IFNULL(t, 'none'), COALESCE(NULLIF(a, 1), 0) FROM s";
+        let (schema, values) = query(&mut catalog, sql).unwrap();
+        // GREATEST and LEAST ignore NULL arguments.
+        assert_eq!(values, vec![
+            vec![float(1.0), int(1), float(2.0), float(1.0), text("x"), int(0)],
+            vec![float(2.5), ScalarValue::Int32(None), float(2.5), float(2.5), text("none"), int(0)],
+            vec![float(3.0), ScalarValue::Int32(None), float(3.0), float(3.0), text("z"), int(3)],
+        ]);
+        let types: Vec<DataType> = schema.fields.iter().map(|field| field.field_type).collect();
+        assert_eq!(types, [DataType::Float64, DataType::Int32, DataType::Float64, DataType::Float64, DataType::String, DataType::Int32]);
+        let names: Vec<&str> = schema.fields.iter().map(|field| field.name.as_str()).collect();
+        assert_eq!(names, ["COALESCE(a, b)", "NULLIF(a, 3)", "GREATEST(a, b, 2)", "LEAST(a, b)", "IFNULL(t, 'none')", "COALESCE(NULLIF(a, 1), 0)"]);
+    }
+
+    #[test]
+    fn bad_arguments_are_rejected_when_planning() {
+        let mut catalog = catalog();
+        assert_eq!(error_message(&mut catalog, "SELECT COALESCE(a, t) FROM s"), "Incompatible argument types: Int32 and String");
+        assert_eq!(error_message(&mut catalog, "SELECT NULLIF(a) FROM s"), "Wrong number of arguments for NullIf: got 1");
+    }
+}
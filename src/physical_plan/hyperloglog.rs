@@ -0,0 +1,111 @@
+use std::hash::{DefaultHasher, Hash, Hasher};
+
+const PRECISION: u32 = 14;
+const REGISTER_COUNT: usize = 1 << PRECISION;
+
+/// A HyperLogLog sketch for estimating the number of distinct values.
+/// It always uses 16 KiB of registers however many values are added, with a
+/// standard error of about 0.8%.
+#[derive(Debug, Clone)]
+pub struct HyperLogLog {
+    registers: Vec<u8>,
+}
+
+impl Default for HyperLogLog {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl HyperLogLog {
+    pub fn new() -> Self {
+        Self {
+            registers: vec![0; REGISTER_COUNT],
+        }
+    }
+
+    pub fn add<T: Hash>(&mut self, value: &T) {
+        let mut hasher = DefaultHasher::new();
+        value.hash(&mut hasher);
+        let hash = hasher.finish();
+
+        // The top bits pick a register; the rest supply the run of leading zeros.
+        let index = (hash >> (64 - PRECISION)) as usize;
+        let rank = ((hash << PRECISION).leading_zeros().min(64 - PRECISION) + 1) as u8;
+        self.registers[index] = self.registers[index].max(rank);
+    }
+
+    /// Folds another sketch into this one, as if all its values had been added here.
+    pub fn merge(&mut self, other: &HyperLogLog) {
+        for (mine, theirs) in self.registers.iter_mut().zip(&other.registers) {
+            *mine = (*mine).max(*theirs);
+        }
+    }
+
+    pub fn count(&self) -> u64 {
+        let m = REGISTER_COUNT as f64;
+        let alpha = 0.7213 / (1.0 + 1.079 / m);
+        let sum: f64 = self.registers.iter().map(|&r| 2f64.powi(-i32::from(r))).sum();
+        let estimate = alpha * m * m / sum;
+
+        // Small cardinalities are estimated more accurately by linear counting.
+        let zeros = self.registers.iter().filter(|&&r| r == 0).count();
+        let estimate = if estimate <= 2.5 * m && zeros > 0 {
+            m * (m / zeros as f64).ln()
+        } else {
+            estimate
+        };
+        estimate.round() as u64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sketch(values: impl IntoIterator<Item = u64>) -> HyperLogLog {
+        let mut sketch = HyperLogLog::new();
+        for value in values {
+            sketch.add(&value);
+        }
+        sketch
+    }
+
+    /// The estimate's error relative to `actual`.
+    fn error(sketch: &HyperLogLog, actual: u64) -> f64 {
+        (sketch.count() as f64 - actual as f64).abs() / actual as f64
+    }
+
+    #[test]
+    fn small_counts_are_nearly_exact() {
+        assert_eq!(HyperLogLog::new().count(), 0);
+        assert_eq!(sketch([42]).count(), 1);
+        for actual in [10, 100, 1_000] {
+            let estimate = sketch(0..actual).count();
+            assert!(estimate.abs_diff(actual) <= actual / 100 + 1, "estimate for {} was {}", actual, estimate);
+        }
+    }
+
+    #[test]
+    fn large_counts_stay_within_three_standard_errors() {
+        for actual in [10_000, 100_000, 1_000_000] {
+            let sketch = sketch(0..actual);
+            assert!(error(&sketch, actual) < 0.025, "estimate for {} was {}", actual, sketch.count());
+        }
+    }
+
+    #[test]
+    fn repeated_values_are_counted_once() {
+        let once = sketch(0..5_000);
+        let thrice = sketch((0..3).flat_map(|_| 0..5_000));
+        assert_eq!(once.count(), thrice.count());
+    }
+
+    #[test]
+    fn merging_matches_adding_everything_to_one_sketch() {
+        let mut merged = sketch(0..60_000);
+        merged.merge(&sketch(40_000..100_000));
+        assert_eq!(merged.count(), sketch(0..100_000).count());
+        assert!(error(&merged, 100_000) < 0.025);
+    }
+}
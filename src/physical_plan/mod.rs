@@ -1,3 +1,7 @@
+pub mod accumulator;
+pub mod aggregate;
 pub mod eval;
+pub mod hyperloglog;
 pub mod plan;
 pub mod planner;
+pub mod window;
@@ -1,7 +1,10 @@
+use std::collections::HashSet;
 use std::rc::Rc;
+use std::{iter, mem};
 
 use crate::errors::QueryError;
-use crate::{ColumnVector, DataSource, RecordBatch, Schema, ScalarValue};
+use crate::{ColumnVector, DataSource, RecordBatch, Schema, ScalarValue, SessionConfig};
+use crate::logical_plan::plan::Expression;
 
 use super::eval::PhysicalExpr;
 
@@ -14,7 +17,12 @@ pub trait PhysicalPlan {
 /// Reads batches from a data source. Leaf node — no children.
 pub struct ScanExec {
     pub source: Rc<dyn DataSource>,
+    /// Indexes of the source columns to read; `None` reads all of them.
+    pub projection: Option<Vec<usize>>,
+    /// Predicates handed to the source, which may use them to drop rows early.
+    pub filters: Vec<Expression>,
     pub schema: Schema,
+    pub config: SessionConfig,
 }
 
 impl PhysicalPlan for ScanExec {
@@ -23,7 +31,7 @@ impl PhysicalPlan for ScanExec {
     }
 
     fn execute(&self) -> Box<dyn Iterator<Item = Result<RecordBatch, QueryError>> + '_> {
-        self.source.scan()
+        self.source.scan(&self.config, self.projection.as_deref(), &self.filters)
     }
 
     fn children(&self) -> Vec<&dyn PhysicalPlan> {
@@ -56,22 +64,106 @@ impl PhysicalPlan for FilterExec {
                 .map(|v| matches!(v, ScalarValue::Bool(Some(true))))
                 .collect();
 
-            let filtered_columns: Vec<ColumnVector> = batch
-                .columns
+            RecordBatch::new(batch.schema.clone(), filter_columns(&batch.columns, &mask))
+        }))
+    }
+
+    fn children(&self) -> Vec<&dyn PhysicalPlan> {
+        vec![self.input.as_ref()]
+    }
+}
+
+/// Merges consecutive small batches until they hold at least `target_batch_size`
+/// rows, so a selective filter does not leave the operators above it handling
+/// many nearly empty batches. Batches that are already large pass through.
+pub struct CoalesceBatchesExec {
+    pub input: Box<dyn PhysicalPlan>,
+    pub target_batch_size: usize,
+}
+
+impl PhysicalPlan for CoalesceBatchesExec {
+    fn schema(&self) -> &Schema {
+        self.input.schema()
+    }
+
+    fn execute(&self) -> Box<dyn Iterator<Item = Result<RecordBatch, QueryError>> + '_> {
+        let mut input_iter = self.input.execute();
+        let schema = self.schema();
+        let mut buffered: Vec<Vec<ScalarValue>> = vec![Vec::new(); schema.fields.len()];
+        let mut buffered_rows = 0;
+        let mut finished = false;
+
+        Box::new(iter::from_fn(move || {
+            while !finished && buffered_rows < self.target_batch_size {
+                match input_iter.next() {
+                    Some(Ok(batch)) => {
+                        let row_count = batch.columns.first().map_or(0, |col| col.values.len());
+                        if buffered_rows == 0 && row_count >= self.target_batch_size {
+                            return Some(Ok(batch));
+                        }
+                        for (values, column) in buffered.iter_mut().zip(batch.columns) {
+                            values.extend(column.values);
+                        }
+                        buffered_rows += row_count;
+                    }
+                    Some(Err(e)) => return Some(Err(e)),
+                    None => finished = true,
+                }
+            }
+            if buffered_rows == 0 {
+                return None;
+            }
+            buffered_rows = 0;
+            let columns = buffered.iter_mut().map(|values| ColumnVector::new(mem::take(values))).collect();
+            Some(RecordBatch::new(schema.clone(), columns))
+        }))
+    }
+
+    fn children(&self) -> Vec<&dyn PhysicalPlan> {
+        vec![self.input.as_ref()]
+    }
+}
+
+/// Keeps the values at the positions where `mask` is true.
+fn filter_columns(columns: &[ColumnVector], mask: &[bool]) -> Vec<ColumnVector> {
+    columns
+        .iter()
+        .map(|col| {
+            let kept: Vec<ScalarValue> = col
+                .values
                 .iter()
-                .map(|col| {
-                    let kept: Vec<ScalarValue> = col
-                        .values
-                        .iter()
-                        .zip(mask.iter())
-                        .filter(|&(_, keep)| *keep)
-                        .map(|(val, _)| val.clone())
-                        .collect();
-                    ColumnVector::new(kept)
-                })
+                .zip(mask.iter())
+                .filter(|&(_, keep)| *keep)
+                .map(|(val, _)| val.clone())
+                .collect();
+            ColumnVector::new(kept)
+        })
+        .collect()
+}
+
+/// Drops rows identical to one already emitted. Every distinct row seen so far
+/// is kept in a hash set, so batches stream through without waiting for the input to end.
+pub struct DistinctExec {
+    pub input: Box<dyn PhysicalPlan>,
+}
+
+impl PhysicalPlan for DistinctExec {
+    fn schema(&self) -> &Schema {
+        self.input.schema()
+    }
+
+    fn execute(&self) -> Box<dyn Iterator<Item = Result<RecordBatch, QueryError>> + '_> {
+        let input_iter = self.input.execute();
+        let mut seen: HashSet<Vec<ScalarValue>> = HashSet::new();
+
+        Box::new(input_iter.map(move |batch_result| {
+            let batch = batch_result?;
+            let row_count = batch.columns.first().map_or(0, |col| col.values.len());
+            let mask: Vec<bool> = (0..row_count)
+                .map(|row| seen.insert(batch.columns.iter().map(|col| col.values[row].clone()).collect()))
                 .collect();
 
-            RecordBatch::new(batch.schema.clone(), filtered_columns)
+            RecordBatch::new(batch.schema.clone(), filter_columns(&batch.columns, &mask))
         }))
     }
 
@@ -109,3 +201,79 @@ impl PhysicalPlan for ProjectionExec {
         vec![self.input.as_ref()]
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::datasource::csv::CsvDataSource;
+    use crate::{DataType, Field};
+
+    use super::*;
+
+    /// Hands out batches of the given sizes, numbering the rows from 0.
+    struct Batches {
+        schema: Schema,
+        sizes: Vec<usize>,
+    }
+
+    impl Batches {
+        fn new(sizes: &[usize]) -> Self {
+            let schema = Schema::new(vec![Field { name: "n".to_string(), field_type: DataType::Int32, is_nullable: false }]);
+            Self { schema, sizes: sizes.to_vec() }
+        }
+    }
+
+    impl PhysicalPlan for Batches {
+        fn schema(&self) -> &Schema {
+            &self.schema
+        }
+
+        fn execute(&self) -> Box<dyn Iterator<Item = Result<RecordBatch, QueryError>> + '_> {
+            let mut next = 0;
+            Box::new(self.sizes.iter().map(move |&size| {
+                let values = (next..next + size).map(|n| ScalarValue::Int32(Some(n as i32))).collect();
+                next += size;
+                RecordBatch::new(self.schema.clone(), vec![ColumnVector::new(values)])
+            }))
+        }
+
+        fn children(&self) -> Vec<&dyn PhysicalPlan> {
+            vec![]
+        }
+    }
+
+    fn batch_sizes(plan: &dyn PhysicalPlan) -> Vec<usize> {
+        plan.execute().map(|batch| batch.unwrap().columns[0].values.len()).collect()
+    }
+
+    #[test]
+    fn coalescing_merges_small_batches_in_order() {
+        let plan = CoalesceBatchesExec { input: Box::new(Batches::new(&[1, 0, 2, 5, 2])), target_batch_size: 4 };
+        assert_eq!(batch_sizes(&plan), [8, 2]);
+        let values: Vec<ScalarValue> = plan.execute().flat_map(|batch| batch.unwrap().columns.remove(0).values).collect();
+        assert_eq!(values, (0..10).map(|n| ScalarValue::Int32(Some(n))).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn coalescing_passes_large_batches_through() {
+        let plan = CoalesceBatchesExec { input: Box::new(Batches::new(&[6, 1, 1, 1, 1, 0])), target_batch_size: 4 };
+        assert_eq!(batch_sizes(&plan), [6, 4]);
+        let plan = CoalesceBatchesExec { input: Box::new(Batches::new(&[0, 0])), target_batch_size: 4 };
+        assert_eq!(batch_sizes(&plan), Vec::<usize>::new());
+    }
+
+    #[test]
+    fn scans_fill_batches_up_to_the_configured_size() {
+        // test/students.csv has 200 rows.
+        let source: Rc<dyn DataSource> = Rc::new(CsvDataSource::new("test/students.csv".to_string()).unwrap());
+        let scan = |config: SessionConfig| ScanExec {
+            source: source.clone(),
+            projection: Some(vec![0]),
+            filters: Vec::new(),
+            schema: source.schema().project(&[0]),
+            config,
+        };
+        let sizes = batch_sizes(&scan(SessionConfig::new().with_batch_size(64)));
+        assert_eq!(sizes, [64, 64, 64, 8]);
+        assert_eq!(batch_sizes(&scan(SessionConfig::new().with_batch_size(0))), vec![1; 200]);
+    }
+}
@@ -1,34 +1,60 @@
 use crate::errors::QueryError;
-use crate::{DataType, Field, Schema};
-use crate::logical_plan::plan::{Catalog, Expression, LogicalPlan};
+use std::rc::Rc;
 
-use super::eval::{BinaryExpr, ColumnExpr, LiteralExpr, PhysicalExpr, UnaryExpr};
-use super::plan::{FilterExec, PhysicalPlan, ProjectionExec, ScanExec};
+use crate::datasource::memory::MemTable;
+use crate::{ColumnVector, DataType, Field, RecordBatch, Schema, SessionConfig};
+use crate::logical_plan::plan::{Catalog, Expression, LogicalPlan, SortExpr, WindowFrameBound, WindowFrameUnits};
+
+use super::eval::{BinaryExpr, ColumnExpr, LiteralExpr, PhysicalExpr, PhysicalSortExpr, ScalarFunctionExpr, UnaryExpr};
+use super::aggregate::{AggregateExpr, AggregateKind, HashAggregateExec};
+use super::plan::{CoalesceBatchesExec, DistinctExec, FilterExec, PhysicalPlan, ProjectionExec, ScanExec};
+use super::window::{WindowExec, WindowExpr};
 
 /// Converts a LogicalPlan tree into an executable PhysicalPlan tree.
 pub fn create_physical_plan(
     plan: &LogicalPlan,
     catalog: &Catalog,
+    config: &SessionConfig,
 ) -> Result<Box<dyn PhysicalPlan>, QueryError> {
     match plan {
-        LogicalPlan::Scan { path, schema, .. } => {
+        LogicalPlan::Scan { path, schema, projection, filters } => {
             let source = catalog.get_source(path)?;
+            let projection = projection
+                .as_ref()
+                .map(|columns| {
+                    columns
+                        .iter()
+                        .map(|name| source.schema().index_of(name))
+                        .collect::<Result<Vec<usize>, String>>()
+                })
+                .transpose()
+                .map_err(|message| QueryError::ValidationError { message })?;
+            let schema = match &projection {
+                Some(indices) => source.schema().project(indices),
+                None => schema.clone(),
+            };
             Ok(Box::new(ScanExec {
                 source,
-                schema: schema.clone(),
+                projection,
+                filters: filters.clone(),
+                schema,
+                config: config.clone(),
             }))
         }
         LogicalPlan::Filter { input, predicate } => {
-            let physical_input = create_physical_plan(input, catalog)?;
+            let physical_input = create_physical_plan(input, catalog, config)?;
             let input_schema = physical_input.schema().clone();
             let physical_predicate = create_physical_expr(predicate, &input_schema)?;
-            Ok(Box::new(FilterExec {
-                input: physical_input,
-                predicate: physical_predicate,
+            Ok(Box::new(CoalesceBatchesExec {
+                input: Box::new(FilterExec {
+                    input: physical_input,
+                    predicate: physical_predicate,
+                }),
+                target_batch_size: config.batch_size(),
             }))
         }
         LogicalPlan::Projection { input, columns } => {
-            let physical_input = create_physical_plan(input, catalog)?;
+            let physical_input = create_physical_plan(input, catalog, config)?;
             let input_schema = physical_input.schema().clone();
 
             let physical_exprs: Result<Vec<_>, _> = columns
@@ -36,47 +62,165 @@ pub fn create_physical_plan(
                 .map(|expr| create_physical_expr(expr, &input_schema))
                 .collect();
 
-            let output_fields: Vec<Field> = columns
-                .iter()
-                .map(|expr| match expr {
-                    Expression::Column { name, data_type } => Field {
-                        name: name.clone(),
-                        field_type: *data_type,
-                        is_nullable: true,
-                    },
-                    Expression::Literal(scalar) => {
-                        let dt = match scalar {
-                            crate::ScalarValue::Int32(_) => DataType::Int32,
-                            crate::ScalarValue::Float64(_) => DataType::Float64,
-                            crate::ScalarValue::Bool(_) => DataType::Bool,
-                            crate::ScalarValue::String(_) => DataType::String,
-                        };
-                        Field {
-                            name: format!("{:?}", scalar),
-                            field_type: dt,
-                            is_nullable: true,
-                        }
-                    }
-                    _ => Field {
-                        name: format!("{:?}", expr),
-                        field_type: DataType::Int32,
-                        is_nullable: true,
-                    },
-                })
-                .collect();
+            let output_fields: Result<Vec<Field>, QueryError> =
+                columns.iter().map(|expr| expr.to_field(&input_schema)).collect();
 
             Ok(Box::new(ProjectionExec {
                 input: physical_input,
-                schema: Schema::new(output_fields),
+                schema: Schema::new(output_fields?),
                 exprs: physical_exprs?,
             }))
         }
+        LogicalPlan::Window { input, window_exprs } => {
+            let physical_input = create_physical_plan(input, catalog, config)?;
+            let input_schema = physical_input.schema().clone();
+
+            let mut fields = input_schema.fields.clone();
+            let mut physical_window_exprs = Vec::with_capacity(window_exprs.len());
+            for expr in window_exprs {
+                fields.push(expr.to_field(&input_schema)?);
+                physical_window_exprs.push(create_window_expr(expr, &input_schema)?);
+            }
+
+            Ok(Box::new(WindowExec {
+                input: physical_input,
+                schema: Schema::new(fields),
+                window_exprs: physical_window_exprs,
+            }))
+        }
+        LogicalPlan::Aggregate { input, group_by, aggr_exprs } => {
+            let physical_input = create_physical_plan(input, catalog, config)?;
+            let input_schema = physical_input.schema().clone();
+
+            let physical_group_by = group_by
+                .iter()
+                .map(|expr| create_physical_expr(expr, &input_schema))
+                .collect::<Result<Vec<_>, _>>()?;
+            let physical_aggr_exprs = aggr_exprs
+                .iter()
+                .map(|expr| create_aggregate_expr(expr, &input_schema))
+                .collect::<Result<Vec<_>, _>>()?;
+
+            Ok(Box::new(HashAggregateExec {
+                input: physical_input,
+                schema: plan.schema()?,
+                group_by: physical_group_by,
+                aggr_exprs: physical_aggr_exprs,
+            }))
+        }
+        LogicalPlan::Distinct { input } => Ok(Box::new(DistinctExec {
+            input: create_physical_plan(input, catalog, config)?,
+        })),
+        LogicalPlan::Values { schema, rows } => {
+            // Inline rows are scanned like any other in-memory table.
+            let columns = (0..schema.fields.len())
+                .map(|i| ColumnVector::new(rows.iter().map(|row| row[i].clone()).collect()))
+                .collect();
+            let batch = RecordBatch::new(schema.clone(), columns)?;
+            Ok(Box::new(ScanExec {
+                source: Rc::new(MemTable::try_new(schema.clone(), vec![batch])?),
+                projection: None,
+                filters: Vec::new(),
+                schema: schema.clone(),
+                config: config.clone(),
+            }))
+        }
+    }
+}
+
+fn create_aggregate_expr(expr: &Expression, schema: &Schema) -> Result<AggregateExpr, QueryError> {
+    match expr {
+        Expression::Alias { expr, .. } => create_aggregate_expr(expr, schema),
+        Expression::AggregateFunction { func, args, distinct, order_by } => Ok(AggregateExpr {
+            kind: AggregateKind::Builtin { func: *func, distinct: *distinct },
+            args: args
+                .iter()
+                .map(|arg| create_physical_expr(arg, schema))
+                .collect::<Result<Vec<_>, _>>()?,
+            order_by: create_sort_exprs(order_by, schema)?,
+            return_type: expr
+                .get_data_type()
+                .map_err(|message| QueryError::ValidationError { message })?,
+        }),
+        Expression::AggregateUdf { udaf, args } => Ok(AggregateExpr {
+            kind: AggregateKind::Udf(udaf.clone()),
+            args: args
+                .iter()
+                .map(|arg| create_physical_expr(arg, schema))
+                .collect::<Result<Vec<_>, _>>()?,
+            order_by: Vec::new(),
+            return_type: expr
+                .get_data_type()
+                .map_err(|message| QueryError::ValidationError { message })?,
+        }),
+        _ => Err(QueryError::ValidationError {
+            message: format!("Not an aggregate expression: {:?}", expr),
+        }),
     }
 }
 
+fn create_window_expr(expr: &Expression, schema: &Schema) -> Result<WindowExpr, QueryError> {
+    let return_type = expr
+        .get_data_type()
+        .map_err(|message| QueryError::ValidationError { message })?;
+    match expr {
+        Expression::Alias { expr, .. } => create_window_expr(expr, schema),
+        Expression::WindowFunction { func, args, partition_by, order_by, frame } => {
+            let has_offset = |bound: WindowFrameBound| {
+                matches!(bound, WindowFrameBound::Preceding(_) | WindowFrameBound::Following(_))
+            };
+            if frame.units == WindowFrameUnits::Range && (has_offset(frame.start) || has_offset(frame.end)) {
+                let numeric_key = match order_by.as_slice() {
+                    [sort] => matches!(
+                        sort.expr.get_data_type(),
+                        Ok(DataType::Int32 | DataType::Float64)
+                    ),
+                    _ => false,
+                };
+                if !numeric_key {
+                    return Err(QueryError::ValidationError {
+                        message: "RANGE frames with an offset require exactly one numeric ORDER BY key".to_string(),
+                    });
+                }
+            }
+
+            let physical_exprs = |exprs: &[Expression]| {
+                exprs
+                    .iter()
+                    .map(|e| create_physical_expr(e, schema))
+                    .collect::<Result<Vec<_>, _>>()
+            };
+            Ok(WindowExpr {
+                func: *func,
+                args: physical_exprs(args)?,
+                partition_by: physical_exprs(partition_by)?,
+                order_by: create_sort_exprs(order_by, schema)?,
+                frame: *frame,
+                return_type,
+            })
+        }
+        _ => Err(QueryError::ValidationError {
+            message: format!("Not a window expression: {:?}", expr),
+        }),
+    }
+}
+
+fn create_sort_exprs(order_by: &[SortExpr], schema: &Schema) -> Result<Vec<PhysicalSortExpr>, QueryError> {
+    order_by
+        .iter()
+        .map(|sort| {
+            Ok(PhysicalSortExpr {
+                expr: create_physical_expr(&sort.expr, schema)?,
+                asc: sort.asc,
+                nulls_first: sort.nulls_first,
+            })
+        })
+        .collect()
+}
+
 /// Converts a logical Expression into a physical PhysicalExpr.
 /// Column names are resolved to indexes here so the executor never searches by name.
-fn create_physical_expr(
+pub(crate) fn create_physical_expr(
     expr: &Expression,
     schema: &Schema,
 ) -> Result<Box<dyn PhysicalExpr>, QueryError> {
@@ -114,5 +258,26 @@ fn create_physical_expr(
                 op: operator.clone(),
             }))
         }
+        Expression::Alias { expr, .. } => create_physical_expr(expr, schema),
+        Expression::WindowFunction { .. } => Err(QueryError::ValidationError {
+            message: format!("Window function must be computed by a Window node: {:?}", expr),
+        }),
+        Expression::AggregateFunction { .. } | Expression::AggregateUdf { .. } => Err(QueryError::ValidationError {
+            message: format!("Aggregate function must be computed by an Aggregate node: {:?}", expr),
+        }),
+        Expression::ScalarFunction { func, args } => {
+            let return_type = expr
+                .get_data_type()
+                .map_err(|message| QueryError::ValidationError { message })?;
+            let physical_args = args
+                .iter()
+                .map(|arg| create_physical_expr(arg, schema))
+                .collect::<Result<Vec<_>, _>>()?;
+            Ok(Box::new(ScalarFunctionExpr {
+                func: *func,
+                args: physical_args,
+                return_type,
+            }))
+        }
     }
 }
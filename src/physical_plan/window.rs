@@ -0,0 +1,410 @@
+use std::cmp::Ordering;
+use std::iter;
+
+use crate::errors::QueryError;
+use crate::logical_plan::plan::{WindowFrame, WindowFrameBound, WindowFrameUnits, WindowFunction};
+use crate::{ColumnVector, DataType, RecordBatch, ScalarValue, Schema};
+
+use super::eval::{PhysicalExpr, PhysicalSortExpr, cast_scalar, compare_sort_values};
+use super::plan::PhysicalPlan;
+
+/// One window function call with its partitioning, ordering and frame.
+pub struct WindowExpr {
+    pub func: WindowFunction,
+    pub args: Vec<Box<dyn PhysicalExpr>>,
+    pub partition_by: Vec<Box<dyn PhysicalExpr>>,
+    pub order_by: Vec<PhysicalSortExpr>,
+    pub frame: WindowFrame,
+    pub return_type: DataType,
+}
+
+/// Computes window functions over the whole input and appends one column per
+/// window expression. Rows come out in the same order and batches as they went in.
+pub struct WindowExec {
+    pub input: Box<dyn PhysicalPlan>,
+    pub schema: Schema,
+    pub window_exprs: Vec<WindowExpr>,
+}
+
+impl PhysicalPlan for WindowExec {
+    fn schema(&self) -> &Schema {
+        &self.schema
+    }
+
+    fn execute(&self) -> Box<dyn Iterator<Item = Result<RecordBatch, QueryError>> + '_> {
+        match self.compute() {
+            Ok(batches) => Box::new(batches.into_iter().map(Ok)),
+            Err(e) => Box::new(iter::once(Err(e))),
+        }
+    }
+
+    fn children(&self) -> Vec<&dyn PhysicalPlan> {
+        vec![self.input.as_ref()]
+    }
+}
+
+impl WindowExec {
+    fn compute(&self) -> Result<Vec<RecordBatch>, QueryError> {
+        let batches = self.input.execute().collect::<Result<Vec<RecordBatch>, QueryError>>()?;
+
+        let results = self
+            .window_exprs
+            .iter()
+            .map(|expr| expr.evaluate(&batches))
+            .collect::<Result<Vec<Vec<ScalarValue>>, QueryError>>()?;
+
+        let mut offset = 0;
+        let mut output = Vec::with_capacity(batches.len());
+        for batch in batches {
+            let row_count = batch.columns.first().map_or(0, |col| col.values.len());
+            let mut columns = batch.columns;
+            for result in &results {
+                columns.push(ColumnVector::new(result[offset..offset + row_count].to_vec()));
+            }
+            offset += row_count;
+            output.push(RecordBatch::new(self.schema.clone(), columns)?);
+        }
+        Ok(output)
+    }
+}
+
+/// Evaluates an expression against every batch and concatenates the results.
+fn evaluate_all(expr: &dyn PhysicalExpr, batches: &[RecordBatch]) -> Result<Vec<ScalarValue>, QueryError> {
+    let mut values = Vec::new();
+    for batch in batches {
+        values.extend(expr.evaluate(batch)?.values);
+    }
+    Ok(values)
+}
+
+impl WindowExpr {
+    /// Returns one value per input row, in input order.
+    fn evaluate(&self, batches: &[RecordBatch]) -> Result<Vec<ScalarValue>, QueryError> {
+        let args = self
+            .args
+            .iter()
+            .map(|arg| evaluate_all(arg.as_ref(), batches))
+            .collect::<Result<Vec<_>, _>>()?;
+        let partition_keys = self
+            .partition_by
+            .iter()
+            .map(|expr| evaluate_all(expr.as_ref(), batches))
+            .collect::<Result<Vec<_>, _>>()?;
+        let order_keys = self
+            .order_by
+            .iter()
+            .map(|sort| evaluate_all(sort.expr.as_ref(), batches))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let row_count: usize = batches
+            .iter()
+            .map(|batch| batch.columns.first().map_or(0, |col| col.values.len()))
+            .sum();
+
+        let compare_partition = |a: usize, b: usize| {
+            partition_keys
+                .iter()
+                .map(|key| compare_sort_values(&key[a], &key[b], true, true))
+                .find(|o| *o != Ordering::Equal)
+                .unwrap_or(Ordering::Equal)
+        };
+        let compare_order = |a: usize, b: usize| {
+            order_keys
+                .iter()
+                .zip(&self.order_by)
+                .map(|(key, sort)| compare_sort_values(&key[a], &key[b], sort.asc, sort.nulls_first))
+                .find(|o| *o != Ordering::Equal)
+                .unwrap_or(Ordering::Equal)
+        };
+
+        let mut sorted: Vec<usize> = (0..row_count).collect();
+        sorted.sort_by(|&a, &b| compare_partition(a, b).then_with(|| compare_order(a, b)));
+
+        let mut output = vec![ScalarValue::null(self.return_type); row_count];
+        let mut start = 0;
+        while start < sorted.len() {
+            let mut end = start + 1;
+            while end < sorted.len() && compare_partition(sorted[start], sorted[end]) == Ordering::Equal {
+                end += 1;
+            }
+            let partition = &sorted[start..end];
+
+            // peer_start[i]..peer_end[i] is the run of rows sharing row i's ORDER BY keys.
+            let mut peer_start = vec![0; partition.len()];
+            let mut peer_end = vec![0; partition.len()];
+            let mut group_start = 0;
+            for i in 1..=partition.len() {
+                if i == partition.len() || compare_order(partition[group_start], partition[i]) != Ordering::Equal {
+                    for j in group_start..i {
+                        peer_start[j] = group_start;
+                        peer_end[j] = i;
+                    }
+                    group_start = i;
+                }
+            }
+
+            let peers = Peers { start: &peer_start, end: &peer_end };
+            self.evaluate_partition(partition, &args, order_keys.first(), &peers, &mut output)?;
+            start = end;
+        }
+        Ok(output)
+    }
+
+    fn evaluate_partition(
+        &self,
+        partition: &[usize],
+        args: &[Vec<ScalarValue>],
+        order_key: Option<&Vec<ScalarValue>>,
+        peers: &Peers,
+        output: &mut [ScalarValue],
+    ) -> Result<(), QueryError> {
+        match self.func {
+            WindowFunction::RowNumber => {
+                for (pos, &row) in partition.iter().enumerate() {
+                    output[row] = ScalarValue::Int32(Some(pos as i32 + 1));
+                }
+            }
+            WindowFunction::Rank => {
+                for (pos, &row) in partition.iter().enumerate() {
+                    output[row] = ScalarValue::Int32(Some(peers.start[pos] as i32 + 1));
+                }
+            }
+            WindowFunction::DenseRank => {
+                let mut rank = 0;
+                for (pos, &row) in partition.iter().enumerate() {
+                    if peers.start[pos] == pos {
+                        rank += 1;
+                    }
+                    output[row] = ScalarValue::Int32(Some(rank));
+                }
+            }
+            WindowFunction::Lag | WindowFunction::Lead => {
+                for (pos, &row) in partition.iter().enumerate() {
+                    let offset = match args.get(1).map(|offsets| &offsets[row]) {
+                        None => 1,
+                        Some(ScalarValue::Int32(Some(offset))) => *offset as i64,
+                        Some(_) => continue,
+                    };
+                    let target = if self.func == WindowFunction::Lag {
+                        pos as i64 - offset
+                    } else {
+                        pos as i64 + offset
+                    };
+                    output[row] = if target >= 0 && (target as usize) < partition.len() {
+                        cast_scalar(&args[0][partition[target as usize]], self.return_type)
+                    } else {
+                        match args.get(2) {
+                            Some(defaults) => cast_scalar(&defaults[row], self.return_type),
+                            None => ScalarValue::null(self.return_type),
+                        }
+                    };
+                }
+            }
+            WindowFunction::FirstValue => {
+                for (pos, &row) in partition.iter().enumerate() {
+                    let (start, end) = self.frame_bounds(pos, partition, order_key, peers)?;
+                    if start < end {
+                        output[row] = args[0][partition[start]].clone();
+                    }
+                }
+            }
+            WindowFunction::Sum | WindowFunction::Avg => {
+                // Prefix sums over the partition make every frame lookup O(1).
+                let mut int_sums = vec![0i64; partition.len() + 1];
+                let mut float_sums = vec![0f64; partition.len() + 1];
+                let mut counts = vec![0usize; partition.len() + 1];
+                for (pos, &row) in partition.iter().enumerate() {
+                    let (int_value, float_value, count) = match &args[0][row] {
+                        ScalarValue::Int32(Some(v)) => (i64::from(*v), f64::from(*v), 1),
+                        ScalarValue::Float64(Some(v)) => (0, *v, 1),
+                        _ => (0, 0.0, 0),
+                    };
+                    int_sums[pos + 1] = int_sums[pos] + int_value;
+                    float_sums[pos + 1] = float_sums[pos] + float_value;
+                    counts[pos + 1] = counts[pos] + count;
+                }
+
+                for (pos, &row) in partition.iter().enumerate() {
+                    let (start, end) = self.frame_bounds(pos, partition, order_key, peers)?;
+                    let count = counts[end] - counts[start];
+                    if count == 0 {
+                        continue;
+                    }
+                    let float_sum = float_sums[end] - float_sums[start];
+                    output[row] = match (self.func, self.return_type) {
+                        (WindowFunction::Avg, _) => ScalarValue::Float64(Some(float_sum / count as f64)),
+                        (_, DataType::Int32) => {
+                            let sum = i32::try_from(int_sums[end] - int_sums[start]).map_err(|_| {
+                                QueryError::ValidationError { message: "SUM overflowed Int32".to_string() }
+                            })?;
+                            ScalarValue::Int32(Some(sum))
+                        }
+                        _ => ScalarValue::Float64(Some(float_sum)),
+                    };
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the half-open range of partition positions inside the frame of row `pos`.
+    fn frame_bounds(
+        &self,
+        pos: usize,
+        partition: &[usize],
+        order_key: Option<&Vec<ScalarValue>>,
+        peers: &Peers,
+    ) -> Result<(usize, usize), QueryError> {
+        let len = partition.len();
+        let start = self.bound_position(self.frame.start, true, pos, partition, order_key, peers)?;
+        let end = self.bound_position(self.frame.end, false, pos, partition, order_key, peers)?;
+        let start = start.min(len);
+        Ok((start, end.clamp(start, len)))
+    }
+
+    fn bound_position(
+        &self,
+        bound: WindowFrameBound,
+        is_start: bool,
+        pos: usize,
+        partition: &[usize],
+        order_key: Option<&Vec<ScalarValue>>,
+        peers: &Peers,
+    ) -> Result<usize, QueryError> {
+        let len = partition.len();
+        let (offset, forward) = match bound {
+            WindowFrameBound::UnboundedPreceding => return Ok(0),
+            WindowFrameBound::UnboundedFollowing => return Ok(len),
+            WindowFrameBound::CurrentRow => {
+                return Ok(match (self.frame.units, is_start) {
+                    (WindowFrameUnits::Rows, true) => pos,
+                    (WindowFrameUnits::Rows, false) => pos + 1,
+                    (WindowFrameUnits::Range, true) => peers.start[pos],
+                    (WindowFrameUnits::Range, false) => peers.end[pos],
+                });
+            }
+            WindowFrameBound::Preceding(n) => (n as usize, false),
+            WindowFrameBound::Following(n) => (n as usize, true),
+        };
+
+        if self.frame.units == WindowFrameUnits::Rows {
+            return Ok(match (forward, is_start) {
+                (true, true) => pos + offset,
+                (true, false) => pos + offset + 1,
+                (false, true) => pos.saturating_sub(offset),
+                (false, false) => (pos + 1).saturating_sub(offset),
+            });
+        }
+
+        // RANGE with an offset: compare numeric ORDER BY values.
+        let keys = order_key.ok_or_else(|| QueryError::ValidationError {
+            message: "RANGE frames with an offset require an ORDER BY".to_string(),
+        })?;
+        let current = match as_f64(&keys[partition[pos]]) {
+            Some(value) => value,
+            None => return Ok(if is_start { peers.start[pos] } else { peers.end[pos] }),
+        };
+        let asc = self.order_by[0].asc;
+        let step = if forward == asc { offset as f64 } else { -(offset as f64) };
+        let target = current + step;
+
+        // Rows with a null key sit at one end of the partition; only search the others.
+        let non_null: Vec<usize> = (0..len).filter(|&i| as_f64(&keys[partition[i]]).is_some()).collect();
+        let (first, last) = (non_null[0], non_null[non_null.len() - 1] + 1);
+        let slice = &partition[first..last];
+        let found = if is_start {
+            slice.partition_point(|&row| {
+                let value = as_f64(&keys[row]).unwrap_or_default();
+                if asc { value < target } else { value > target }
+            })
+        } else {
+            slice.partition_point(|&row| {
+                let value = as_f64(&keys[row]).unwrap_or_default();
+                if asc { value <= target } else { value >= target }
+            })
+        };
+        Ok(first + found)
+    }
+}
+
+struct Peers<'a> {
+    start: &'a [usize],
+    end: &'a [usize],
+}
+
+fn as_f64(value: &ScalarValue) -> Option<f64> {
+    match value {
+        ScalarValue::Int32(Some(v)) => Some(f64::from(*v)),
+        ScalarValue::Float64(Some(v)) => Some(*v),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::logical_plan::plan::Catalog;
+    use crate::test_util::{float, int, rows, text};
+
+    use super::*;
+
+    /// A catalog with table `s (g, x)`, its rows out of order.
+    fn catalog() -> Catalog {
+        let mut catalog = Catalog::new();
+        rows(&mut catalog, "CREATE TABLE s (g TEXT, x INT); INSERT INTO s VALUES ('b', 7), ('a', 2), ('a', 1), ('b', 5), ('a', 4), ('a', 2)");
+        catalog
+    }
+
+    #[test]
+    fn ranking_numbers_rows_within_each_partition() {
+        let mut catalog = catalog();
+        let sql = "SELECT g, x, ROW_NUMBER() OVER (PARTITION BY g ORDER BY x), RANK() OVER (PARTITION BY g ORDER BY x), \
+                   DENSE_RANK() OVER (PARTITION BY g ORDER BY x) FROM s";
+        // Rows keep their input order, and ties are numbered in that order.
+        assert_eq!(rows(&mut catalog, sql), vec![
+            vec![text("b"), int(7), int(2), int(2), int(2)],
+            vec![text("a"), int(2), int(2), int(2), int(2)],
+            vec![text("a"), int(1), int(1), int(1), int(1)],
+            vec![text("b"), int(5), int(1), int(1), int(1)],
+            vec![text("a"), int(4), int(4), int(4), int(3)],
+            vec![text("a"), int(2), int(3), int(2), int(2)],
+        ]);
+    }
+
+    #[test]
+    fn frames_pick_the_rows_an_aggregate_sees() {
+        let mut catalog = catalog();
+        let sql = "SELECT x, SUM(x) OVER (ORDER BY x ROWS BETWEEN 1 PRECEDING AND CURRENT ROW), SUM(x) OVER (ORDER BY x), \
+                   SUM(x) OVER (ORDER BY x RANGE BETWEEN 1 PRECEDING AND 1 FOLLOWING), AVG(x) OVER (PARTITION BY g), \
+                   SUM(x) OVER () FROM s WHERE g = 'a'";
+        // The default frame with ORDER BY runs up to the last peer of the row.
+        assert_eq!(rows(&mut catalog, sql), vec![
+            vec![int(2), int(3), int(5), int(5), float(2.25), int(9)],
+            vec![int(1), int(1), int(1), int(5), float(2.25), int(9)],
+            vec![int(4), int(6), int(9), int(4), float(2.25), int(9)],
+            vec![int(2), int(4), int(5), int(5), float(2.25), int(9)],
+        ]);
+    }
+
+    #[test]
+    fn offsets_read_other_rows_of_the_partition() {
+        let mut catalog = catalog();
+        let sql = "SELECT x, LAG(x) OVER (PARTITION BY g ORDER BY x), LEAD(x, 2, 0) OVER (PARTITION BY g ORDER BY x), \
+                   FIRST_VALUE(x) OVER (PARTITION BY g ORDER BY x DESC) FROM s";
+        assert_eq!(rows(&mut catalog, sql), vec![
+            vec![int(7), int(5), int(0), int(7)],
+            vec![int(2), int(1), int(4), int(4)],
+            vec![int(1), ScalarValue::Int32(None), int(2), int(4)],
+            vec![int(5), ScalarValue::Int32(None), int(0), int(7)],
+            vec![int(4), int(2), int(0), int(4)],
+            vec![int(2), int(2), int(0), int(4)],
+        ]);
+    }
+
+    #[test]
+    fn aggregates_inside_window_calls_are_grouped_first() {
+        let mut catalog = catalog();
+        let sql = "SELECT g, SUM(x), RANK() OVER (ORDER BY SUM(x) DESC), SUM(SUM(x)) OVER () FROM s GROUP BY g";
+        // Groups come out in the order they are first seen.
+        assert_eq!(rows(&mut catalog, sql), vec![vec![text("b"), int(12), int(1), int(21)], vec![text("a"), int(9), int(2), int(21)]]);
+    }
+}
@@ -0,0 +1,453 @@
+use std::{collections::BTreeMap, env, fs, io, path::{Component, Path, PathBuf}};
+
+use serde_json::{Map, Value};
+
+use crate::logical_plan::definition::{Definition, ExternalFormat, ExternalTable};
+use crate::logical_plan::names::{DEFAULT_DATABASE, DEFAULT_SCHEMA, QualifiedName};
+use crate::logical_plan::plan::Catalog;
+use crate::errors::{LexerError, QueryError};
+use crate::{DataType, Field, Schema};
+
+use super::ddl::plan_view;
+use super::planner::register_external_table;
+use super::sql::{Statement, parse_sql};
+
+// A catalog file is a JSON object with the schemas of each database, and in
+// each schema the external tables and the views created with SQL, keyed by
+// name:
+//
+// {
+//   "databases": {
+//     "main": {
+//       "public": {
+//         "tables": {
+//           "students": {
+//             "format": "csv",
+//             "location": "data/students.csv",
+//             "columns": [{ "name": "id", "type": "Int32", "nullable": false }],
+//             "options": { "delimiter": ";" }
+//           }
+//         },
+//         "views": {
+//           "verified": {
+//             "query": "SELECT Name FROM students WHERE IsVerified = true",
+//             "columns": [],
+//             "database": "main",
+//             "search_path": ["public"]
+//           }
+//         }
+//       },
+//       "analytics": {}
+//     }
+//   }
+// }
+//
+// `tables`, `views`, `columns` and `options` may be left out. A view's query
+// is planned with `database` as the current database and `search_path` as the
+// search path, which default to the view's own database and schema. Files
+// written before there were schemas hold `tables` and `views` at the top
+// level, which are read into `main.public`. Relative locations are relative to
+// the directory of the catalog file, so the file can move along with its data.
+// In-memory tables are not saved, so views over them are refused while a file
+// is open.
+
+/// Loads the schemas, tables and views defined in the catalog file at `path`
+/// and makes DDL statements save the catalog back to it. A file that does not
+/// exist yet is an empty catalog, and is created by the first DDL statement.
+pub fn open_catalog_file(path: &Path, catalog: &mut Catalog) -> Result<(), QueryError> {
+    catalog.set_file_path(path.to_path_buf());
+    let text = match fs::read_to_string(path) {
+        Ok(text) => text,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(()),
+        Err(e) => return Err(QueryError::DataSourceError {
+            message: format!("Failed to read catalog file {}: {}", path.display(), e),
+        }),
+    };
+    let invalid = |message: String| QueryError::ValidationError {
+        message: format!("Invalid catalog file {}: {}", path.display(), message),
+    };
+    let root: Value = serde_json::from_str(&text).map_err(|e| invalid(e.to_string()))?;
+    let root = root.as_object().ok_or_else(|| invalid("expected a JSON object".to_string()))?;
+    let (file_dir, current_dir) = location_dirs(path).map_err(|e| invalid(e.to_string()))?;
+
+    let mut contents = vec![(DEFAULT_DATABASE.to_string(), DEFAULT_SCHEMA.to_string(), root)];
+    for (database, schemas) in members(root, "databases").map_err(invalid)? {
+        let schemas = schemas.as_object().ok_or_else(|| invalid(format!("database '{}' must be an object", database)))?;
+        for (schema, objects) in schemas {
+            let objects = objects.as_object().ok_or_else(|| invalid(format!("schema '{}.{}' must be an object", database, schema)))?;
+            catalog.register_schema(database.clone(), schema.clone());
+            contents.push((database.clone(), schema.clone(), objects));
+        }
+    }
+
+    // Tables registered before loading, such as the command line's file, are
+    // not replaced: an entry of the file with the same name is an error.
+    let taken = |name: &QualifiedName, catalog: &Catalog| match catalog.resolve(&name.reference()) {
+        Ok(Some(_)) => Err(invalid(format!("'{}' is already registered", name))),
+        _ => Ok(()),
+    };
+    let mut views = Vec::new();
+    for (database, schema, objects) in contents {
+        for (table_name, table) in members(objects, "tables").map_err(invalid)? {
+            let name = QualifiedName { database: database.clone(), schema: schema.clone(), table: table_name.clone() };
+            taken(&name, catalog)?;
+            let mut table = external_table_from_json(table).map_err(|message| invalid(format!("table '{}': {}", name, message)))?;
+            table.location = rebase_location(&table.location, &file_dir, &current_dir);
+            register_external_table(name.reference(), table, catalog).map_err(|e| invalid(format!("table '{}': {:?}", name, e)))?;
+        }
+        for (view_name, view) in members(objects, "views").map_err(invalid)? {
+            let name = QualifiedName { database: database.clone(), schema: schema.clone(), table: view_name.clone() };
+            taken(&name, catalog)?;
+            let view = view_from_json(view, &name).map_err(|message| invalid(format!("view '{}': {}", name, message)))?;
+            views.push((name, view));
+        }
+    }
+    // A view may read views listed after it, so views are planned in rounds
+    // until every one is planned or a round makes no progress.
+    while !views.is_empty() {
+        let mut unplanned = Vec::new();
+        let mut last_error = None;
+        let view_count = views.len();
+        for (name, view) in views {
+            match plan_view_sql(&name, &view, catalog) {
+                Ok(()) => {},
+                Err(e) => {
+                    last_error = Some(invalid(format!("view '{}': {:?}", name, e)));
+                    unplanned.push((name, view));
+                },
+            }
+        }
+        if unplanned.len() == view_count
+            && let Some(error) = last_error
+        {
+            return Err(error);
+        }
+        views = unplanned;
+    }
+    Ok(())
+}
+
+/// Writes the schemas of the catalog and the definitions of its tables and
+/// views to its catalog file. Does nothing when the catalog has no file.
+pub fn save_catalog_file(catalog: &Catalog) -> Result<(), QueryError> {
+    let Some(path) = catalog.file_path() else {
+        return Ok(());
+    };
+    let write_error = |e: io::Error| QueryError::DataSourceError {
+        message: format!("Failed to write catalog file {}: {}", path.display(), e),
+    };
+    let (file_dir, current_dir) = location_dirs(path).map_err(write_error)?;
+    let mut databases: BTreeMap<&str, BTreeMap<&str, Map<String, Value>>> = BTreeMap::new();
+    for (database, schema) in catalog.schema_names() {
+        databases.entry(database).or_default().insert(schema, Map::new());
+    }
+    for (name, definition) in catalog.definitions() {
+        let (key, value) = match definition {
+            Definition::ExternalTable(table) => {
+                let location = rebase_location(&table.location, &current_dir, &file_dir);
+                ("tables", external_table_to_json(table, &location))
+            },
+            Definition::View { query, columns, database, search_path } => {
+                let mut view = Map::new();
+                view.insert("query".to_string(), Value::from(query.as_str()));
+                view.insert("columns".to_string(), Value::from(columns.clone()));
+                view.insert("database".to_string(), Value::from(database.as_str()));
+                view.insert("search_path".to_string(), Value::from(search_path.clone()));
+                ("views", Value::Object(view))
+            },
+        };
+        let objects = databases.entry(&name.database).or_default().entry(&name.schema).or_default();
+        if let Value::Object(members) = objects.entry(key).or_insert_with(|| Value::Object(Map::new())) {
+            members.insert(name.table.clone(), value);
+        }
+    }
+    let databases = databases
+        .into_iter()
+        .map(|(database, schemas)| {
+            let schemas = schemas.into_iter().map(|(schema, objects)| (schema.to_string(), Value::Object(objects))).collect();
+            (database.to_string(), Value::Object(schemas))
+        })
+        .collect();
+    let mut root = Map::new();
+    root.insert("databases".to_string(), Value::Object(databases));
+
+    // The catalog is written next to the file and then renamed over it, so a
+    // failed write leaves the old catalog in place.
+    let mut temp_path = path.as_os_str().to_owned();
+    temp_path.push(".tmp");
+    let text = serde_json::to_string_pretty(&Value::Object(root)).map_err(|e| write_error(e.into()))?;
+    fs::write(&temp_path, text + "\n").map_err(write_error)?;
+    fs::rename(&temp_path, path).map_err(write_error)
+}
+
+/// Plans a saved view with the database and search path it was created with,
+/// and registers it as `name`.
+fn plan_view_sql(name: &QualifiedName, view: &Definition, catalog: &mut Catalog) -> Result<(), QueryError> {
+    let Definition::View { query, columns, database, search_path } = view else {
+        return Err(QueryError::ValidationError {
+            message: format!("'{}' is not a view", name),
+        });
+    };
+    let statements = parse_sql(query).map_err(|LexerError::InvalidToken { message }| QueryError::ValidationError { message })?;
+    let [Statement::Query(parsed)] = statements.as_slice() else {
+        return Err(QueryError::ValidationError {
+            message: format!("Not a single query: {}", query),
+        });
+    };
+    let previous_database = catalog.current_database().to_string();
+    let previous_search_path = catalog.search_path().to_vec();
+    catalog.set_search_path(database.clone(), search_path.clone());
+    let plan = plan_view(parsed, &name.to_string(), columns, catalog);
+    catalog.set_search_path(previous_database, previous_search_path);
+
+    catalog.register_view(name.reference(), plan?);
+    catalog.define(name.reference(), view.clone());
+    Ok(())
+}
+
+/// The directory of the catalog file at `path` and the directory the engine
+/// runs in, both absolute. Relative locations are rebased between the two.
+fn location_dirs(path: &Path) -> io::Result<(PathBuf, PathBuf)> {
+    let current_dir = normalize(&env::current_dir()?);
+    let file_dir = normalize(&current_dir.join(path.parent().unwrap_or(Path::new(""))));
+    Ok((file_dir, current_dir))
+}
+
+/// Rewrites a `location` relative to the directory `from` to be relative to
+/// the directory `to`. Absolute locations are left as they are.
+fn rebase_location(location: &str, from: &Path, to: &Path) -> String {
+    if Path::new(location).is_absolute() {
+        return location.to_string();
+    }
+    let target = normalize(&from.join(location));
+    let common = target.components().zip(to.components()).take_while(|(a, b)| a == b).count();
+    let mut relative: PathBuf = to.components().skip(common).map(|_| Component::ParentDir).collect();
+    relative.extend(target.components().skip(common));
+    relative.to_string_lossy().into_owned()
+}
+
+/// Removes the `.` and `..` components of a path without looking at the file system.
+fn normalize(path: &Path) -> PathBuf {
+    let mut normal = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {},
+            Component::ParentDir if matches!(normal.components().next_back(), Some(Component::Normal(_))) => {
+                normal.pop();
+            },
+            component => normal.push(component),
+        }
+    }
+    normal
+}
+
+/// The members of the object `key` of `root`, which may be left out.
+fn members<'a>(root: &'a Map<String, Value>, key: &str) -> Result<Vec<(&'a String, &'a Value)>, String> {
+    match root.get(key) {
+        None => Ok(Vec::new()),
+        Some(Value::Object(members)) => Ok(members.iter().collect()),
+        Some(_) => Err(format!("\"{}\" must be an object", key)),
+    }
+}
+
+/// The JSON of an external table, saved with `location` rather than its own.
+fn external_table_to_json(table: &ExternalTable, location: &str) -> Value {
+    let mut object = Map::new();
+    let format = match table.format {
+        ExternalFormat::Csv => "csv",
+        ExternalFormat::Json => "json",
+        ExternalFormat::Parquet => "parquet",
+    };
+    object.insert("format".to_string(), Value::from(format));
+    object.insert("location".to_string(), Value::from(location));
+    if let Some(schema) = &table.schema {
+        let columns = schema
+            .fields
+            .iter()
+            .map(|field| {
+                let mut column = Map::new();
+                column.insert("name".to_string(), Value::from(field.name.as_str()));
+                column.insert("type".to_string(), Value::from(format!("{:?}", field.field_type)));
+                column.insert("nullable".to_string(), Value::from(field.is_nullable));
+                Value::Object(column)
+            })
+            .collect();
+        object.insert("columns".to_string(), Value::Array(columns));
+    }
+    if !table.options.is_empty() {
+        let options = table.options.iter().map(|(key, value)| (key.clone(), Value::from(value.as_str()))).collect();
+        object.insert("options".to_string(), Value::Object(options));
+    }
+    Value::Object(object)
+}
+
+fn external_table_from_json(value: &Value) -> Result<ExternalTable, String> {
+    let object = value.as_object().ok_or("expected an object")?;
+    let format_name = string_member(object, "format")?;
+    let format = match format_name.to_lowercase().as_str() {
+        "csv" => ExternalFormat::Csv,
+        "json" => ExternalFormat::Json,
+        "parquet" => ExternalFormat::Parquet,
+        _ => return Err(format!("unknown format '{}'", format_name)),
+    };
+    let location = string_member(object, "location")?.to_string();
+
+    let schema = match object.get("columns") {
+        None => None,
+        Some(Value::Array(columns)) => Some(Schema::new(columns.iter().map(field_from_json).collect::<Result<Vec<Field>, String>>()?)),
+        Some(_) => return Err("\"columns\" must be an array".to_string()),
+    };
+    let options = match object.get("options") {
+        None => Vec::new(),
+        Some(Value::Object(options)) => options
+            .iter()
+            .map(|(key, value)| match value {
+                Value::String(text) => Ok((key.clone(), text.clone())),
+                Value::Number(_) | Value::Bool(_) => Ok((key.clone(), value.to_string())),
+                _ => Err(format!("option '{}' must be a string, number or boolean", key)),
+            })
+            .collect::<Result<Vec<(String, String)>, String>>()?,
+        Some(_) => return Err("\"options\" must be an object".to_string()),
+    };
+    Ok(ExternalTable { format, location, schema, options })
+}
+
+fn field_from_json(value: &Value) -> Result<Field, String> {
+    let object = value.as_object().ok_or("each column must be an object")?;
+    let name = string_member(object, "name")?.to_string();
+    let type_name = string_member(object, "type")?;
+    let field_type = match type_name.to_lowercase().as_str() {
+        "int32" => DataType::Int32,
+        "float64" => DataType::Float64,
+        "string" => DataType::String,
+        "bool" => DataType::Bool,
+        _ => return Err(format!("unknown type '{}' for column {}", type_name, name)),
+    };
+    let is_nullable = match object.get("nullable") {
+        None => true,
+        Some(Value::Bool(nullable)) => *nullable,
+        Some(_) => return Err(format!("\"nullable\" of column {} must be true or false", name)),
+    };
+    Ok(Field { name, field_type, is_nullable })
+}
+
+fn view_from_json(value: &Value, name: &QualifiedName) -> Result<Definition, String> {
+    let object = value.as_object().ok_or("expected an object")?;
+    let query = string_member(object, "query")?.to_string();
+    let columns = string_array_member(object, "columns")?.unwrap_or_default();
+    let database = match object.get("database") {
+        None => name.database.clone(),
+        Some(_) => string_member(object, "database")?.to_string(),
+    };
+    let search_path = string_array_member(object, "search_path")?.unwrap_or_else(|| vec![name.schema.clone()]);
+    Ok(Definition::View { query, columns, database, search_path })
+}
+
+fn string_array_member(object: &Map<String, Value>, key: &str) -> Result<Option<Vec<String>>, String> {
+    match object.get(key) {
+        None => Ok(None),
+        Some(Value::Array(values)) => values
+            .iter()
+            .map(|value| value.as_str().map(str::to_string).ok_or(format!("\"{}\" must hold strings", key)))
+            .collect::<Result<Vec<String>, String>>()
+            .map(Some),
+        Some(_) => Err(format!("\"{}\" must be an array", key)),
+    }
+}
+
+fn string_member<'a>(object: &'a Map<String, Value>, key: &str) -> Result<&'a str, String> {
+    match object.get(key) {
+        Some(Value::String(value)) => Ok(value),
+        Some(_) => Err(format!("\"{}\" must be a string", key)),
+        None => Err(format!("\"{}\" is missing", key)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{error_message, int, rows, temp_dir, text};
+
+    fn open(path: &Path) -> Catalog {
+        let mut catalog = Catalog::new();
+        open_catalog_file(path, &mut catalog).unwrap();
+        catalog
+    }
+
+    #[test]
+    fn saved_tables_and_views_load_in_a_new_catalog() {
+        let path = temp_dir("catalog-round-trip").join("catalog.json");
+        let mut catalog = open(&path);
+        rows(&mut catalog, "CREATE SCHEMA analytics");
+        rows(&mut catalog, "CREATE EXTERNAL TABLE analytics.Students STORED AS CSV LOCATION 'test/students.csv'");
+        // `first` reads `named`, which is saved after it, so loading takes two rounds.
+        rows(&mut catalog, "CREATE VIEW analytics.named (who, verified) AS SELECT Name, IsVerified FROM analytics.students");
+        rows(&mut catalog, "CREATE VIEW first AS SELECT who FROM analytics.named WHERE who = 'Student 1'");
+
+        let mut loaded = open(&path);
+        assert_eq!(rows(&mut loaded, "SELECT * FROM first"), vec![vec![text("Student 1")]]);
+        assert_eq!(rows(&mut loaded, "SELECT COUNT(*) FROM analytics.named WHERE verified = true"), rows(&mut catalog, "SELECT COUNT(*) FROM analytics.students WHERE IsVerified = true"));
+        assert!(loaded.has_schema("main", "analytics"));
+        assert!(loaded.contains("analytics.\"Students\""));
+    }
+
+    #[test]
+    fn files_without_schemas_load_into_main_public() {
+        let path = temp_dir("catalog-legacy").join("catalog.json");
+        let location = fs::canonicalize("test/students.csv").unwrap();
+        fs::write(&path, format!(r#"{{
+            "tables": {{ "students": {{ "format": "csv", "location": "{}" }} }},
+            "views": {{ "two": {{ "query": "SELECT COUNT(*) AS n FROM students WHERE Name = 'Student 2'", "columns": [] }} }}
+        }}"#, location.display())).unwrap();
+        let mut catalog = open(&path);
+        assert_eq!(rows(&mut catalog, "SELECT n FROM main.public.two"), vec![vec![int(1)]]);
+    }
+
+    #[test]
+    fn locations_are_relative_to_the_catalog_file() {
+        let dir = temp_dir("catalog-relative");
+        fs::create_dir_all(dir.join("data")).unwrap();
+        fs::copy("test/students.csv", dir.join("data/students.csv")).unwrap();
+        let path = dir.join("catalog.json");
+        fs::write(&path, r#"{ "tables": { "students": { "format": "csv", "location": "data/students.csv" } } }"#).unwrap();
+
+        let mut catalog = open(&path);
+        let expected = rows(&mut catalog, "SELECT COUNT(*) FROM students");
+        // Locations given in SQL are relative to the directory the engine runs in.
+        rows(&mut catalog, "CREATE EXTERNAL TABLE here STORED AS CSV LOCATION 'test/students.csv'");
+        let saved: Value = serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+        let location = |table: &str| saved["databases"]["main"]["public"]["tables"][table]["location"].clone();
+        assert_eq!(location("students"), "data/students.csv");
+        let here = PathBuf::from(location("here").as_str().unwrap());
+        assert!(here.is_relative());
+        assert_eq!(normalize(&dir.join(here)), normalize(&env::current_dir().unwrap().join("test/students.csv")));
+
+        let mut loaded = open(&path);
+        assert_eq!(rows(&mut loaded, "SELECT COUNT(*) FROM here"), expected);
+    }
+
+    #[test]
+    fn views_over_unsaved_tables_are_refused() {
+        let path = temp_dir("catalog-unsaved").join("catalog.json");
+        let mut catalog = open(&path);
+        rows(&mut catalog, "CREATE TABLE mem (a INT)");
+        assert_eq!(
+            error_message(&mut catalog, "CREATE VIEW mv AS SELECT a FROM mem"),
+            "View 'mv' reads 'main.public.mem', which is not saved in the catalog file; only external tables and views are",
+        );
+        assert!(!catalog.contains("mv"));
+        assert!(!open(&path).contains("mem"));
+    }
+
+    #[test]
+    fn names_already_registered_are_not_replaced() {
+        let path = temp_dir("catalog-clash").join("catalog.json");
+        fs::write(&path, r#"{ "tables": { "students": { "format": "csv", "location": "test/students.csv" } } }"#).unwrap();
+        let mut catalog = Catalog::new();
+        rows(&mut catalog, "CREATE TABLE students (a INT)");
+        let Err(QueryError::ValidationError { message }) = open_catalog_file(&path, &mut catalog) else {
+            panic!("loading over a registered table succeeded");
+        };
+        assert!(message.ends_with("'main.public.students' is already registered"), "{}", message);
+    }
+}
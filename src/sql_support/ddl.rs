@@ -0,0 +1,304 @@
+use std::rc::Rc;
+
+use sqlparser::ast::{ObjectName, ObjectType, Query, SchemaName, SetExpr, Statement, TableFactor};
+
+use crate::datasource::memory::MemTable;
+use crate::logical_plan::definition::Definition;
+use crate::logical_plan::names::QualifiedName;
+use crate::logical_plan::plan::{Catalog, LogicalPlan};
+use crate::errors::QueryError;
+
+use super::sql::parse_sql;
+use super::planner::{column_defs_to_schema, query_to_logical_plan, rename_columns, table_name};
+
+/// Runs `CREATE TABLE name (column type [NOT NULL], ...)`, registering an empty
+/// in-memory table with those columns. Rows are then added with `INSERT`.
+/// `OR REPLACE` replaces an existing table unless a view reads it.
+pub fn create_table(statement: &Statement, catalog: &mut Catalog) -> Result<(), QueryError> {
+    let create = match statement {
+        Statement::CreateTable(create) if !create.external && create.query.is_none() => create,
+        _ => return Err(QueryError::ValidationError {
+            message: format!("Not a CREATE TABLE statement: {}", statement),
+        }),
+    };
+    if create.columns.is_empty() {
+        return Err(QueryError::ValidationError {
+            message: "CREATE TABLE needs a column list or AS and a query".to_string(),
+        });
+    }
+    if create.location.is_some() || create.hive_formats.as_ref().is_some_and(|formats| formats.location.is_some()) {
+        return Err(QueryError::ValidationError {
+            message: "CREATE TABLE with a LOCATION reads a file, so it needs EXTERNAL".to_string(),
+        });
+    }
+    let qualified = catalog.qualify(&table_name(&create.name))?;
+    let name = qualified.reference();
+    if catalog.contains(&name) {
+        if !create.or_replace {
+            if create.if_not_exists {
+                return Ok(());
+            }
+            return Err(QueryError::ValidationError {
+                message: format!("Table '{}' already exists", create.name),
+            });
+        }
+        check_not_read_by_views(&[qualified], "replace", catalog)?;
+    }
+
+    let schema = column_defs_to_schema(&create.columns)?;
+    for (i, field) in schema.fields.iter().enumerate() {
+        if schema.fields[..i].iter().any(|other| other.name == field.name) {
+            return Err(QueryError::ValidationError {
+                message: format!("Column {} is declared more than once", field.name),
+            });
+        }
+    }
+    catalog.register_table(name, Rc::new(MemTable::try_new(schema, Vec::new())?));
+    Ok(())
+}
+
+/// Runs `CREATE [OR REPLACE] VIEW name [(columns)] AS query`. The query is
+/// planned now, so it must refer to existing tables and columns, and its plan
+/// is stored in the catalog to be planned in place of the view by later
+/// queries. The listed columns rename the query's columns in order. `OR
+/// REPLACE` replaces an existing view unless another view reads it.
+pub fn create_view(statement: &Statement, catalog: &mut Catalog) -> Result<(), QueryError> {
+    let Statement::CreateView(create) = statement else {
+        return Err(QueryError::ValidationError {
+            message: format!("Not a CREATE VIEW statement: {}", statement),
+        });
+    };
+    if create.materialized {
+        return Err(QueryError::ValidationError {
+            message: "Materialized views are not supported; use CREATE TABLE ... AS instead".to_string(),
+        });
+    }
+    let qualified = catalog.qualify(&table_name(&create.name))?;
+    let name = qualified.reference();
+    if catalog.get_schema(&name).is_some() {
+        return Err(QueryError::ValidationError {
+            message: format!("'{}' is a table, not a view", create.name),
+        });
+    }
+    if catalog.get_view(&name).is_some() {
+        if !create.or_replace {
+            if create.if_not_exists {
+                return Ok(());
+            }
+            return Err(QueryError::ValidationError {
+                message: format!("View '{}' already exists", create.name),
+            });
+        }
+        check_not_read_by_views(&[qualified], "replace", catalog)?;
+    }
+
+    let columns: Vec<String> = create.columns.iter().map(|column| column.name.value.clone()).collect();
+    let plan = plan_view(&create.query, &name, &columns, catalog)?;
+    // A saved view is planned again when the catalog file is loaded, so every
+    // table and view it reads must be saved too.
+    if catalog.file_path().is_some() {
+        for read in read_relations(&create.query) {
+            if let Some(read) = catalog.resolve(&read)?
+                && !is_saved(catalog, &read)
+            {
+                return Err(QueryError::ValidationError {
+                    message: format!(
+                        "View '{}' reads '{}', which is not saved in the catalog file; only external tables and views are",
+                        create.name, read
+                    ),
+                });
+            }
+        }
+    }
+    let definition = Definition::View {
+        query: create.query.to_string(),
+        columns,
+        database: catalog.current_database().to_string(),
+        search_path: catalog.search_path().to_vec(),
+    };
+    catalog.register_view(name.clone(), plan);
+    catalog.define(name, definition);
+    Ok(())
+}
+
+/// Whether `name` is registered again when the catalog file is loaded: a table
+/// or view with a definition, or the rejected-rows table of an external table.
+fn is_saved(catalog: &Catalog, name: &QualifiedName) -> bool {
+    catalog.definitions().into_iter().any(|(defined, definition)| {
+        defined == name
+            || matches!(definition, Definition::ExternalTable(table) if table.options.iter().any(|(key, value)| {
+                key.eq_ignore_ascii_case("rejected_rows_table") && catalog.resolve(value).ok().flatten().as_ref() == Some(name)
+            }))
+    })
+}
+
+/// Plans the query of view `name`, renaming its first columns to `columns`.
+pub(crate) fn plan_view(query: &Query, name: &str, columns: &[String], catalog: &Catalog) -> Result<LogicalPlan, QueryError> {
+    let plan = query_to_logical_plan(query, catalog)?;
+    if columns.is_empty() {
+        return Ok(plan);
+    }
+    let names: Vec<&str> = columns.iter().map(String::as_str).collect();
+    rename_columns(plan, name, &names)
+}
+
+/// Runs `DROP TABLE`, `DROP VIEW` or `DROP SCHEMA` with one or more names,
+/// returning how many were dropped. Unless `IF EXISTS` is given, every name
+/// must exist. Nothing is dropped if a name is missing, a view created with SQL
+/// reads one of them, or a schema still holds tables or views.
+pub fn drop_objects(statement: &Statement, catalog: &mut Catalog) -> Result<usize, QueryError> {
+    let Statement::Drop { object_type, if_exists, names, .. } = statement else {
+        return Err(QueryError::ValidationError {
+            message: format!("Not a DROP statement: {}", statement),
+        });
+    };
+    let kind = match object_type {
+        ObjectType::Table => "Table",
+        ObjectType::View => "View",
+        ObjectType::Schema => return drop_schemas(names, *if_exists, catalog),
+        other => return Err(QueryError::ValidationError {
+            message: format!("DROP {} is not supported", other),
+        }),
+    };
+    let mut dropped: Vec<QualifiedName> = Vec::with_capacity(names.len());
+    for name in names {
+        let resolved = match catalog.resolve(&table_name(name)) {
+            Err(_) if *if_exists => None,
+            resolved => resolved?,
+        };
+        let exists = resolved.filter(|resolved| match object_type {
+            ObjectType::View => catalog.get_view(&resolved.reference()).is_some(),
+            _ => catalog.get_schema(&resolved.reference()).is_some(),
+        });
+        match exists {
+            Some(resolved) if dropped.contains(&resolved) => {},
+            Some(resolved) => dropped.push(resolved),
+            None if *if_exists => {},
+            None => return Err(QueryError::ValidationError {
+                message: format!("{} '{}' does not exist", kind, name),
+            }),
+        }
+    }
+
+    check_not_read_by_views(&dropped, "drop", catalog)?;
+    for name in &dropped {
+        if *object_type == ObjectType::View {
+            catalog.deregister_view(&name.reference());
+        } else {
+            catalog.deregister_table(&name.reference());
+        }
+    }
+    Ok(dropped.len())
+}
+
+/// Fails if a view created with SQL reads one of `names`, which are about to
+/// be dropped or replaced as `action` says. Such a view holds a plan of what it
+/// read before, and is saved as SQL that could not be planned again when the
+/// catalog is loaded.
+pub(crate) fn check_not_read_by_views(names: &[QualifiedName], action: &str, catalog: &Catalog) -> Result<(), QueryError> {
+    for (view, definition) in catalog.definitions() {
+        let Definition::View { query, database, search_path, .. } = definition else {
+            continue;
+        };
+        if names.contains(view) {
+            continue;
+        }
+        let read = match parse_sql(query).as_deref() {
+            Ok([Statement::Query(query)]) => read_relations(query),
+            _ => continue,
+        };
+        let read_name = read
+            .iter()
+            .filter_map(|name| catalog.resolve_from(name, database, search_path).ok().flatten())
+            .find(|name| names.contains(name));
+        if let Some(name) = read_name {
+            return Err(QueryError::ValidationError {
+                message: format!("Cannot {} '{}' because view '{}' reads it; drop the view first", action, name, view),
+            });
+        }
+    }
+    Ok(())
+}
+
+/// Runs `CREATE SCHEMA [IF NOT EXISTS] [database.]schema`.
+pub fn create_schema(statement: &Statement, catalog: &mut Catalog) -> Result<(), QueryError> {
+    let Statement::CreateSchema { schema_name, if_not_exists, .. } = statement else {
+        return Err(QueryError::ValidationError {
+            message: format!("Not a CREATE SCHEMA statement: {}", statement),
+        });
+    };
+    let SchemaName::Simple(name) = schema_name else {
+        return Err(QueryError::ValidationError {
+            message: format!("Unsupported schema name: {}", schema_name),
+        });
+    };
+    let (database, schema) = catalog.resolve_schema(&table_name(name))?;
+    if !catalog.register_schema(database, schema) && !if_not_exists {
+        return Err(QueryError::ValidationError {
+            message: format!("Schema '{}' already exists", name),
+        });
+    }
+    Ok(())
+}
+
+/// Drops the schemas `names`, which must not hold any tables or views.
+fn drop_schemas(names: &[ObjectName], if_exists: bool, catalog: &mut Catalog) -> Result<usize, QueryError> {
+    let mut dropped = Vec::with_capacity(names.len());
+    for name in names {
+        let (database, schema) = catalog.resolve_schema(&table_name(name))?;
+        if !catalog.has_schema(&database, &schema) {
+            if if_exists {
+                continue;
+            }
+            return Err(QueryError::ValidationError {
+                message: format!("Schema '{}' does not exist", name),
+            });
+        }
+        let held = catalog.table_names().into_iter().chain(catalog.view_names()).find(|held| held.database == database && held.schema == schema);
+        if let Some(held) = held {
+            return Err(QueryError::ValidationError {
+                message: format!("Cannot drop schema '{}' because it holds '{}'; drop its tables and views first", name, held.table),
+            });
+        }
+        if !dropped.contains(&(database.clone(), schema.clone())) {
+            dropped.push((database, schema));
+        }
+    }
+    for (database, schema) in &dropped {
+        catalog.deregister_schema(database, schema);
+    }
+    Ok(dropped.len())
+}
+
+/// The names of the tables and views `query` reads, including in subqueries.
+fn read_relations(query: &Query) -> Vec<String> {
+    fn from_set_expr(body: &SetExpr, names: &mut Vec<String>) {
+        match body {
+            SetExpr::Select(select) => {
+                for table in &select.from {
+                    from_table_factor(&table.relation, names);
+                    for join in &table.joins {
+                        from_table_factor(&join.relation, names);
+                    }
+                }
+            },
+            SetExpr::Query(query) => from_set_expr(&query.body, names),
+            SetExpr::SetOperation { left, right, .. } => {
+                from_set_expr(left, names);
+                from_set_expr(right, names);
+            },
+            _ => {},
+        }
+    }
+    fn from_table_factor(factor: &TableFactor, names: &mut Vec<String>) {
+        match factor {
+            TableFactor::Table { name, .. } => names.push(table_name(name)),
+            TableFactor::Derived { subquery, .. } => from_set_expr(&subquery.body, names),
+            _ => {},
+        }
+    }
+
+    let mut names = Vec::new();
+    from_set_expr(&query.body, &mut names);
+    names
+}
@@ -0,0 +1,423 @@
+use std::rc::Rc;
+
+use sqlparser::ast::{AssignmentTarget, Expr, FromTable, ObjectName, ObjectNamePart, Statement, TableFactor, TableObject, TableWithJoins};
+
+use crate::logical_plan::plan::{Catalog, Expression, LogicalPlan};
+use crate::optimizer::Optimizer;
+use crate::physical_plan::{eval::PhysicalExpr, planner::{create_physical_expr, create_physical_plan}};
+use crate::{ColumnVector, DataSource, DataType, Field, RecordBatch, ScalarValue, Schema, SessionConfig, errors::QueryError};
+
+use super::planner::{query_to_logical_plan, resolve_column, sql_expr_to_expression, table_name};
+
+/// Runs `INSERT INTO table [(columns)] query`, where the query is usually
+/// `VALUES (...), ...` or a `SELECT`. Returns the number of rows inserted.
+///
+/// The query's columns go into the listed columns, or into all of the table's
+/// columns in order, by position. Columns left out are NULL. Integers are
+/// widened for float columns. Any other type difference is an error before the
+/// query runs, and NULL in a column that is not nullable is an error once it
+/// is read. Either way no rows are inserted.
+pub fn insert_into(statement: &Statement, catalog: &Catalog, config: &SessionConfig) -> Result<usize, QueryError> {
+    let Statement::Insert(insert) = statement else {
+        return Err(QueryError::ValidationError {
+            message: format!("Not an INSERT statement: {}", statement),
+        });
+    };
+    if insert.overwrite || insert.ignore || insert.or.is_some() || insert.on.is_some() || insert.returning.is_some() {
+        return Err(QueryError::ValidationError {
+            message: format!("Unsupported INSERT clause in: {}", statement),
+        });
+    }
+    let Some(query) = &insert.source else {
+        return Err(QueryError::ValidationError {
+            message: "INSERT needs VALUES or a query to take its rows from".to_string(),
+        });
+    };
+    let name = match &insert.table {
+        TableObject::TableName(name) => table_name(name),
+        other => return Err(QueryError::ValidationError {
+            message: format!("Cannot insert into {}", other),
+        }),
+    };
+    let source = writable_table(catalog, &name)?;
+    let schema = source.schema().clone();
+
+    let mut targets = Vec::with_capacity(insert.columns.len());
+    for column in &insert.columns {
+        let field = resolve_column(&schema, column)?;
+        let index = schema.index_of(&field.name).map_err(|message| QueryError::ValidationError { message })?;
+        if targets.contains(&index) {
+            return Err(QueryError::ValidationError {
+                message: format!("Column {} is listed more than once", column.value),
+            });
+        }
+        targets.push(index);
+    }
+    if targets.is_empty() {
+        targets = (0..schema.fields.len()).collect();
+    }
+
+    let plan = query_to_logical_plan(query, catalog)?;
+    let query_schema = plan.schema()?;
+    if query_schema.fields.len() != targets.len() {
+        return Err(QueryError::ValidationError {
+            message: format!(
+                "INSERT into {} columns of '{}' got {} columns from its query",
+                targets.len(),
+                name,
+                query_schema.fields.len(),
+            ),
+        });
+    }
+    for (i, (field, &target)) in query_schema.fields.iter().zip(&targets).enumerate() {
+        if !holds_only_null(&plan, i) {
+            check_type(field.field_type, &schema.fields[target])?;
+        }
+    }
+    let plan = create_physical_plan(&Optimizer::new().optimize(&plan, catalog)?, catalog, config)?;
+
+    // Every row is checked before any is inserted.
+    let mut batches = Vec::new();
+    let mut row_count = 0;
+    for batch in plan.execute() {
+        let batch = batch?;
+        let rows = batch.columns.first().map_or(0, |column| column.values.len());
+        let mut columns: Vec<Vec<ScalarValue>> =
+            schema.fields.iter().map(|field| vec![ScalarValue::null(field.field_type); rows]).collect();
+        for (column, &target) in batch.columns.into_iter().zip(&targets) {
+            columns[target] = column.values;
+        }
+        batches.push(fit_batch(&schema, columns, row_count)?);
+        row_count += rows;
+    }
+    source.insert(batches)?;
+    Ok(row_count)
+}
+
+/// Runs `UPDATE table SET column = expression, ... [WHERE predicate]` and
+/// returns the number of rows changed. The expressions are computed from the
+/// row's values before the update and must fit their column as in `INSERT`,
+/// with their types checked before any row is read.
+pub fn update_table(statement: &Statement, catalog: &Catalog, config: &SessionConfig) -> Result<usize, QueryError> {
+    let Statement::Update(update) = statement else {
+        return Err(QueryError::ValidationError {
+            message: format!("Not an UPDATE statement: {}", statement),
+        });
+    };
+    if update.from.is_some() || update.or.is_some() || update.returning.is_some() || update.limit.is_some() {
+        return Err(QueryError::ValidationError {
+            message: format!("Unsupported UPDATE clause in: {}", statement),
+        });
+    }
+    let source = writable_table(catalog, &single_table(&update.table)?)?;
+    let schema = source.schema().clone();
+
+    let mut assignments: Vec<(usize, Box<dyn PhysicalExpr>)> = Vec::with_capacity(update.assignments.len());
+    for assignment in &update.assignments {
+        let AssignmentTarget::ColumnName(ObjectName(parts)) = &assignment.target else {
+            return Err(QueryError::ValidationError {
+                message: format!("Cannot assign to {}", assignment.target),
+            });
+        };
+        let [ObjectNamePart::Identifier(column)] = parts.as_slice() else {
+            return Err(QueryError::ValidationError {
+                message: format!("Cannot assign to {}", assignment.target),
+            });
+        };
+        let field = resolve_column(&schema, column)?;
+        let index = schema.index_of(&field.name).map_err(|message| QueryError::ValidationError { message })?;
+        if assignments.iter().any(|(assigned, _)| *assigned == index) {
+            return Err(QueryError::ValidationError {
+                message: format!("Column {} is assigned more than once", column),
+            });
+        }
+        let expr = sql_expr_to_expression(&assignment.value, &schema, catalog)?;
+        if !is_null_literal(&expr) {
+            let value_type = expr.get_data_type().map_err(|message| QueryError::ValidationError { message })?;
+            check_type(value_type, &schema.fields[index])?;
+        }
+        assignments.push((index, create_physical_expr(&expr, &schema)?));
+    }
+    let predicate = update.selection.as_ref().map(|selection| plan_predicate(selection, &schema, catalog)).transpose()?;
+
+    let mut batches = Vec::new();
+    let mut row_count = 0;
+    let mut updated = 0;
+    for batch in source.scan(config, None, &[]) {
+        let batch = batch?;
+        let matches = matching_rows(predicate.as_deref(), &batch)?;
+        let mut columns: Vec<Vec<ScalarValue>> = batch.columns.iter().map(|column| column.values.clone()).collect();
+        for (index, expr) in &assignments {
+            let values = expr.evaluate(&batch)?.values;
+            for (row, value) in values.into_iter().enumerate().filter(|&(row, _)| matches[row]) {
+                columns[*index][row] = value;
+            }
+        }
+        updated += matches.iter().filter(|&&matched| matched).count();
+        batches.push(fit_batch(&schema, columns, row_count)?);
+        row_count += matches.len();
+    }
+    source.overwrite(batches)?;
+    Ok(updated)
+}
+
+/// Runs `DELETE FROM table [WHERE predicate]` and returns the number of rows
+/// deleted. Without `WHERE` every row is deleted.
+pub fn delete_from(statement: &Statement, catalog: &Catalog, config: &SessionConfig) -> Result<usize, QueryError> {
+    let Statement::Delete(delete) = statement else {
+        return Err(QueryError::ValidationError {
+            message: format!("Not a DELETE statement: {}", statement),
+        });
+    };
+    if !delete.tables.is_empty()
+        || delete.using.is_some()
+        || delete.returning.is_some()
+        || !delete.order_by.is_empty()
+        || delete.limit.is_some()
+    {
+        return Err(QueryError::ValidationError {
+            message: format!("Unsupported DELETE clause in: {}", statement),
+        });
+    }
+    let (FromTable::WithFromKeyword(tables) | FromTable::WithoutKeyword(tables)) = &delete.from;
+    let [table] = tables.as_slice() else {
+        return Err(QueryError::ValidationError {
+            message: "DELETE works on exactly one table".to_string(),
+        });
+    };
+    let source = writable_table(catalog, &single_table(table)?)?;
+    let schema = source.schema().clone();
+    let predicate = delete.selection.as_ref().map(|selection| plan_predicate(selection, &schema, catalog)).transpose()?;
+
+    let mut batches = Vec::new();
+    let mut deleted = 0;
+    for batch in source.scan(config, None, &[]) {
+        let batch = batch?;
+        let matches = matching_rows(predicate.as_deref(), &batch)?;
+        let kept = matches.iter().filter(|&&matched| !matched).count();
+        deleted += matches.len() - kept;
+        if kept == 0 {
+            continue;
+        }
+        let columns = batch
+            .columns
+            .iter()
+            .map(|column| {
+                let values = column.values.iter().zip(&matches).filter(|&(_, &matched)| !matched).map(|(value, _)| value.clone());
+                ColumnVector::new(values.collect())
+            })
+            .collect();
+        batches.push(RecordBatch::new(schema.clone(), columns)?);
+    }
+    source.overwrite(batches)?;
+    Ok(deleted)
+}
+
+/// Looks up a table that `INSERT`, `UPDATE` and `DELETE` can change.
+fn writable_table(catalog: &Catalog, name: &str) -> Result<Rc<dyn DataSource>, QueryError> {
+    let source = catalog.get_source(name)?;
+    if !source.is_writable() {
+        return Err(QueryError::ValidationError {
+            message: format!("Table '{}' is read-only: only in-memory tables can be changed", name),
+        });
+    }
+    Ok(source)
+}
+
+/// The name of the table an `UPDATE` or `DELETE` changes, which must not be joined to others.
+fn single_table(table: &TableWithJoins) -> Result<String, QueryError> {
+    match &table.relation {
+        TableFactor::Table { name, args: None, .. } if table.joins.is_empty() => Ok(table_name(name)),
+        _ => Err(QueryError::ValidationError {
+            message: format!("Only a single table can be changed, not {}", table),
+        }),
+    }
+}
+
+/// Plans a `WHERE` predicate over the table's columns.
+fn plan_predicate(selection: &Expr, schema: &Schema, catalog: &Catalog) -> Result<Box<dyn PhysicalExpr>, QueryError> {
+    let predicate = sql_expr_to_expression(selection, schema, catalog)?;
+    match predicate.get_data_type() {
+        Ok(DataType::Bool) => create_physical_expr(&predicate, schema),
+        Ok(other) => Err(QueryError::ValidationError {
+            message: format!("WHERE must be a boolean expression, not {:?}", other),
+        }),
+        Err(message) => Err(QueryError::ValidationError { message }),
+    }
+}
+
+/// Which rows of `batch` the predicate accepts. A NULL result does not match,
+/// and without a predicate every row does.
+fn matching_rows(predicate: Option<&dyn PhysicalExpr>, batch: &RecordBatch) -> Result<Vec<bool>, QueryError> {
+    match predicate {
+        Some(predicate) => Ok(predicate
+            .evaluate(batch)?
+            .values
+            .iter()
+            .map(|value| matches!(value, ScalarValue::Bool(Some(true))))
+            .collect()),
+        None => Ok(vec![true; batch.columns.first().map_or(0, |column| column.values.len())]),
+    }
+}
+
+/// Checks that values of `value_type` fit `field` as `fit_value` would, so a
+/// wrong type is an error even when no rows are written.
+fn check_type(value_type: DataType, field: &Field) -> Result<(), QueryError> {
+    match (value_type, field.field_type) {
+        (value_type, field_type) if value_type == field_type => Ok(()),
+        (DataType::Int32, DataType::Float64) => Ok(()),
+        (value_type, field_type) => Err(QueryError::TypeMismatch {
+            column_name: field.name.clone(),
+            expected: format!("{:?}", field_type),
+            actual: format!("{:?}", value_type),
+        }),
+    }
+}
+
+/// Whether `column` of `plan` holds nothing but NULL literals. Its type is
+/// only a placeholder then, so it fits a column of any type.
+fn holds_only_null(plan: &LogicalPlan, column: usize) -> bool {
+    match plan {
+        LogicalPlan::Values { rows, .. } => rows.iter().all(|row| row[column].is_null()),
+        LogicalPlan::Projection { columns, .. } => is_null_literal(&columns[column]),
+        _ => false,
+    }
+}
+
+fn is_null_literal(expr: &Expression) -> bool {
+    match expr {
+        Expression::Literal(value) => value.is_null(),
+        Expression::Alias { expr, .. } => is_null_literal(expr),
+        _ => false,
+    }
+}
+
+/// Builds a batch of `schema` from one list of values per column, widening
+/// integers for float columns. `first_row` is the number of rows before these,
+/// so errors can name the 1-based row at fault.
+fn fit_batch(schema: &Schema, columns: Vec<Vec<ScalarValue>>, first_row: usize) -> Result<RecordBatch, QueryError> {
+    let columns = columns
+        .into_iter()
+        .zip(&schema.fields)
+        .map(|(values, field)| {
+            let values = values
+                .into_iter()
+                .enumerate()
+                .map(|(i, value)| fit_value(value, field, first_row + i + 1))
+                .collect::<Result<Vec<ScalarValue>, QueryError>>()?;
+            Ok(ColumnVector::new(values))
+        })
+        .collect::<Result<Vec<ColumnVector>, QueryError>>()?;
+    RecordBatch::new(schema.clone(), columns)
+}
+
+fn fit_value(value: ScalarValue, field: &Field, row: usize) -> Result<ScalarValue, QueryError> {
+    match value {
+        value if value.is_null() && field.is_nullable => Ok(ScalarValue::null(field.field_type)),
+        value if value.is_null() => Err(QueryError::UnexpectedNull { row, column_name: field.name.clone() }),
+        ScalarValue::Int32(Some(v)) if field.field_type == DataType::Float64 => Ok(ScalarValue::Float64(Some(f64::from(v)))),
+        value if value.data_type() == field.field_type => Ok(value),
+        value => Err(QueryError::TypeMismatch {
+            column_name: field.name.clone(),
+            expected: format!("{:?}", field.field_type),
+            actual: format!("{:?}", value.data_type()),
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::errors::QueryError;
+    use crate::logical_plan::plan::Catalog;
+    use crate::test_util::{error_message, float, int, query, rows, text};
+    use crate::ScalarValue;
+
+    fn catalog_with_table() -> Catalog {
+        let mut catalog = Catalog::new();
+        rows(&mut catalog, "CREATE TABLE t (id INT NOT NULL, name VARCHAR, score DOUBLE)");
+        rows(&mut catalog, "INSERT INTO t VALUES (1, 'a', 2)");
+        catalog
+    }
+
+    #[test]
+    fn insert_widens_integers_and_fills_missing_columns_with_null() {
+        let mut catalog = catalog_with_table();
+        rows(&mut catalog, "INSERT INTO t (name, ID) VALUES ('b', 2)");
+
+        assert_eq!(
+            rows(&mut catalog, "SELECT * FROM t"),
+            vec![vec![int(1), text("a"), float(2.0)], vec![int(2), text("b"), ScalarValue::Float64(None)]],
+        );
+    }
+
+    #[test]
+    fn insert_rejects_wrong_types_and_nulls_without_inserting_anything() {
+        let mut catalog = catalog_with_table();
+
+        let mismatch = query(&mut catalog, "INSERT INTO t VALUES (2, 'b', 'x')");
+        assert!(matches!(mismatch, Err(QueryError::TypeMismatch { column_name, .. }) if column_name == "score"));
+        let null = query(&mut catalog, "INSERT INTO t VALUES (3, 'c', 1.5), (NULL, 'd', 1)");
+        assert!(matches!(null, Err(QueryError::UnexpectedNull { row: 2, column_name }) if column_name == "id"));
+        assert!(matches!(query(&mut catalog, "INSERT INTO t (name) VALUES ('z')"), Err(QueryError::UnexpectedNull { .. })));
+
+        assert_eq!(rows(&mut catalog, "SELECT id FROM t"), vec![vec![int(1)]]);
+    }
+
+    #[test]
+    fn types_are_checked_even_when_no_rows_are_written() {
+        let mut catalog = catalog_with_table();
+        rows(&mut catalog, "CREATE TABLE empty_source (name VARCHAR)");
+
+        let insert = query(&mut catalog, "INSERT INTO t (score) SELECT name FROM empty_source");
+        assert!(matches!(insert, Err(QueryError::TypeMismatch { column_name, .. }) if column_name == "score"));
+        let update = query(&mut catalog, "UPDATE t SET name = 5 WHERE false");
+        assert!(matches!(update, Err(QueryError::TypeMismatch { column_name, .. }) if column_name == "name"));
+        // NULL fits a column of any type, and integers fit float columns.
+        rows(&mut catalog, "INSERT INTO t VALUES (2, NULL, NULL)");
+        rows(&mut catalog, "UPDATE t SET name = NULL, score = id WHERE false");
+
+        assert_eq!(rows(&mut catalog, "SELECT id FROM t"), vec![vec![int(1)], vec![int(2)]]);
+    }
+
+    #[test]
+    fn insert_checks_its_column_list() {
+        let mut catalog = catalog_with_table();
+
+        assert_eq!(error_message(&mut catalog, "INSERT INTO t VALUES (1, 'a')"), "INSERT into 3 columns of 't' got 2 columns from its query");
+        assert_eq!(error_message(&mut catalog, "INSERT INTO t (id, id) VALUES (1, 2)"), "Column id is listed more than once");
+    }
+
+    #[test]
+    fn update_and_delete_change_matching_rows() {
+        let mut catalog = catalog_with_table();
+        rows(&mut catalog, "INSERT INTO t VALUES (2, 'b', 5), (3, 'c', NULL)");
+
+        rows(&mut catalog, "UPDATE t SET score = score * 2 WHERE id < 3");
+        rows(&mut catalog, "DELETE FROM t WHERE name = 'b'");
+        assert_eq!(
+            rows(&mut catalog, "SELECT id, score FROM t"),
+            vec![vec![int(1), float(4.0)], vec![int(3), ScalarValue::Float64(None)]],
+        );
+    }
+
+    #[test]
+    fn update_checks_types_and_nullability() {
+        let mut catalog = catalog_with_table();
+
+        assert!(matches!(query(&mut catalog, "UPDATE t SET id = NULL"), Err(QueryError::UnexpectedNull { .. })));
+        assert!(matches!(query(&mut catalog, "UPDATE t SET name = 5"), Err(QueryError::TypeMismatch { .. })));
+        assert_eq!(error_message(&mut catalog, "DELETE FROM t WHERE name"), "WHERE must be a boolean expression, not String");
+        assert_eq!(rows(&mut catalog, "SELECT * FROM t"), vec![vec![int(1), text("a"), float(2.0)]]);
+    }
+
+    #[test]
+    fn files_are_read_only() {
+        let mut catalog = Catalog::new();
+        rows(&mut catalog, "CREATE EXTERNAL TABLE students STORED AS CSV LOCATION 'test/students.csv'");
+
+        assert_eq!(
+            error_message(&mut catalog, "DELETE FROM students"),
+            "Table 'students' is read-only: only in-memory tables can be changed",
+        );
+    }
+}
@@ -0,0 +1,343 @@
+use sqlparser::ast::{Expr, Ident, ObjectName, ObjectType, Set, ShowStatementIn, ShowStatementInParentType, ShowStatementOptions, Statement, Use, Value};
+
+use crate::logical_plan::names::quote_if_needed;
+use crate::logical_plan::plan::{Catalog, LogicalPlan};
+use crate::physical_plan::{plan::PhysicalPlan, planner::create_physical_plan};
+use crate::{DataType, Field, ScalarValue, Schema, SessionConfig, errors::QueryError};
+
+use super::catalog_file::save_catalog_file;
+use super::ddl::{create_schema, create_table, create_view, drop_objects};
+use super::dml::{delete_from, insert_into, update_table};
+use super::planner::{create_external_table, table_name};
+use super::write::{copy_to, create_query_plan, create_table_as};
+
+/// What running a statement produced.
+pub enum StatementResult {
+    /// Rows to pull from the plan, for queries, `SHOW TABLES` and `DESCRIBE`.
+    Rows(Box<dyn PhysicalPlan>),
+    /// A statement that changed rows or the catalog, with a line saying what it did.
+    Done(String),
+}
+
+/// Runs any statement the engine supports. Queries are only planned: their
+/// rows are pulled from the plan in the result. Statements that create or drop
+/// schemas, tables and views save the catalog to its catalog file, if it has
+/// one. `USE` and `SET search_path` only last as long as the catalog.
+pub fn execute_statement(statement: &Statement, catalog: &mut Catalog, config: &SessionConfig) -> Result<StatementResult, QueryError> {
+    let message = match statement {
+        Statement::Query(query) => return Ok(StatementResult::Rows(create_query_plan(query, catalog, config)?)),
+        Statement::ShowTables { show_options, .. } => {
+            let plan = show_tables(show_options, catalog)?;
+            return Ok(StatementResult::Rows(create_physical_plan(&plan, catalog, config)?));
+        },
+        Statement::ShowSchemas { show_options, .. } => {
+            let plan = show_schemas(show_options, catalog)?;
+            return Ok(StatementResult::Rows(create_physical_plan(&plan, catalog, config)?));
+        },
+        Statement::ExplainTable { table_name, .. } => {
+            let plan = describe_table(table_name, catalog)?;
+            return Ok(StatementResult::Rows(create_physical_plan(&plan, catalog, config)?));
+        },
+        Statement::CreateTable(create) if create.query.is_some() => {
+            format!("Table created with {}", counted(create_table_as(statement, catalog, config)?, "row"))
+        },
+        Statement::CreateTable(create) if create.external => {
+            create_external_table(statement, catalog)?;
+            "Table created".to_string()
+        },
+        Statement::CreateTable(_) => {
+            create_table(statement, catalog)?;
+            "Table created".to_string()
+        },
+        Statement::CreateView(_) => {
+            create_view(statement, catalog)?;
+            "View created".to_string()
+        },
+        Statement::CreateSchema { .. } => {
+            create_schema(statement, catalog)?;
+            "Schema created".to_string()
+        },
+        Statement::Drop { object_type, .. } => {
+            let noun = match object_type {
+                ObjectType::View => "view",
+                ObjectType::Schema => "schema",
+                _ => "table",
+            };
+            format!("{} dropped", counted(drop_objects(statement, catalog)?, noun))
+        },
+        Statement::Use(Use::Object(name) | Use::Schema(name)) => use_schema(name, catalog)?,
+        Statement::Set(Set::SingleAssignment { variable, values, .. }) if variable.to_string().eq_ignore_ascii_case("search_path") => {
+            set_search_path(values, catalog)?
+        },
+        Statement::Copy { .. } => format!("{} copied", counted(copy_to(statement, catalog, config)?, "row")),
+        Statement::Insert(_) => format!("{} inserted", counted(insert_into(statement, catalog, config)?, "row")),
+        Statement::Update(_) => format!("{} updated", counted(update_table(statement, catalog, config)?, "row")),
+        Statement::Delete(_) => format!("{} deleted", counted(delete_from(statement, catalog, config)?, "row")),
+        _ => return Err(QueryError::ValidationError {
+            message: format!("Unsupported statement: {}", statement),
+        }),
+    };
+    if matches!(statement, Statement::CreateTable(_) | Statement::CreateView(_) | Statement::CreateSchema { .. } | Statement::Drop { .. }) {
+        save_catalog_file(catalog)?;
+    }
+    Ok(StatementResult::Done(message))
+}
+
+/// `count` followed by `noun`, made plural unless `count` is 1.
+fn counted(count: usize, noun: &str) -> String {
+    match count {
+        1 => format!("1 {}", noun),
+        _ => format!("{} {}s", count, noun),
+    }
+}
+
+/// Runs `USE [database.]schema`, which makes the schema the current one: the
+/// first on the search path, where new tables and views go. The rest of the
+/// search path is kept unless the database changes.
+fn use_schema(name: &ObjectName, catalog: &mut Catalog) -> Result<String, QueryError> {
+    let (database, schema) = catalog.resolve_schema(&table_name(name))?;
+    if !catalog.has_schema(&database, &schema) {
+        return Err(QueryError::ValidationError {
+            message: format!("Schema '{}' does not exist", name),
+        });
+    }
+    let mut search_path = vec![schema.clone()];
+    if database == catalog.current_database() {
+        search_path.extend(catalog.search_path().iter().skip(1).filter(|other| **other != schema).cloned());
+    }
+    let message = format!("Using schema {}.{}", quote_if_needed(&database), quote_if_needed(&schema));
+    catalog.set_search_path(database, search_path);
+    Ok(message)
+}
+
+/// Runs `SET search_path = schema, ...`, which sets the schemas of the current
+/// database that a table name alone is looked for in. The first becomes the
+/// current schema.
+fn set_search_path(values: &[Expr], catalog: &mut Catalog) -> Result<String, QueryError> {
+    let mut search_path = Vec::with_capacity(values.len());
+    for value in values {
+        let name = match value {
+            Expr::Identifier(ident) => ident.to_string(),
+            Expr::Value(value) if let Value::SingleQuotedString(text) = &value.value => Ident::with_quote('"', text).to_string(),
+            other => return Err(QueryError::ValidationError {
+                message: format!("search_path takes schema names, not {}", other),
+            }),
+        };
+        let (database, schema) = catalog.resolve_schema(&name)?;
+        if !catalog.has_schema(&database, &schema) {
+            return Err(QueryError::ValidationError {
+                message: format!("Schema '{}' does not exist", value),
+            });
+        }
+        if !search_path.contains(&schema) {
+            search_path.push(schema);
+        }
+    }
+    let message = format!("Search path is {}", search_path.iter().map(|schema| quote_if_needed(schema)).collect::<Vec<String>>().join(", "));
+    catalog.set_search_path(catalog.current_database().to_string(), search_path);
+    Ok(message)
+}
+
+/// Lists the tables and views in the schemas of the current database, or in
+/// the schema given with `FROM` or `IN`, by schema and name.
+fn show_tables(options: &ShowStatementOptions, catalog: &Catalog) -> Result<LogicalPlan, QueryError> {
+    if options.starts_with.is_some() || options.limit.is_some() || options.limit_from.is_some() || options.filter_position.is_some() {
+        return Err(QueryError::ValidationError {
+            message: "SHOW TABLES only takes FROM or IN and a schema".to_string(),
+        });
+    }
+    let (database, schema) = match &options.show_in {
+        None => (catalog.current_database().to_string(), None),
+        Some(ShowStatementIn { parent_type: None | Some(ShowStatementInParentType::Schema), parent_name: Some(name), .. }) => {
+            let (database, schema) = catalog.resolve_schema(&table_name(name))?;
+            if !catalog.has_schema(&database, &schema) {
+                return Err(QueryError::ValidationError {
+                    message: format!("Schema '{}' does not exist", name),
+                });
+            }
+            (database, Some(schema))
+        },
+        Some(show_in) => return Err(QueryError::ValidationError {
+            message: format!("Unsupported SHOW TABLES clause: {}", show_in),
+        }),
+    };
+    let tables = catalog.table_names().into_iter().map(|name| (name, "table"));
+    let views = catalog.view_names().into_iter().map(|name| (name, "view"));
+    let mut relations: Vec<_> = tables
+        .chain(views)
+        .filter(|(name, _)| name.database == database && schema.as_ref().is_none_or(|schema| name.schema == *schema))
+        .collect();
+    relations.sort_unstable();
+
+    let rows = relations
+        .into_iter()
+        .map(|(name, kind)| {
+            vec![
+                ScalarValue::String(Some(name.schema.clone())),
+                ScalarValue::String(Some(name.table.clone())),
+                ScalarValue::String(Some(kind.to_string())),
+            ]
+        })
+        .collect();
+    Ok(LogicalPlan::Values { schema: Schema::new(vec![text_field("schema"), text_field("name"), text_field("type")]), rows })
+}
+
+/// Lists the schemas of every database.
+fn show_schemas(options: &ShowStatementOptions, catalog: &Catalog) -> Result<LogicalPlan, QueryError> {
+    if options.show_in.is_some() || options.starts_with.is_some() || options.limit.is_some() || options.filter_position.is_some() {
+        return Err(QueryError::ValidationError {
+            message: "SHOW SCHEMAS does not take any options".to_string(),
+        });
+    }
+    let rows = catalog
+        .schema_names()
+        .into_iter()
+        .map(|(database, schema)| vec![ScalarValue::String(Some(database.to_string())), ScalarValue::String(Some(schema.to_string()))])
+        .collect();
+    Ok(LogicalPlan::Values { schema: Schema::new(vec![text_field("database"), text_field("schema")]), rows })
+}
+
+/// Lists the columns of a table or view with their types and nullability.
+fn describe_table(name: &ObjectName, catalog: &Catalog) -> Result<LogicalPlan, QueryError> {
+    let name = table_name(name);
+    let schema = match catalog.get_view(&name) {
+        Some(plan) => plan.schema()?,
+        None => catalog.get_source(&name)?.schema().clone(),
+    };
+    let rows = schema
+        .fields
+        .iter()
+        .map(|field| {
+            vec![
+                ScalarValue::String(Some(field.name.clone())),
+                ScalarValue::String(Some(format!("{:?}", field.field_type))),
+                ScalarValue::Bool(Some(field.is_nullable)),
+            ]
+        })
+        .collect();
+    let fields = vec![
+        text_field("name"),
+        text_field("type"),
+        Field { name: "nullable".to_string(), field_type: DataType::Bool, is_nullable: false },
+    ];
+    Ok(LogicalPlan::Values { schema: Schema::new(fields), rows })
+}
+
+fn text_field(name: &str) -> Field {
+    Field { name: name.to_string(), field_type: DataType::String, is_nullable: false }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::logical_plan::plan::Catalog;
+    use crate::test_util::{error_message, int, rows, text};
+    use crate::ScalarValue;
+
+    #[test]
+    fn describe_keeps_the_nullability_of_view_columns() {
+        let mut catalog = Catalog::new();
+        rows(&mut catalog, "CREATE TABLE t (id INT NOT NULL, name VARCHAR)");
+        rows(&mut catalog, "CREATE VIEW v AS SELECT id, id AS k, name, id + 1 AS p FROM t");
+
+        let described: Vec<(ScalarValue, ScalarValue)> = rows(&mut catalog, "DESCRIBE v").into_iter().map(|row| (row[0].clone(), row[2].clone())).collect();
+        assert_eq!(
+            described,
+            vec![
+                (text("id"), ScalarValue::Bool(Some(false))),
+                (text("k"), ScalarValue::Bool(Some(false))),
+                (text("name"), ScalarValue::Bool(Some(true))),
+                (text("p"), ScalarValue::Bool(Some(true))),
+            ],
+        );
+    }
+
+    #[test]
+    fn tables_and_views_are_listed_and_dropped() {
+        let mut catalog = Catalog::new();
+        rows(&mut catalog, "CREATE TABLE t (a INT); INSERT INTO t VALUES (1), (2)");
+        rows(&mut catalog, "CREATE VIEW big AS SELECT a FROM t WHERE a > 1");
+
+        assert_eq!(
+            rows(&mut catalog, "SHOW TABLES"),
+            vec![vec![text("public"), text("big"), text("view")], vec![text("public"), text("t"), text("table")]],
+        );
+        assert_eq!(rows(&mut catalog, "SELECT * FROM big"), vec![vec![int(2)]]);
+        assert_eq!(error_message(&mut catalog, "DROP TABLE t"), "Cannot drop 'main.public.t' because view 'main.public.big' reads it; drop the view first");
+        assert_eq!(error_message(&mut catalog, "DROP VIEW t"), "View 't' does not exist");
+
+        rows(&mut catalog, "DROP VIEW big; DROP TABLE t; DROP TABLE IF EXISTS t");
+        assert!(rows(&mut catalog, "SHOW TABLES").is_empty());
+    }
+
+    #[test]
+    fn tables_and_views_read_by_a_view_are_not_replaced() {
+        let mut catalog = Catalog::new();
+        rows(&mut catalog, "CREATE TABLE t AS SELECT * FROM (VALUES (1)) AS s (x); CREATE VIEW v AS SELECT x FROM t; CREATE VIEW w AS SELECT x FROM v");
+
+        let message = "Cannot replace 'main.public.t' because view 'main.public.v' reads it; drop the view first";
+        assert_eq!(error_message(&mut catalog, "CREATE OR REPLACE TABLE t AS SELECT * FROM (VALUES ('a')) AS s (x)"), message);
+        assert_eq!(error_message(&mut catalog, "CREATE OR REPLACE TABLE t (x VARCHAR)"), message);
+        assert_eq!(
+            error_message(&mut catalog, "CREATE OR REPLACE VIEW v AS SELECT 'a' AS x FROM t"),
+            "Cannot replace 'main.public.v' because view 'main.public.w' reads it; drop the view first"
+        );
+        assert_eq!(rows(&mut catalog, "SELECT x + 1 FROM w"), vec![vec![int(2)]]);
+
+        rows(&mut catalog, "CREATE OR REPLACE VIEW w AS SELECT x * 2 AS x FROM v");
+        assert_eq!(rows(&mut catalog, "SELECT x FROM w"), vec![vec![int(2)]]);
+    }
+
+    #[test]
+    fn create_checks_names_and_columns() {
+        let mut catalog = Catalog::new();
+        rows(&mut catalog, "CREATE TABLE t (a INT)");
+
+        assert_eq!(error_message(&mut catalog, "CREATE TABLE t (b INT)"), "Table 't' already exists");
+        assert_eq!(error_message(&mut catalog, "CREATE TABLE u (b INT, b INT)"), "Column b is declared more than once");
+        assert_eq!(error_message(&mut catalog, "CREATE VIEW t AS SELECT a FROM t"), "'t' is a table, not a view");
+        rows(&mut catalog, "CREATE TABLE IF NOT EXISTS t (b INT)");
+        assert_eq!(rows(&mut catalog, "DESCRIBE t")[0][0], text("a"));
+    }
+
+    #[test]
+    fn use_and_search_path_pick_where_names_resolve() {
+        let mut catalog = Catalog::new();
+        rows(&mut catalog, "CREATE TABLE t (a INT); INSERT INTO t VALUES (1)");
+        rows(&mut catalog, "CREATE SCHEMA Staging; USE staging; CREATE TABLE t (a INT); INSERT INTO t VALUES (2)");
+        assert_eq!(catalog.current_schema(), "Staging");
+        assert_eq!(rows(&mut catalog, "SELECT a FROM T"), vec![vec![int(2)]]);
+        assert_eq!(rows(&mut catalog, "SELECT a FROM Public.t"), vec![vec![int(1)]]);
+
+        rows(&mut catalog, "SET search_path = public, STAGING");
+        assert_eq!(catalog.search_path(), ["public", "Staging"]);
+        assert_eq!(rows(&mut catalog, "SELECT a FROM t"), vec![vec![int(1)]]);
+        rows(&mut catalog, "CREATE VIEW v AS SELECT a FROM t");
+
+        // A view keeps resolving names with the search path it was created with.
+        rows(&mut catalog, "USE staging");
+        assert_eq!(rows(&mut catalog, "SELECT a FROM public.v"), vec![vec![int(1)]]);
+        assert_eq!(error_message(&mut catalog, "USE ghost"), "Schema 'ghost' does not exist");
+        assert_eq!(error_message(&mut catalog, "SET search_path = ghost"), "Schema 'ghost' does not exist");
+    }
+
+    #[test]
+    fn quoted_names_tell_apart_what_differs_only_in_case() {
+        let mut catalog = Catalog::new();
+        rows(&mut catalog, "CREATE TABLE Items (a INT); INSERT INTO Items VALUES (1)");
+        rows(&mut catalog, "CREATE TABLE \"items\" (a INT); INSERT INTO \"items\" VALUES (2)");
+        assert_eq!(rows(&mut catalog, "SELECT a FROM items"), vec![vec![int(2)]]);
+        assert_eq!(rows(&mut catalog, "SELECT a FROM \"Items\""), vec![vec![int(1)]]);
+        assert_eq!(error_message(&mut catalog, "SELECT a FROM ITEMS"), "'ITEMS' is ambiguous: it could be any of Items, items; quote it to pick one");
+        assert_eq!(error_message(&mut catalog, "SELECT a FROM \"ITEMS\""), "Table '\"ITEMS\"' not found in catalog");
+    }
+
+    #[test]
+    fn column_names_resolve_in_any_case_unless_ambiguous() {
+        let mut catalog = Catalog::new();
+        rows(&mut catalog, "CREATE TABLE t (Id INT, \"id\" INT, Name VARCHAR); INSERT INTO t VALUES (1, 2, 'a')");
+        assert_eq!(rows(&mut catalog, "SELECT name, NAME, \"Name\" FROM t"), vec![vec![text("a"), text("a"), text("a")]]);
+        assert_eq!(rows(&mut catalog, "SELECT Id, id FROM t"), vec![vec![int(1), int(2)]]);
+        assert_eq!(error_message(&mut catalog, "SELECT ID FROM t"), "'ID' is ambiguous: it could be any of Id, id; quote it to pick one");
+        assert_eq!(error_message(&mut catalog, "SELECT \"NAME\" FROM t"), "column NAME does not exist in the schema");
+    }
+}
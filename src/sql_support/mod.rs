@@ -1,2 +1,7 @@
+pub mod catalog_file;
+pub mod ddl;
+pub mod dml;
+pub mod executor;
 pub mod sql;
-pub mod planner;
\ No newline at end of file
+pub mod planner;
+pub mod write;
\ No newline at end of file
@@ -1,16 +1,28 @@
-use sqlparser::ast::{Statement, SetExpr, SelectItem, Expr, BinaryOperator as SqlBinaryOp, Value};
-use crate::logical_plan::plan::{LogicalPlan, Expression, Operator, Catalog};
-use crate::{ScalarValue, Schema, errors::QueryError};
+use sqlparser::ast::{Statement, SetExpr, SelectItem, Expr, BinaryOperator as SqlBinaryOp, Value, Function, FunctionArg, FunctionArgExpr, FunctionArguments, FunctionArgumentClause, OrderByExpr, DuplicateTreatment, CreateTableOptions, FileFormat, SqlOption, ColumnDef, ColumnOption, DataType as SqlDataType, Distinct, GroupByExpr, WindowType, WindowFrame as SqlWindowFrame, WindowFrameBound as SqlWindowFrameBound, WindowFrameUnits as SqlWindowFrameUnits};
+use crate::logical_plan::plan::{LogicalPlan, Expression, Operator, Catalog, AggregateFunction, ScalarFunction, SortExpr, WindowFrame, WindowFrameBound, WindowFrameUnits, WindowFunction};
+use crate::datasource::csv::{CsvDataSource, CsvReadOptions};
+use crate::datasource::json::{JsonDataSource, JsonReadOptions};
+use crate::datasource::listing::{FILE_NAME_COLUMN, ListingTable, is_listing_location};
+use crate::datasource::parquet::ParquetDataSource;
+use crate::datasource::rejected::{RejectedRowsSink, RejectedRowsTable};
+use crate::logical_plan::definition::{Definition, ExternalFormat, ExternalTable};
+use crate::logical_plan::names::{NamePart, find_name};
+use crate::logical_plan::udaf::AggregateUdf;
+use crate::{DataSource, DataType, Field, ScalarValue, Schema, errors::QueryError};
+use std::rc::Rc;
 
 trait ToExpression {
-    fn to_expression(&self, schema: &Schema) -> Result<Expression, QueryError>;
+    fn to_expression(&self, schema: &Schema, catalog: &Catalog) -> Result<Expression, QueryError>;
 }
 
 impl ToExpression for SelectItem {
-    fn to_expression(&self, schema: &Schema) -> Result<Expression, QueryError> {
+    fn to_expression(&self, schema: &Schema, catalog: &Catalog) -> Result<Expression, QueryError> {
         match self {
-            SelectItem::UnnamedExpr(expr) => expr.to_expression(schema),
-            SelectItem::ExprWithAlias { expr, .. } => expr.to_expression(schema),
+            SelectItem::UnnamedExpr(expr) => expr.to_expression(schema, catalog),
+            SelectItem::ExprWithAlias { expr, alias } => Ok(Expression::Alias {
+                expr: Box::new(expr.to_expression(schema, catalog)?),
+                name: alias.value.clone(),
+            }),
             SelectItem::Wildcard(_) => {
                 Err(QueryError::ValidationError {
                     message: "SELECT * is not yet supported in projection".to_string(),
@@ -24,14 +36,12 @@ impl ToExpression for SelectItem {
 }
 
 impl ToExpression for Expr {
-    fn to_expression(&self, schema: &Schema) -> Result<Expression, QueryError> {
+    fn to_expression(&self, schema: &Schema, catalog: &Catalog) -> Result<Expression, QueryError> {
         match self {
             Expr::Identifier(ident) => {
-                let name = ident.value.clone();
-                let field = schema.column_exists(&name)
-                    .map_err(|e| QueryError::ValidationError { message: e })?;
+                let field = resolve_column(schema, ident)?;
                 Ok(Expression::Column {
-                    name,
+                    name: field.name.clone(),
                     data_type: field.field_type,
                 })
             }
@@ -40,8 +50,8 @@ impl ToExpression for Expr {
                 Ok(Expression::Literal(scalar))
             }
             Expr::BinaryOp { left, op, right } => {
-                let left_expr = left.to_expression(schema)?;
-                let right_expr = right.to_expression(schema)?;
+                let left_expr = left.to_expression(schema, catalog)?;
+                let right_expr = right.to_expression(schema, catalog)?;
                 let operator = sql_binary_op_to_operator(op)?;
                 Ok(Expression::Binary {
                     left: Box::new(left_expr),
@@ -50,13 +60,14 @@ impl ToExpression for Expr {
                 })
             }
             Expr::UnaryOp { op, expr } => {
-                let operand = expr.to_expression(schema)?;
+                let operand = expr.to_expression(schema, catalog)?;
                 let operator = sql_unary_op_to_operator(op)?;
                 Ok(Expression::Unary {
                     operand: Box::new(operand),
                     operator,
                 })
             }
+            Expr::Function(function) => function.to_expression(schema, catalog),
             _ => Err(QueryError::ValidationError {
                 message: format!("Unsupported expression type: {:?}", self),
             }),
@@ -64,6 +75,322 @@ impl ToExpression for Expr {
     }
 }
 
+impl ToExpression for Function {
+    fn to_expression(&self, schema: &Schema, catalog: &Catalog) -> Result<Expression, QueryError> {
+        let name = self.name.to_string();
+        if let Some(over) = &self.over {
+            return window_function_to_expression(self, over, schema, catalog);
+        }
+        if let Some(func) = AggregateFunction::from_name(&name) {
+            return aggregate_function_to_expression(self, func, schema, catalog);
+        }
+        if let Some(udaf) = catalog.get_udaf(&name) {
+            return udaf_to_expression(self, udaf, schema, catalog);
+        }
+        let func = ScalarFunction::from_name(&name).ok_or_else(|| QueryError::ValidationError {
+            message: format!("Unsupported function: {}", name),
+        })?;
+
+        let args = function_arg_exprs(self)?
+            .into_iter()
+            .map(|arg| arg.to_expression(schema, catalog))
+            .collect::<Result<Vec<Expression>, QueryError>>()?;
+
+        let expr = Expression::ScalarFunction { func, args };
+        expr.get_data_type()
+            .map_err(|message| QueryError::ValidationError { message })?;
+        Ok(Expression::Alias {
+            expr: Box::new(expr),
+            name: self.to_string(),
+        })
+    }
+}
+
+fn aggregate_function_to_expression(
+    function: &Function,
+    func: AggregateFunction,
+    schema: &Schema,
+    catalog: &Catalog,
+) -> Result<Expression, QueryError> {
+    let distinct = matches!(
+        &function.args,
+        FunctionArguments::List(list) if list.duplicate_treatment == Some(DuplicateTreatment::Distinct)
+    );
+    if distinct && func != AggregateFunction::Count {
+        return Err(QueryError::ValidationError {
+            message: format!("DISTINCT is only supported for COUNT: {}", function),
+        });
+    }
+
+    // COUNT(*) counts rows, which is the same as counting a non-null constant.
+    let is_count_star = matches!(
+        &function.args,
+        FunctionArguments::List(list)
+            if matches!(list.args.as_slice(), [FunctionArg::Unnamed(FunctionArgExpr::Wildcard)])
+    );
+    let mut args = if func == AggregateFunction::Count && is_count_star && !distinct {
+        vec![Expression::Literal(ScalarValue::Int32(Some(1)))]
+    } else {
+        function_arg_exprs(function)?
+            .into_iter()
+            .map(|arg| arg.to_expression(schema, catalog))
+            .collect::<Result<Vec<Expression>, QueryError>>()?
+    };
+
+    let mut order_by = sort_exprs(&function.within_group, schema, catalog)?;
+    if !order_by.is_empty() && !func.is_ordered_set() {
+        return Err(QueryError::ValidationError {
+            message: format!("WITHIN GROUP is only supported for ordered-set aggregates: {}", function),
+        });
+    }
+    if let FunctionArguments::List(list) = &function.args {
+        for clause in &list.clauses {
+            match clause {
+                FunctionArgumentClause::OrderBy(exprs) => order_by.extend(sort_exprs(exprs, schema, catalog)?),
+                // MySQL-style `STRING_AGG(x SEPARATOR ';')` supplies the separator as a clause.
+                FunctionArgumentClause::Separator(value) if func == AggregateFunction::StringAgg => {
+                    args.push(Expression::Literal(sql_value_to_scalar(value)?));
+                }
+                _ => return Err(QueryError::ValidationError {
+                    message: format!("Unsupported clause in aggregate call: {}", clause),
+                }),
+            }
+        }
+    }
+
+    if args
+        .iter()
+        .chain(order_by.iter().map(|sort| &sort.expr))
+        .any(|arg| arg.contains_aggregate_function())
+    {
+        return Err(QueryError::ValidationError {
+            message: format!("Aggregate function calls cannot be nested: {}", function),
+        });
+    }
+
+    let expr = Expression::AggregateFunction { func, args, distinct, order_by };
+    expr.get_data_type()
+        .map_err(|message| QueryError::ValidationError { message })?;
+
+    Ok(Expression::Alias {
+        expr: Box::new(expr),
+        name: function.to_string(),
+    })
+}
+
+fn udaf_to_expression(
+    function: &Function,
+    udaf: Rc<dyn AggregateUdf>,
+    schema: &Schema,
+    catalog: &Catalog,
+) -> Result<Expression, QueryError> {
+    if let FunctionArguments::List(list) = &function.args
+        && (list.duplicate_treatment.is_some() || !list.clauses.is_empty())
+    {
+        return Err(QueryError::ValidationError {
+            message: format!("User-defined aggregates only take plain arguments: {}", function),
+        });
+    }
+    if !function.within_group.is_empty() {
+        return Err(QueryError::ValidationError {
+            message: format!("WITHIN GROUP is only supported for ordered-set aggregates: {}", function),
+        });
+    }
+
+    let args = function_arg_exprs(function)?
+        .into_iter()
+        .map(|arg| arg.to_expression(schema, catalog))
+        .collect::<Result<Vec<Expression>, QueryError>>()?;
+    if args.iter().any(|arg| arg.contains_aggregate_function()) {
+        return Err(QueryError::ValidationError {
+            message: format!("Aggregate function calls cannot be nested: {}", function),
+        });
+    }
+
+    let expr = Expression::AggregateUdf { udaf, args };
+    expr.get_data_type()
+        .map_err(|message| QueryError::ValidationError { message })?;
+
+    Ok(Expression::Alias {
+        expr: Box::new(expr),
+        name: function.to_string(),
+    })
+}
+
+fn window_function_to_expression(
+    function: &Function,
+    over: &WindowType,
+    schema: &Schema,
+    catalog: &Catalog,
+) -> Result<Expression, QueryError> {
+    let name = function.name.to_string();
+    let func = WindowFunction::from_name(&name).ok_or_else(|| QueryError::ValidationError {
+        message: format!("Unsupported window function: {}", name),
+    })?;
+    let spec = match over {
+        WindowType::WindowSpec(spec) => spec,
+        WindowType::NamedWindow(window_name) => return Err(QueryError::ValidationError {
+            message: format!("Named windows are not supported: {}", window_name),
+        }),
+    };
+
+    let args = function_arg_exprs(function)?
+        .into_iter()
+        .map(|arg| arg.to_expression(schema, catalog))
+        .collect::<Result<Vec<Expression>, QueryError>>()?;
+    let partition_by = spec
+        .partition_by
+        .iter()
+        .map(|expr| expr.to_expression(schema, catalog))
+        .collect::<Result<Vec<Expression>, QueryError>>()?;
+    let order_by = sort_exprs(&spec.order_by, schema, catalog)?;
+    let frame = match &spec.window_frame {
+        Some(frame) => sql_window_frame_to_frame(frame)?,
+        None => WindowFrame::default_for(!order_by.is_empty()),
+    };
+
+    let expr = Expression::WindowFunction { func, args, partition_by, order_by, frame };
+    expr.get_data_type()
+        .map_err(|message| QueryError::ValidationError { message })?;
+
+    // The call's SQL text names its output column until an alias replaces it.
+    Ok(Expression::Alias {
+        expr: Box::new(expr),
+        name: function.to_string(),
+    })
+}
+
+/// Converts ORDER BY keys. NULLs sort last ascending and first descending, as in PostgreSQL.
+fn sort_exprs(order_by: &[OrderByExpr], schema: &Schema, catalog: &Catalog) -> Result<Vec<SortExpr>, QueryError> {
+    order_by
+        .iter()
+        .map(|order| {
+            let asc = order.options.asc.unwrap_or(true);
+            Ok(SortExpr {
+                expr: order.expr.to_expression(schema, catalog)?,
+                asc,
+                nulls_first: order.options.nulls_first.unwrap_or(!asc),
+            })
+        })
+        .collect()
+}
+
+fn sql_window_frame_to_frame(frame: &SqlWindowFrame) -> Result<WindowFrame, QueryError> {
+    let units = match frame.units {
+        SqlWindowFrameUnits::Rows => WindowFrameUnits::Rows,
+        SqlWindowFrameUnits::Range => WindowFrameUnits::Range,
+        SqlWindowFrameUnits::Groups => return Err(QueryError::ValidationError {
+            message: "GROUPS window frames are not supported".to_string(),
+        }),
+    };
+    let start = sql_frame_bound_to_bound(&frame.start_bound)?;
+    let end = match &frame.end_bound {
+        Some(bound) => sql_frame_bound_to_bound(bound)?,
+        None => WindowFrameBound::CurrentRow,
+    };
+    Ok(WindowFrame { units, start, end })
+}
+
+fn sql_frame_bound_to_bound(bound: &SqlWindowFrameBound) -> Result<WindowFrameBound, QueryError> {
+    match bound {
+        SqlWindowFrameBound::CurrentRow => Ok(WindowFrameBound::CurrentRow),
+        SqlWindowFrameBound::Preceding(None) => Ok(WindowFrameBound::UnboundedPreceding),
+        SqlWindowFrameBound::Following(None) => Ok(WindowFrameBound::UnboundedFollowing),
+        SqlWindowFrameBound::Preceding(Some(offset)) => Ok(WindowFrameBound::Preceding(frame_offset(offset)?)),
+        SqlWindowFrameBound::Following(Some(offset)) => Ok(WindowFrameBound::Following(frame_offset(offset)?)),
+    }
+}
+
+fn frame_offset(expr: &Expr) -> Result<u64, QueryError> {
+    if let Expr::Value(value_with_span) = expr
+        && let Value::Number(n, _) = &value_with_span.value
+        && let Ok(offset) = n.parse::<u64>()
+    {
+        return Ok(offset);
+    }
+    Err(QueryError::ValidationError {
+        message: format!("Window frame offset must be a non-negative integer: {}", expr),
+    })
+}
+
+/// Replaces each aliased call matched by `is_target` with a reference to the
+/// column a Window or Aggregate node computes for it, collecting the calls into `extracted`.
+fn extract_exprs(
+    expr: Expression,
+    is_target: fn(&Expression) -> bool,
+    extracted: &mut Vec<Expression>,
+) -> Result<Expression, QueryError> {
+    match expr {
+        Expression::Alias { expr, name } if is_target(&expr) => {
+            let data_type = expr
+                .get_data_type()
+                .map_err(|message| QueryError::ValidationError { message })?;
+            if !extracted.iter().any(|existing| existing.output_name() == name) {
+                extracted.push(Expression::Alias { expr, name: name.clone() });
+            }
+            Ok(Expression::Column { name, data_type })
+        }
+        Expression::Alias { expr, name } => Ok(Expression::Alias {
+            expr: Box::new(extract_exprs(*expr, is_target, extracted)?),
+            name,
+        }),
+        Expression::Binary { left, right, operator } => Ok(Expression::Binary {
+            left: Box::new(extract_exprs(*left, is_target, extracted)?),
+            right: Box::new(extract_exprs(*right, is_target, extracted)?),
+            operator,
+        }),
+        Expression::Unary { operand, operator } => Ok(Expression::Unary {
+            operand: Box::new(extract_exprs(*operand, is_target, extracted)?),
+            operator,
+        }),
+        Expression::ScalarFunction { func, args } => Ok(Expression::ScalarFunction {
+            func,
+            args: args
+                .into_iter()
+                .map(|arg| extract_exprs(arg, is_target, extracted))
+                .collect::<Result<Vec<Expression>, QueryError>>()?,
+        }),
+        // Aggregates inside a window call, as in `RANK() OVER (ORDER BY COUNT(*))`,
+        // are computed by the Aggregate node below the Window node.
+        Expression::WindowFunction { func, args, partition_by, order_by, frame } => Ok(Expression::WindowFunction {
+            func,
+            args: args
+                .into_iter()
+                .map(|arg| extract_exprs(arg, is_target, extracted))
+                .collect::<Result<Vec<Expression>, QueryError>>()?,
+            partition_by: partition_by
+                .into_iter()
+                .map(|expr| extract_exprs(expr, is_target, extracted))
+                .collect::<Result<Vec<Expression>, QueryError>>()?,
+            order_by: order_by
+                .into_iter()
+                .map(|sort| Ok(SortExpr { expr: extract_exprs(sort.expr, is_target, extracted)?, ..sort }))
+                .collect::<Result<Vec<SortExpr>, QueryError>>()?,
+            frame,
+        }),
+        other => Ok(other),
+    }
+}
+
+fn function_arg_exprs(function: &Function) -> Result<Vec<&Expr>, QueryError> {
+    match &function.args {
+        FunctionArguments::None => Ok(vec![]),
+        FunctionArguments::List(list) => list
+            .args
+            .iter()
+            .map(|arg| match arg {
+                FunctionArg::Unnamed(FunctionArgExpr::Expr(expr)) => Ok(expr),
+                _ => Err(QueryError::ValidationError {
+                    message: format!("Unsupported function argument: {}", arg),
+                }),
+            })
+            .collect(),
+        FunctionArguments::Subquery(_) => Err(QueryError::ValidationError {
+            message: format!("Subquery arguments are not supported: {}", function.name),
+        }),
+    }
+}
+
 pub fn sql_to_logical_plan(
     statement: &Statement,
     catalog: &Catalog,
@@ -76,33 +403,236 @@ pub fn sql_to_logical_plan(
     }
 }
 
-fn query_to_logical_plan(
+/// Plans a scalar expression, such as the `WHERE` or `SET` of a data change, over the columns of `schema`.
+pub(crate) fn sql_expr_to_expression(expr: &Expr, schema: &Schema, catalog: &Catalog) -> Result<Expression, QueryError> {
+    expr.to_expression(schema, catalog)
+}
+
+/// Registers the CSV, JSON Lines or Parquet file named by a `CREATE EXTERNAL TABLE` statement in the catalog.
+pub fn create_external_table(statement: &Statement, catalog: &mut Catalog) -> Result<(), QueryError> {
+    let create = match statement {
+        Statement::CreateTable(create) if create.external => create,
+        _ => return Err(QueryError::ValidationError {
+            message: format!("Not a CREATE EXTERNAL TABLE statement: {}", statement),
+        }),
+    };
+    let location = create.location.as_ref().ok_or_else(|| QueryError::ValidationError {
+        message: format!("CREATE EXTERNAL TABLE {} requires a LOCATION", create.name),
+    })?;
+
+    let sql_options: &[SqlOption] = match &create.table_options {
+        CreateTableOptions::None => &[],
+        CreateTableOptions::Options(options) => options,
+        other => return Err(QueryError::ValidationError {
+            message: format!("Unsupported table options: {}", other),
+        }),
+    };
+    let name = catalog.qualify(&table_name(&create.name))?.reference();
+    if catalog.contains(&name) {
+        if create.if_not_exists {
+            return Ok(());
+        }
+        return Err(QueryError::ValidationError {
+            message: format!("Table '{}' already exists", create.name),
+        });
+    }
+
+    let schema = match create.columns.is_empty() {
+        true => None,
+        false => Some(column_defs_to_schema(&create.columns)?),
+    };
+    let mut options = Vec::new();
+    for sql_option in sql_options {
+        match sql_option {
+            SqlOption::KeyValue { key, value } => options.push((key.value.clone(), option_value(value)?)),
+            other => return Err(QueryError::ValidationError {
+                message: format!("Unsupported option: {}", other),
+            }),
+        }
+    }
+    let format = match create.file_format {
+        None | Some(FileFormat::TEXTFILE) => ExternalFormat::Csv,
+        Some(FileFormat::JSONFILE) => ExternalFormat::Json,
+        Some(FileFormat::PARQUET) => ExternalFormat::Parquet,
+        Some(other) => return Err(QueryError::ValidationError {
+            message: format!("Unsupported external table format: {}", other),
+        }),
+    };
+    register_external_table(name, ExternalTable { format, location: location.clone(), schema, options }, catalog)
+}
+
+/// Opens the files of `table`, registers them as table `name` and records the
+/// definition in the catalog.
+pub fn register_external_table(name: String, table: ExternalTable, catalog: &mut Catalog) -> Result<(), QueryError> {
+    let location = table.location.as_str();
+    // `rejected_rows_table` names a table that collects the rejected rows of a
+    // CSV table and is registered next to the external table itself.
+    let mut rejected_table = None;
+    let source: Rc<dyn DataSource> = match table.format {
+        ExternalFormat::Csv => {
+            let mut options = CsvReadOptions::new();
+            if let Some(schema) = &table.schema {
+                options = options.schema(schema.clone());
+            }
+            for (key, value) in &table.options {
+                options = if key.eq_ignore_ascii_case("rejected_rows_table") {
+                    let rejected = Rc::new(RejectedRowsTable::new());
+                    rejected_table = Some((value.clone(), rejected.clone()));
+                    options.rejected_rows(RejectedRowsSink::Table(rejected))
+                } else {
+                    options.set(key, value)?
+                };
+            }
+            if let Some((rejected_name, _)) = &rejected_table
+                && catalog.contains(rejected_name)
+            {
+                return Err(QueryError::ValidationError {
+                    message: format!("Table '{}' already exists", rejected_name),
+                });
+            }
+            if options.rejected_rows.is_some() && is_listing_location(location) {
+                return Err(QueryError::ValidationError {
+                    message: "Rejected rows can only be collected for a single CSV file".to_string(),
+                });
+            }
+            let extensions = options.file_extensions.clone();
+            open_location(location, &extensions, table.schema.as_ref(), move |path| CsvDataSource::with_options(path, options.clone()))?
+        },
+        ExternalFormat::Json => {
+            let mut options = JsonReadOptions::new();
+            if let Some(schema) = &table.schema {
+                options = options.schema(schema.clone());
+            }
+            for (key, value) in &table.options {
+                options = options.set(key, value)?;
+            }
+            let extensions = options.file_extensions.clone();
+            open_location(location, &extensions, table.schema.as_ref(), move |path| JsonDataSource::with_options(path, options.clone()))?
+        },
+        ExternalFormat::Parquet => {
+            if let Some((key, _)) = table.options.first() {
+                return Err(QueryError::ValidationError {
+                    message: format!("Unknown Parquet option '{}'", key),
+                });
+            }
+            // Parquet files carry their own schema, so declared columns are not used.
+            open_location(location, &["parquet".to_string()], None, ParquetDataSource::new)?
+        },
+    };
+
+    catalog.register_table(name.clone(), source);
+    catalog.define(name, Definition::ExternalTable(table));
+    if let Some((rejected_name, rejected)) = rejected_table {
+        catalog.register_table(rejected_name, rejected);
+    }
+    Ok(())
+}
+
+/// Opens the file at `location` with `open`, or every file it names as one
+/// `ListingTable` when it is a directory or a glob pattern. The table has the
+/// declared `schema`, if any, and otherwise the schema of its first file.
+fn open_location<S: DataSource + 'static>(
+    location: &str,
+    extensions: &[String],
+    schema: Option<&Schema>,
+    open: impl Fn(String) -> Result<S, QueryError> + 'static,
+) -> Result<Rc<dyn DataSource>, QueryError> {
+    if !is_listing_location(location) {
+        return Ok(Rc::new(open(location.to_string())?));
+    }
+    let table = ListingTable::try_new(location, extensions, schema.cloned(), move |path| {
+        Ok(Rc::new(open(path)?) as Rc<dyn DataSource>)
+    })?;
+    Ok(Rc::new(table))
+}
+
+/// Builds a schema from SQL column definitions. Columns are nullable unless declared `NOT NULL`.
+pub(crate) fn column_defs_to_schema(columns: &[ColumnDef]) -> Result<Schema, QueryError> {
+    let fields = columns
+        .iter()
+        .map(|column| {
+            let field_type = match &column.data_type {
+                SqlDataType::Int(_)
+                | SqlDataType::Integer(_)
+                | SqlDataType::Int4(_)
+                | SqlDataType::Int32
+                | SqlDataType::SmallInt(_) => DataType::Int32,
+                SqlDataType::Double(_)
+                | SqlDataType::DoublePrecision
+                | SqlDataType::Float(_)
+                | SqlDataType::Float8
+                | SqlDataType::Float64
+                | SqlDataType::Real => DataType::Float64,
+                SqlDataType::Bool | SqlDataType::Boolean => DataType::Bool,
+                SqlDataType::Varchar(_)
+                | SqlDataType::CharacterVarying(_)
+                | SqlDataType::Char(_)
+                | SqlDataType::Character(_)
+                | SqlDataType::Text
+                | SqlDataType::String(_) => DataType::String,
+                other => return Err(QueryError::ValidationError {
+                    message: format!("Unsupported column type {} for column {}", other, column.name),
+                }),
+            };
+            let not_null = column.options.iter().any(|option| matches!(option.option, ColumnOption::NotNull));
+            Ok(Field {
+                name: column.name.value.clone(),
+                field_type,
+                is_nullable: !not_null,
+            })
+        })
+        .collect::<Result<Vec<Field>, QueryError>>()?;
+    Ok(Schema::new(fields))
+}
+
+/// The text of an option value, which may be written as a string, number, boolean or bare word.
+fn option_value(value: &Expr) -> Result<String, QueryError> {
+    match value {
+        Expr::Value(value_with_span) => match &value_with_span.value {
+            Value::SingleQuotedString(s) | Value::DoubleQuotedString(s) => Ok(s.clone()),
+            Value::Number(n, _) => Ok(n.clone()),
+            Value::Boolean(b) => Ok(b.to_string()),
+            other => Err(QueryError::ValidationError {
+                message: format!("Unsupported option value: {}", other),
+            }),
+        },
+        Expr::Identifier(ident) => Ok(ident.value.clone()),
+        other => Err(QueryError::ValidationError {
+            message: format!("Unsupported option value: {}", other),
+        }),
+    }
+}
+
+pub(crate) fn query_to_logical_plan(
     query: &sqlparser::ast::Query,
     catalog: &Catalog,
 ) -> Result<LogicalPlan, QueryError> {
     let select = match query.body.as_ref() {
         SetExpr::Select(select) => select,
+        SetExpr::Values(values) => return values_to_logical_plan(values, catalog),
+        SetExpr::Query(query) => return query_to_logical_plan(query, catalog),
         _ => return Err(QueryError::ValidationError {
-            message: "Only SELECT queries are supported".to_string(),
+            message: "Only SELECT and VALUES queries are supported".to_string(),
         }),
     };
 
-    let table_name = extract_table_name(select)?;
-    
-    let schema = catalog.get_schema(&table_name)
-        .ok_or_else(|| QueryError::ValidationError {
-            message: format!("Table '{}' not found in catalog", table_name),
-        })?
-        .clone();
-
-    let mut plan = LogicalPlan::Scan {
-        path: table_name.clone(),
-        schema: schema.clone(),
-        projection: None,
-    };
+    let mut plan = from_to_logical_plan(select, catalog)?;
+    let schema = plan.schema()?;
+    // Virtual columns of a table are only returned when selected by name.
+    let is_table_scan = matches!(plan, LogicalPlan::Scan { .. });
 
     if let Some(selection) = &select.selection {
-        let predicate = selection.to_expression(&schema)?;
+        let predicate = selection.to_expression(&schema, catalog)?;
+        if predicate.contains_window_function() {
+            return Err(QueryError::ValidationError {
+                message: "Window functions are not allowed in WHERE".to_string(),
+            });
+        }
+        if predicate.contains_aggregate_function() {
+            return Err(QueryError::ValidationError {
+                message: "Aggregate functions are not allowed in WHERE".to_string(),
+            });
+        }
         plan = LogicalPlan::Filter {
             input: Box::new(plan),
             predicate,
@@ -114,6 +644,9 @@ fn query_to_logical_plan(
         match item {
             SelectItem::Wildcard(_) => {
                 for field in &schema.fields {
+                    if is_table_scan && field.name == FILE_NAME_COLUMN {
+                        continue;
+                    }
                     projection_columns.push(Expression::Column {
                         name: field.name.clone(),
                         data_type: field.field_type,
@@ -121,11 +654,52 @@ fn query_to_logical_plan(
                 }
             }
             _ => {
-                projection_columns.push(item.to_expression(&schema)?);
+                projection_columns.push(item.to_expression(&schema, catalog)?);
             }
         }
     }
 
+    let group_by = match &select.group_by {
+        GroupByExpr::Expressions(exprs, modifiers) if modifiers.is_empty() => exprs
+            .iter()
+            .map(|expr| expr.to_expression(&schema, catalog))
+            .collect::<Result<Vec<Expression>, QueryError>>()?,
+        _ => return Err(QueryError::ValidationError {
+            message: format!("Unsupported GROUP BY clause: {}", select.group_by),
+        }),
+    };
+
+    let mut aggr_exprs = Vec::new();
+    let projection_columns = projection_columns
+        .into_iter()
+        .map(|expr| extract_exprs(expr, Expression::is_aggregate_call, &mut aggr_exprs))
+        .collect::<Result<Vec<Expression>, QueryError>>()?;
+    if !group_by.is_empty() || !aggr_exprs.is_empty() {
+        plan = LogicalPlan::Aggregate {
+            input: Box::new(plan),
+            group_by,
+            aggr_exprs,
+        };
+        let aggregate_schema = plan.schema()?;
+        for expr in projection_columns.iter().filter(|e| !e.contains_window_function()) {
+            expr.is_valid(&aggregate_schema).map_err(|err| QueryError::ValidationError {
+                message: format!("{}; columns must appear in GROUP BY or inside an aggregate function", err),
+            })?;
+        }
+    }
+
+    let mut window_exprs = Vec::new();
+    let projection_columns = projection_columns
+        .into_iter()
+        .map(|expr| extract_exprs(expr, |e| matches!(e, Expression::WindowFunction { .. }), &mut window_exprs))
+        .collect::<Result<Vec<Expression>, QueryError>>()?;
+    if !window_exprs.is_empty() {
+        plan = LogicalPlan::Window {
+            input: Box::new(plan),
+            window_exprs,
+        };
+    }
+
     if !projection_columns.is_empty() {
         plan = LogicalPlan::Projection {
             input: Box::new(plan),
@@ -133,16 +707,169 @@ fn query_to_logical_plan(
         };
     }
 
+    match &select.distinct {
+        None | Some(Distinct::All) => {}
+        Some(Distinct::Distinct) => {
+            plan = LogicalPlan::Distinct {
+                input: Box::new(plan),
+            };
+        }
+        Some(Distinct::On(_)) => return Err(QueryError::ValidationError {
+            message: "SELECT DISTINCT ON is not supported".to_string(),
+        }),
+    }
+
     Ok(plan)
 }
 
-fn extract_table_name(select: &sqlparser::ast::Select) -> Result<String, QueryError> {
+/// Plans the FROM clause: either a table from the catalog or a subquery, such
+/// as `(VALUES ...) t(id, name)`, whose alias may rename its columns.
+fn from_to_logical_plan(select: &sqlparser::ast::Select, catalog: &Catalog) -> Result<LogicalPlan, QueryError> {
     if select.from.is_empty() {
         return Err(QueryError::ValidationError {
             message: "Query must have a FROM clause".to_string(),
         });
     }
 
+    match &select.from[0].relation {
+        sqlparser::ast::TableFactor::Derived { lateral: false, subquery, alias, .. } => {
+            let plan = query_to_logical_plan(subquery, catalog)?;
+            match alias {
+                Some(alias) if !alias.columns.is_empty() => {
+                    let names: Vec<&str> = alias.columns.iter().map(|column| column.name.value.as_str()).collect();
+                    rename_columns(plan, &alias.name.value, &names)
+                },
+                _ => Ok(plan),
+            }
+        },
+        _ => {
+            let table_name = extract_table_name(select)?;
+            let path = catalog.resolve(&table_name)?
+                .ok_or_else(|| QueryError::ValidationError {
+                    message: format!("Table '{}' not found in catalog", table_name),
+                })?
+                .reference();
+            if let Some(plan) = catalog.get_view(&path) {
+                return Ok(plan.clone());
+            }
+            let schema = catalog.get_source(&path)?.schema().clone();
+            Ok(LogicalPlan::Scan {
+                path,
+                schema,
+                projection: None,
+                filters: Vec::new(),
+            })
+        }
+    }
+}
+
+/// Renames the first columns of `plan` to `names`, as listed by a table alias
+/// such as `t(id, name)` or a view's column list. Columns past the listed names
+/// keep their names.
+pub(crate) fn rename_columns(plan: LogicalPlan, relation: &str, names: &[&str]) -> Result<LogicalPlan, QueryError> {
+    let schema = plan.schema()?;
+    if names.len() > schema.fields.len() {
+        return Err(QueryError::ValidationError {
+            message: format!(
+                "Table {} has {} columns available but {} columns specified",
+                relation, schema.fields.len(), names.len()
+            ),
+        });
+    }
+    let columns = schema.fields.iter().enumerate().map(|(i, field)| {
+        let column = Expression::Column { name: field.name.clone(), data_type: field.field_type };
+        match names.get(i) {
+            Some(new_name) => Expression::Alias { expr: Box::new(column), name: new_name.to_string() },
+            None => column,
+        }
+    }).collect();
+    Ok(LogicalPlan::Projection { input: Box::new(plan), columns })
+}
+
+/// Plans `VALUES (...), (...)` as inline rows with columns named `column1`,
+/// `column2` and so on. Every value must be a literal, and each column takes
+/// the type its non-null values share, with integers widened to floats when
+/// the two are mixed.
+fn values_to_logical_plan(values: &sqlparser::ast::Values, catalog: &Catalog) -> Result<LogicalPlan, QueryError> {
+    let no_columns = Schema::new(Vec::new());
+    let mut rows = values
+        .rows
+        .iter()
+        .map(|row| {
+            row.iter()
+                .map(|expr| literal_value(&expr.to_expression(&no_columns, catalog)?))
+                .collect::<Result<Vec<ScalarValue>, QueryError>>()
+        })
+        .collect::<Result<Vec<Vec<ScalarValue>>, QueryError>>()?;
+
+    let width = rows.first().map_or(0, Vec::len);
+    if let Some(row) = rows.iter().find(|row| row.len() != width) {
+        return Err(QueryError::ValidationError {
+            message: format!("VALUES lists must all be the same length, found {} and {}", width, row.len()),
+        });
+    }
+
+    let mut fields = Vec::with_capacity(width);
+    for i in 0..width {
+        let mut field_type = None;
+        for value in rows.iter().map(|row| &row[i]).filter(|value| !value.is_null()) {
+            field_type = Some(match (field_type, value.data_type()) {
+                (None, value_type) => value_type,
+                (Some(current), value_type) if current == value_type => current,
+                (Some(DataType::Int32 | DataType::Float64), DataType::Int32 | DataType::Float64) => DataType::Float64,
+                (Some(current), value_type) => return Err(QueryError::ValidationError {
+                    message: format!("VALUES column {} mixes {:?} and {:?} values", i + 1, current, value_type),
+                }),
+            });
+        }
+        let field_type = field_type.unwrap_or(DataType::String);
+        let mut is_nullable = false;
+        for row in &mut rows {
+            is_nullable |= row[i].is_null();
+            row[i] = match &row[i] {
+                value if value.is_null() => ScalarValue::null(field_type),
+                &ScalarValue::Int32(Some(v)) if field_type == DataType::Float64 => ScalarValue::Float64(Some(f64::from(v))),
+                value => value.clone(),
+            };
+        }
+        fields.push(Field { name: format!("column{}", i + 1), field_type, is_nullable });
+    }
+
+    Ok(LogicalPlan::Values { schema: Schema::new(fields), rows })
+}
+
+/// The value of a literal, or of a negated numeric literal such as `-1`.
+fn literal_value(expr: &Expression) -> Result<ScalarValue, QueryError> {
+    match expr {
+        Expression::Literal(value) => Ok(value.clone()),
+        Expression::Unary { operand, operator: crate::logical_plan::plan::UnaryOperator::Negate } => match operand.as_ref() {
+            Expression::Literal(ScalarValue::Int32(v)) => Ok(ScalarValue::Int32(v.map(|v| -v))),
+            Expression::Literal(ScalarValue::Float64(v)) => Ok(ScalarValue::Float64(v.map(|v| -v))),
+            _ => Err(QueryError::ValidationError {
+                message: format!("VALUES only accepts literal values, found {:?}", expr),
+            }),
+        },
+        _ => Err(QueryError::ValidationError {
+            message: format!("VALUES only accepts literal values, found {:?}", expr),
+        }),
+    }
+}
+
+/// A table reference as written, with any quotes, for the catalog to resolve.
+pub(crate) fn table_name(name: &sqlparser::ast::ObjectName) -> String {
+    name.to_string()
+}
+
+/// The column of `schema` that `ident` names. An unquoted name also matches a
+/// column whose name differs only in case, as long as just one does.
+pub(crate) fn resolve_column<'a>(schema: &'a Schema, ident: &sqlparser::ast::Ident) -> Result<&'a Field, QueryError> {
+    let part = NamePart::new(ident.value.as_str(), ident.quote_style.is_some());
+    let name = find_name(schema.fields.iter().map(|field| field.name.as_str()), &part)
+        .map_err(|message| QueryError::ValidationError { message })?;
+    schema.column_exists(name.unwrap_or(&ident.value)).map_err(|message| QueryError::ValidationError { message })
+}
+
+fn extract_table_name(select: &sqlparser::ast::Select) -> Result<String, QueryError> {
     let table_with_joins = &select.from[0];
     match &table_with_joins.relation {
         sqlparser::ast::TableFactor::Table { name, .. } => {
@@ -152,8 +879,7 @@ fn extract_table_name(select: &sqlparser::ast::Select) -> Result<String, QueryEr
                 });
             }
 
-            let table_name_str = name.to_string();
-            Ok(table_name_str.trim_matches('"').trim_matches('\'').to_string())
+            Ok(table_name(name))
         }
         _ => Err(QueryError::ValidationError {
             message: "Only simple table references are supported (no subqueries, joins, etc.)".to_string(),
@@ -215,3 +941,37 @@ fn sql_value_to_scalar(value: &Value) -> Result<ScalarValue, QueryError> {
         }),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::test_util::{error_message, float, int, query, rows, text};
+
+    use super::*;
+
+    #[test]
+    fn values_rows_widen_to_a_common_type_per_column() {
+        let mut catalog = Catalog::new();
+        let (schema, values) = query(&mut catalog, "VALUES (1, 'a'), (2, NULL), (3.5, 'c')").unwrap();
+        let names: Vec<&str> = schema.fields.iter().map(|field| field.name.as_str()).collect();
+        assert_eq!(names, ["column1", "column2"]);
+        assert_eq!(values, vec![
+            vec![float(1.0), text("a")],
+            vec![float(2.0), ScalarValue::String(None)],
+            vec![float(3.5), text("c")],
+        ]);
+    }
+
+    #[test]
+    fn values_can_be_queried_and_renamed() {
+        let mut catalog = Catalog::new();
+        assert_eq!(rows(&mut catalog, "SELECT column2 FROM (VALUES (1, 'a'), (2, 'b')) AS v WHERE column1 = 2"), vec![vec![text("b")]]);
+        assert_eq!(rows(&mut catalog, "SELECT SUM(n) FROM (VALUES (1, 'a'), (2, 'b')) AS v (n, s)"), vec![vec![int(3)]]);
+    }
+
+    #[test]
+    fn values_rows_must_agree() {
+        let mut catalog = Catalog::new();
+        assert_eq!(error_message(&mut catalog, "VALUES (1, 'a'), (2)"), "VALUES lists must all be the same length, found 2 and 1");
+        assert_eq!(error_message(&mut catalog, "VALUES (1), ('a')"), "VALUES column 1 mixes Int32 and String values");
+    }
+}
@@ -1,6 +1,10 @@
 use crate::errors::LexerError;
+use sqlparser::ast::{CreateTableOptions, FileFormat, Statement as SqlStatement};
+use sqlparser::ast::helpers::stmt_create_table::CreateTableBuilder;
 use sqlparser::dialect::GenericDialect;
-use sqlparser::parser::Parser;
+use sqlparser::keywords::Keyword;
+use sqlparser::parser::{Parser, ParserError};
+use sqlparser::tokenizer::Token;
 
 /// Parse a SQL query string into an AST using sqlparser
 /// 
@@ -8,14 +12,110 @@ use sqlparser::parser::Parser;
 /// You can then convert this AST to your LogicalPlan.
 pub fn parse_sql(sql: &str) -> Result<Vec<sqlparser::ast::Statement>, LexerError> {
     let dialect = GenericDialect {};
-    Parser::parse_sql(&dialect, sql)
+    parse_statements(&dialect, sql)
         .map_err(|e| LexerError::InvalidToken {
             message: format!("SQL parsing error: {}", e),
         })
 }
 
+/// Parses statements like `Parser::parse_statements`, except that `CREATE EXTERNAL TABLE`
+/// is read with our own grammar.
+fn parse_statements(dialect: &GenericDialect, sql: &str) -> Result<Vec<SqlStatement>, ParserError> {
+    let mut parser = Parser::new(dialect).try_with_sql(sql)?;
+    let mut statements = Vec::new();
+    let mut expecting_delimiter = false;
+    loop {
+        while parser.consume_token(&Token::SemiColon) {
+            expecting_delimiter = false;
+        }
+        if parser.peek_token().token == Token::EOF {
+            break;
+        }
+        if expecting_delimiter {
+            return parser.expected("end of statement", parser.peek_token());
+        }
+
+        let statement = if parser.parse_keywords(&[Keyword::CREATE, Keyword::EXTERNAL, Keyword::TABLE]) {
+            parse_create_external_table(&mut parser)?
+        } else {
+            parser.parse_statement()?
+        };
+        statements.push(statement);
+        expecting_delimiter = true;
+    }
+    Ok(statements)
+}
+
+/// Parses the rest of
+///
+/// ```text
+/// CREATE EXTERNAL TABLE [IF NOT EXISTS] name [(columns)]
+///     STORED AS {CSV | JSON | PARQUET} LOCATION 'path' [OPTIONS (key = value, ...)]
+/// ```
+///
+/// sqlparser only knows the Hive form, which has no CSV format and takes
+/// `TBLPROPERTIES` instead of `OPTIONS`.
+fn parse_create_external_table(parser: &mut Parser) -> Result<SqlStatement, ParserError> {
+    let if_not_exists = parser.parse_keywords(&[Keyword::IF, Keyword::NOT, Keyword::EXISTS]);
+    let name = parser.parse_object_name(false)?;
+    let (columns, _constraints) = parser.parse_columns()?;
+
+    let mut file_format = None;
+    let mut location = None;
+    let mut options = Vec::new();
+    loop {
+        if parser.parse_keywords(&[Keyword::STORED, Keyword::AS]) {
+            let format = parser.parse_identifier()?;
+            file_format = Some(match format.value.to_uppercase().as_str() {
+                // Hive's TEXTFILE is delimited text, which is what CSV is.
+                "CSV" | "TEXTFILE" => FileFormat::TEXTFILE,
+                "JSON" | "NDJSON" | "JSONFILE" => FileFormat::JSONFILE,
+                "PARQUET" => FileFormat::PARQUET,
+                _ => return Err(ParserError::ParserError(format!("Unsupported file format: {}", format))),
+            });
+        } else if parser.parse_keyword(Keyword::LOCATION) {
+            location = Some(parser.parse_literal_string()?);
+        } else if parser.peek_keyword(Keyword::OPTIONS) {
+            options.extend(parser.parse_options(Keyword::OPTIONS)?);
+        } else {
+            break;
+        }
+    }
+
+    let table_options = if options.is_empty() {
+        CreateTableOptions::None
+    } else {
+        CreateTableOptions::Options(options)
+    };
+    Ok(SqlStatement::CreateTable(
+        CreateTableBuilder::new(name)
+            .columns(columns)
+            .if_not_exists(if_not_exists)
+            .external(true)
+            .file_format(file_format)
+            .location(location)
+            .table_options(table_options)
+            .build(),
+    ))
+}
+
 /// Re-export sqlparser AST types for convenience
 pub use sqlparser::ast::{Statement, Query, Select, Expr, BinaryOperator, UnaryOperator};
 
 /// Re-export planner for converting AST to LogicalPlan
-pub use crate::sql_support::planner::sql_to_logical_plan;
\ No newline at end of file
+pub use crate::sql_support::planner::{create_external_table, sql_to_logical_plan};
+
+/// Re-export the statements that write query results
+pub use crate::sql_support::write::{copy_to, create_table_as};
+
+/// Re-export the statements that change the rows of in-memory tables
+pub use crate::sql_support::dml::{delete_from, insert_into, update_table};
+
+/// Re-export the statements that change the catalog
+pub use crate::sql_support::ddl::{create_schema, create_table, create_view, drop_objects};
+
+/// Re-export the executor that runs any statement
+pub use crate::sql_support::executor::{StatementResult, execute_statement};
+
+/// Re-export loading and saving the catalog file
+pub use crate::sql_support::catalog_file::{open_catalog_file, save_catalog_file};
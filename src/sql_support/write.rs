@@ -0,0 +1,369 @@
+use std::{fs::{self, File}, io::{self, BufWriter, Write}, path::Path, rc::Rc};
+
+use sqlparser::ast::{CopyOption, CopySource, CopyTarget, FileFormat, HiveFormat, HiveIOFormat, Query, Statement};
+
+use crate::datasource::{compression::data_extension, memory::MemTable};
+use crate::logical_plan::definition::{ExternalFormat, ExternalTable};
+use crate::logical_plan::plan::{Catalog, Expression, LogicalPlan};
+use crate::optimizer::Optimizer;
+use crate::physical_plan::{plan::PhysicalPlan, planner::create_physical_plan};
+use crate::writers::{OutputFormat, RecordBatchWriter, csv::CsvWriter};
+use crate::{SessionConfig, errors::QueryError};
+
+use super::ddl::check_not_read_by_views;
+use super::planner::{query_to_logical_plan, register_external_table, resolve_column, table_name};
+
+/// Plans a query all the way to an executable physical plan, optimizing it on the way.
+pub fn create_query_plan(query: &Query, catalog: &Catalog, config: &SessionConfig) -> Result<Box<dyn PhysicalPlan>, QueryError> {
+    let plan = query_to_logical_plan(query, catalog)?;
+    let plan = Optimizer::new().optimize(&plan, catalog)?;
+    create_physical_plan(&plan, catalog, config)
+}
+
+/// Runs `COPY (query) TO 'file'` or `COPY table [(columns)] TO 'file'`,
+/// streaming the result into the file batch by batch. `TO STDOUT` writes to
+/// standard output instead. Returns the number of rows written.
+///
+/// The `FORMAT` option picks one of the output formats of `OutputFormat`, such
+/// as `csv`, `tsv`, `json` or `ndjson`. Without it the format follows the
+/// file's extension, and is CSV when the extension is not a known format.
+/// `HEADER false` leaves out the header line of CSV and TSV.
+pub fn copy_to(statement: &Statement, catalog: &Catalog, config: &SessionConfig) -> Result<usize, QueryError> {
+    let Statement::Copy { source, to: true, target, options, legacy_options, .. } = statement else {
+        return Err(QueryError::ValidationError {
+            message: format!("Not a COPY ... TO statement: {}", statement),
+        });
+    };
+    if let Some(option) = legacy_options.first() {
+        return Err(QueryError::ValidationError {
+            message: format!("Unsupported COPY option: {}", option),
+        });
+    }
+
+    let path = match target {
+        CopyTarget::File { filename } => Some(filename.as_str()),
+        CopyTarget::Stdout => None,
+        other => return Err(QueryError::ValidationError {
+            message: format!("COPY can only write to a file or STDOUT, not {}", other),
+        }),
+    };
+    let mut format = path.and_then(format_from_extension).unwrap_or(OutputFormat::Csv);
+    let mut header = true;
+    for option in options {
+        match option {
+            CopyOption::Format(name) => {
+                format = name.value.parse().map_err(|message| QueryError::ValidationError { message })?;
+            },
+            CopyOption::Header(value) => header = *value,
+            other => return Err(QueryError::ValidationError {
+                message: format!("Unsupported COPY option: {}", other),
+            }),
+        }
+    }
+    if !header && !matches!(format, OutputFormat::Csv | OutputFormat::Tsv) {
+        return Err(QueryError::ValidationError {
+            message: format!("HEADER false is only supported for CSV and TSV, not {}", format),
+        });
+    }
+
+    let plan = match source {
+        CopySource::Query(query) => create_query_plan(query, catalog, config)?,
+        CopySource::Table { table_name, columns } => {
+            let name = catalog.resolve(&table_name.to_string())?
+                .ok_or_else(|| QueryError::ValidationError {
+                    message: format!("Table '{}' not found in catalog", table_name),
+                })?
+                .reference();
+            let schema = catalog.get_source(&name)?.schema().clone();
+            let mut plan = LogicalPlan::Scan { path: name, schema: schema.clone(), projection: None, filters: Vec::new() };
+            if !columns.is_empty() {
+                let columns = columns
+                    .iter()
+                    .map(|column| {
+                        let field = resolve_column(&schema, column)?;
+                        Ok(Expression::Column { name: field.name.clone(), data_type: field.field_type })
+                    })
+                    .collect::<Result<Vec<Expression>, QueryError>>()?;
+                plan = LogicalPlan::Projection { input: Box::new(plan), columns };
+            }
+            let plan = Optimizer::new().optimize(&plan, catalog)?;
+            create_physical_plan(&plan, catalog, config)?
+        },
+    };
+
+    let writer = |out: Box<dyn Write>| -> Box<dyn RecordBatchWriter> {
+        match (format, header) {
+            (OutputFormat::Csv, false) => Box::new(CsvWriter::new(out, plan.schema()).without_header()),
+            (OutputFormat::Tsv, false) => Box::new(CsvWriter::tsv(out, plan.schema()).without_header()),
+            (format, _) => format.writer(out, plan.schema()),
+        }
+    };
+    match path {
+        Some(path) => write_file(path, plan.as_ref(), writer),
+        None => write_all(plan.as_ref(), writer(Box::new(BufWriter::new(io::stdout())))),
+    }
+}
+
+/// Runs `CREATE TABLE name AS query` and registers the result as table `name`.
+///
+/// The rows are kept in memory, unless the Hive clauses `STORED AS {TEXTFILE |
+/// JSONFILE} LOCATION 'path'` are given. Then they are streamed into a CSV or
+/// JSON Lines file at `path`, which is registered as the table. Without `STORED
+/// AS` the file's format follows its extension. `OR REPLACE` replaces an
+/// existing table unless a view reads it. Returns the number of rows.
+pub fn create_table_as(statement: &Statement, catalog: &mut Catalog, config: &SessionConfig) -> Result<usize, QueryError> {
+    let (create, query) = match statement {
+        Statement::CreateTable(create) if !create.external && let Some(query) = &create.query => (create, query),
+        _ => return Err(QueryError::ValidationError {
+            message: format!("Not a CREATE TABLE ... AS statement: {}", statement),
+        }),
+    };
+    if !create.columns.is_empty() {
+        return Err(QueryError::ValidationError {
+            message: "CREATE TABLE ... AS takes its columns from the query, so it cannot list them".to_string(),
+        });
+    }
+    let qualified = catalog.qualify(&table_name(&create.name))?;
+    let name = qualified.reference();
+    if catalog.contains(&name) {
+        if !create.or_replace {
+            if create.if_not_exists {
+                return Ok(0);
+            }
+            return Err(QueryError::ValidationError {
+                message: format!("Table '{}' already exists", create.name),
+            });
+        }
+        check_not_read_by_views(&[qualified], "replace", catalog)?;
+    }
+
+    let (storage, location) = match &create.hive_formats {
+        Some(HiveFormat { storage, location, .. }) => (storage.as_ref(), location.as_deref()),
+        None => (None, None),
+    };
+    let plan = create_query_plan(query, catalog, config)?;
+    let Some(location) = location else {
+        if storage.is_some() {
+            return Err(QueryError::ValidationError {
+                message: "STORED AS needs a LOCATION to write the table to".to_string(),
+            });
+        }
+        let batches = plan.execute().collect::<Result<Vec<_>, QueryError>>()?;
+        let row_count = batches.iter().map(|batch| batch.columns.first().map_or(0, |column| column.values.len())).sum();
+        catalog.register_table(name, Rc::new(MemTable::try_new(plan.schema().clone(), batches)?));
+        return Ok(row_count);
+    };
+
+    let format = match storage {
+        None => match format_from_extension(location) {
+            Some(OutputFormat::Json | OutputFormat::NdJson) => OutputFormat::NdJson,
+            _ => OutputFormat::Csv,
+        },
+        Some(HiveIOFormat::FileFormat { format: FileFormat::TEXTFILE }) => OutputFormat::Csv,
+        Some(HiveIOFormat::FileFormat { format: FileFormat::JSONFILE }) => OutputFormat::NdJson,
+        Some(other) => return Err(QueryError::ValidationError {
+            message: format!("CREATE TABLE ... AS can only write TEXTFILE or JSONFILE, not {:?}", other),
+        }),
+    };
+    let row_count = write_file(location, plan.as_ref(), |out| format.writer(out, plan.schema()))?;
+
+    // The file is read back with the query's schema rather than an inferred one.
+    let table = ExternalTable {
+        format: match format {
+            OutputFormat::NdJson => ExternalFormat::Json,
+            _ => ExternalFormat::Csv,
+        },
+        location: location.to_string(),
+        schema: Some(plan.schema().clone()),
+        options: vec![("file_extension".to_string(), "*".to_string())],
+    };
+    register_external_table(name, table, catalog)?;
+    Ok(row_count)
+}
+
+/// The output format a file extension names, such as `json` for `out.json`.
+fn format_from_extension(path: &str) -> Option<OutputFormat> {
+    match data_extension(Path::new(path))?.as_str() {
+        "jsonl" => Some(OutputFormat::NdJson),
+        extension => extension.parse().ok(),
+    }
+}
+
+/// Streams every batch of `plan` into the file at `path` and returns the number
+/// of rows written. The rows go to a hidden file next to `path` that is renamed
+/// over it once they are all written, so a query can read the file it replaces,
+/// and a failed write leaves the old file in place.
+fn write_file(
+    path: &str,
+    plan: &dyn PhysicalPlan,
+    writer: impl FnOnce(Box<dyn Write>) -> Box<dyn RecordBatchWriter>,
+) -> Result<usize, QueryError> {
+    let path = Path::new(path);
+    let file_name = path.file_name().map(|name| name.to_string_lossy()).unwrap_or_default();
+    let temp_path = path.with_file_name(format!(".{}.tmp", file_name));
+    let write_error = |e: io::Error| QueryError::DataSourceError {
+        message: format!("Failed to write {}: {}", path.display(), e),
+    };
+    let file = File::create(&temp_path).map_err(write_error)?;
+    let row_count = write_all(plan, writer(Box::new(BufWriter::new(file)))).inspect_err(|_| {
+        let _ = fs::remove_file(&temp_path);
+    })?;
+    fs::rename(&temp_path, path).map_err(write_error)?;
+    Ok(row_count)
+}
+
+/// Streams every batch of `plan` into `writer` and returns the number of rows written.
+fn write_all(plan: &dyn PhysicalPlan, mut writer: Box<dyn RecordBatchWriter>) -> Result<usize, QueryError> {
+    let mut row_count = 0;
+    for batch in plan.execute() {
+        let batch = batch?;
+        row_count += batch.columns.first().map_or(0, |column| column.values.len());
+        writer.write(&batch)?;
+    }
+    writer.finish()?;
+    Ok(row_count)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use crate::ScalarValue;
+    use crate::sql_support::sql::parse_sql;
+    use crate::test_util::{error_message, float, int, query, rows, temp_dir, text};
+
+    use super::*;
+
+    /// A catalog with an in-memory table `t` of three rows.
+    fn catalog() -> Catalog {
+        let mut catalog = Catalog::new();
+        query(&mut catalog, "CREATE TABLE t AS SELECT * FROM (VALUES (1, 'a', 2.0), (2, 'b,c', 0.5), (3, NULL, 1.25)) AS v (id, name, score)").unwrap();
+        catalog
+    }
+
+    fn parse(sql: &str) -> Statement {
+        parse_sql(sql).unwrap_or_else(|_| panic!("{} failed to parse", sql)).remove(0)
+    }
+
+    #[test]
+    fn copy_writes_a_query_to_a_csv_file() {
+        let dir = temp_dir("copy-csv");
+        let path = dir.join("out.csv");
+        let sql = format!("COPY (SELECT id, name FROM t WHERE id > 1) TO '{}'", path.display());
+        assert_eq!(copy_to(&parse(&sql), &catalog(), &SessionConfig::new()).unwrap(), 2);
+        assert_eq!(fs::read_to_string(path).unwrap(), "id,name\r\n2,\"b,c\"\r\n3,\r\n");
+    }
+
+    #[test]
+    fn copy_format_follows_the_extension_unless_given() {
+        let dir = temp_dir("copy-formats");
+        let mut catalog = catalog();
+        let copy = |catalog: &mut Catalog, file: &str, options: &str| {
+            let path = dir.join(file);
+            query(catalog, &format!("COPY (SELECT id, score FROM t WHERE id = 1) TO '{}' {}", path.display(), options)).unwrap();
+            fs::read_to_string(path).unwrap()
+        };
+        assert_eq!(copy(&mut catalog, "out.json", ""), "[\n{\"id\":1,\"score\":2.0}\n]\n");
+        assert_eq!(copy(&mut catalog, "out.jsonl", ""), "{\"id\":1,\"score\":2.0}\n");
+        assert_eq!(copy(&mut catalog, "out.txt", ""), "id,score\r\n1,2.0\r\n");
+        assert_eq!(copy(&mut catalog, "out.txt", "(FORMAT tsv)"), "id\tscore\n1\t2.0\n");
+        assert_eq!(copy(&mut catalog, "out.json", "(FORMAT csv, HEADER false)"), "1,2.0\r\n");
+        assert_eq!(copy(&mut catalog, "out.md", ""), "|  id | score |\n| --: | ----: |\n|   1 |   2.0 |\n");
+    }
+
+    #[test]
+    fn copy_reads_a_table_and_its_listed_columns() {
+        let dir = temp_dir("copy-table");
+        let mut catalog = catalog();
+        let path = dir.join("out.csv");
+        query(&mut catalog, &format!("COPY t (name, id) TO '{}'", path.display())).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "name,id\r\na,1\r\n\"b,c\",2\r\n,3\r\n");
+        query(&mut catalog, &format!("COPY T TO '{}' (HEADER false)", path.display())).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "1,a,2.0\r\n2,\"b,c\",0.5\r\n3,,1.25\r\n");
+    }
+
+    #[test]
+    fn copy_rejects_bad_options_and_sources() {
+        let mut catalog = catalog();
+        let path = temp_dir("copy-errors").join("out");
+        assert_eq!(
+            error_message(&mut catalog, &format!("COPY t TO '{}' (FORMAT json, HEADER false)", path.display())),
+            "HEADER false is only supported for CSV and TSV, not json"
+        );
+        assert!(error_message(&mut catalog, &format!("COPY t TO '{}' (FORMAT xml)", path.display())).starts_with("Unknown output format 'xml'"));
+        assert_eq!(error_message(&mut catalog, &format!("COPY missing TO '{}'", path.display())), "Table 'missing' not found in catalog");
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn create_table_as_keeps_rows_in_memory() {
+        let mut catalog = catalog();
+        let statement = parse("CREATE TABLE big AS SELECT id, score * 2 AS double FROM t WHERE id >= 2");
+        assert_eq!(create_table_as(&statement, &mut catalog, &SessionConfig::new()).unwrap(), 2);
+        assert_eq!(rows(&mut catalog, "SELECT * FROM big"), vec![vec![int(2), float(1.0)], vec![int(3), float(2.5)]]);
+
+        assert_eq!(create_table_as(&parse("CREATE TABLE IF NOT EXISTS big AS SELECT id FROM t"), &mut catalog, &SessionConfig::new()).unwrap(), 0);
+        assert_eq!(error_message(&mut catalog, "CREATE TABLE BIG AS SELECT id FROM t"), "Table 'BIG' already exists");
+        query(&mut catalog, "CREATE OR REPLACE TABLE big AS SELECT name FROM t WHERE id = 1").unwrap();
+        assert_eq!(rows(&mut catalog, "SELECT * FROM big"), vec![vec![text("a")]]);
+    }
+
+    #[test]
+    fn create_table_as_with_a_location_writes_and_reads_back_the_file() {
+        let dir = temp_dir("ctas-files");
+        let mut catalog = catalog();
+        let expected = vec![
+            vec![int(1), text("a"), float(2.0)],
+            vec![int(2), text("b,c"), float(0.5)],
+            vec![int(3), ScalarValue::String(None), float(1.25)],
+        ];
+        for (table, file, stored) in [("csv_copy", "copy.csv", ""), ("json_copy", "copy.json", ""), ("text_copy", "copy.data", "STORED AS TEXTFILE"), ("lines_copy", "copy.data2", "STORED AS JSONFILE")] {
+            let path = dir.join(file);
+            query(&mut catalog, &format!("CREATE TABLE {} {} LOCATION '{}' AS SELECT * FROM t", table, stored, path.display())).unwrap();
+            assert!(path.exists(), "{} was not written", file);
+            assert_eq!(rows(&mut catalog, &format!("SELECT * FROM {}", table)), expected, "{}", table);
+        }
+        assert!(fs::read_to_string(dir.join("copy.json")).unwrap().starts_with("{\"id\":1,"));
+        assert!(fs::read_to_string(dir.join("copy.data")).unwrap().starts_with("id,name,score\r\n"));
+    }
+
+    #[test]
+    fn empty_strings_and_nulls_survive_a_csv_file() {
+        let dir = temp_dir("ctas-empty-strings");
+        let path = dir.join("e.csv");
+        let mut catalog = Catalog::new();
+        let sql = format!("CREATE TABLE e LOCATION '{}' AS SELECT * FROM (VALUES ('', 1), (NULL, 2), ('x', 3)) AS v (s, n)", path.display());
+        query(&mut catalog, &sql).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "s,n\r\n\"\",1\r\n,2\r\nx,3\r\n");
+        let expected = vec![vec![text(""), int(1)], vec![ScalarValue::String(None), int(2)], vec![text("x"), int(3)]];
+        assert_eq!(rows(&mut catalog, "SELECT * FROM e"), expected);
+
+        // The file reads back the same way with an inferred schema.
+        rows(&mut catalog, &format!("CREATE EXTERNAL TABLE inferred STORED AS CSV LOCATION '{}'", path.display()));
+        assert_eq!(rows(&mut catalog, "SELECT * FROM inferred"), expected);
+    }
+
+    #[test]
+    fn files_can_be_replaced_by_a_query_that_reads_them() {
+        let dir = temp_dir("write-self");
+        let path = dir.join("c.csv");
+        let mut catalog = catalog();
+        query(&mut catalog, &format!("CREATE TABLE c LOCATION '{}' AS SELECT id, score FROM t", path.display())).unwrap();
+        query(&mut catalog, &format!("CREATE OR REPLACE TABLE c LOCATION '{}' AS SELECT id, score FROM c WHERE id > 1", path.display())).unwrap();
+        assert_eq!(rows(&mut catalog, "SELECT * FROM c"), vec![vec![int(2), float(0.5)], vec![int(3), float(1.25)]]);
+
+        query(&mut catalog, &format!("COPY (SELECT id, score * 2 AS score FROM c) TO '{}'", path.display())).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "id,score\r\n2,1.0\r\n3,2.5\r\n");
+        let names: Vec<String> = fs::read_dir(&dir).unwrap().map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned()).collect();
+        assert_eq!(names, ["c.csv"]);
+    }
+
+    #[test]
+    fn create_table_as_rejects_what_it_cannot_write() {
+        let mut catalog = catalog();
+        assert_eq!(error_message(&mut catalog, "CREATE TABLE c (id INT) AS SELECT id FROM t"), "CREATE TABLE ... AS takes its columns from the query, so it cannot list them");
+        assert_eq!(error_message(&mut catalog, "CREATE TABLE c STORED AS TEXTFILE AS SELECT id FROM t"), "STORED AS needs a LOCATION to write the table to");
+        assert!(error_message(&mut catalog, "CREATE TABLE c STORED AS PARQUET LOCATION 'c.parquet' AS SELECT id FROM t").starts_with("CREATE TABLE ... AS can only write TEXTFILE or JSONFILE"));
+        assert!(!catalog.contains("c"));
+    }
+}
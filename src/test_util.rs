@@ -0,0 +1,73 @@
+//! Helpers shared by the unit tests.
+
+use std::path::PathBuf;
+
+use crate::errors::{LexerError, QueryError};
+use crate::logical_plan::plan::{Catalog, LogicalPlan};
+use crate::optimizer::Optimizer;
+use crate::sql_support::sql::{StatementResult, execute_statement, parse_sql, sql_to_logical_plan};
+use crate::{ScalarValue, Schema, SessionConfig};
+
+/// Runs the statements of `sql` in turn and returns the schema and rows of the
+/// last one, which are empty for statements that return no rows.
+pub fn query(catalog: &mut Catalog, sql: &str) -> Result<(Schema, Vec<Vec<ScalarValue>>), QueryError> {
+    let statements = parse_sql(sql).map_err(|LexerError::InvalidToken { message }| QueryError::ValidationError { message })?;
+    let mut result = (Schema::new(Vec::new()), Vec::new());
+    for statement in &statements {
+        result = match execute_statement(statement, catalog, &SessionConfig::new())? {
+            StatementResult::Rows(plan) => {
+                let mut rows = Vec::new();
+                for batch in plan.execute() {
+                    let batch = batch?;
+                    let height = batch.columns.first().map_or(0, |column| column.values.len());
+                    rows.extend((0..height).map(|row| batch.columns.iter().map(|column| column.values[row].clone()).collect()));
+                }
+                (plan.schema().clone(), rows)
+            },
+            StatementResult::Done(_) => (Schema::new(Vec::new()), Vec::new()),
+        };
+    }
+    Ok(result)
+}
+
+/// The rows `sql` returns, failing the test on any error.
+pub fn rows(catalog: &mut Catalog, sql: &str) -> Vec<Vec<ScalarValue>> {
+    query(catalog, sql).unwrap_or_else(|e| panic!("{} failed: {:?}", sql, e)).1
+}
+
+/// The message of the validation error `sql` fails with.
+pub fn error_message(catalog: &mut Catalog, sql: &str) -> String {
+    match query(catalog, sql) {
+        Err(QueryError::ValidationError { message }) => message,
+        other => panic!("{} did not fail validation: {:?}", sql, other.map(|(_, rows)| rows)),
+    }
+}
+
+/// The optimized logical plan of the query `sql`.
+pub fn optimized_plan(catalog: &Catalog, sql: &str) -> LogicalPlan {
+    let Ok(statements) = parse_sql(sql) else {
+        panic!("{} failed to parse", sql);
+    };
+    let plan = sql_to_logical_plan(&statements[0], catalog).unwrap_or_else(|e| panic!("{} failed to plan: {:?}", sql, e));
+    Optimizer::new().optimize(&plan, catalog).unwrap()
+}
+
+/// An empty directory for test `name` to write files in.
+pub fn temp_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("vektur-test-{}-{}", std::process::id(), name));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).expect("create test directory");
+    dir
+}
+
+pub fn int(value: i32) -> ScalarValue {
+    ScalarValue::Int32(Some(value))
+}
+
+pub fn float(value: f64) -> ScalarValue {
+    ScalarValue::Float64(Some(value))
+}
+
+pub fn text(value: &str) -> ScalarValue {
+    ScalarValue::String(Some(value.to_string()))
+}
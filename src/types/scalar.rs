@@ -1,3 +1,7 @@
+use std::hash::{Hash, Hasher};
+
+use crate::DataType;
+
 #[derive(Debug, Clone)]
 pub enum ScalarValue {
     Int32(Option<i32>),
@@ -6,6 +10,69 @@ pub enum ScalarValue {
     Float64(Option<f64>)
 }
 
+impl ScalarValue {
+    /// Returns a null value of the given type.
+    pub fn null(data_type: DataType) -> Self {
+        match data_type {
+            DataType::Int32 => ScalarValue::Int32(None),
+            DataType::String => ScalarValue::String(None),
+            DataType::Bool => ScalarValue::Bool(None),
+            DataType::Float64 => ScalarValue::Float64(None),
+        }
+    }
+
+    pub fn is_null(&self) -> bool {
+        matches!(
+            self,
+            ScalarValue::Int32(None)
+                | ScalarValue::Float64(None)
+                | ScalarValue::String(None)
+                | ScalarValue::Bool(None)
+        )
+    }
+
+    pub fn data_type(&self) -> DataType {
+        match self {
+            ScalarValue::Int32(_) => DataType::Int32,
+            ScalarValue::String(_) => DataType::String,
+            ScalarValue::Bool(_) => DataType::Bool,
+            ScalarValue::Float64(_) => DataType::Float64,
+        }
+    }
+}
+
+/// Values compare and hash by content so they can be used as grouping and
+/// DISTINCT keys. Floats compare by bit pattern, so NaN equals NaN and -0.0 equals 0.0.
+impl PartialEq for ScalarValue {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (ScalarValue::Int32(l), ScalarValue::Int32(r)) => l == r,
+            (ScalarValue::String(l), ScalarValue::String(r)) => l == r,
+            (ScalarValue::Bool(l), ScalarValue::Bool(r)) => l == r,
+            (ScalarValue::Float64(l), ScalarValue::Float64(r)) => l.map(float_key) == r.map(float_key),
+            _ => false,
+        }
+    }
+}
+
+impl Eq for ScalarValue {}
+
+impl Hash for ScalarValue {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        std::mem::discriminant(self).hash(state);
+        match self {
+            ScalarValue::Int32(v) => v.hash(state),
+            ScalarValue::String(v) => v.hash(state),
+            ScalarValue::Bool(v) => v.hash(state),
+            ScalarValue::Float64(v) => v.map(float_key).hash(state),
+        }
+    }
+}
+
+fn float_key(value: f64) -> u64 {
+    if value == 0.0 { 0 } else { value.to_bits() }
+}
+
 #[derive(Debug, Clone)]
 pub struct ColumnVector {
     pub values: Vec<ScalarValue>,
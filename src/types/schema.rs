@@ -12,7 +12,7 @@ pub struct Schema {
     pub fields: Vec<Field>,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct RecordBatch {
     pub schema: Schema,
     pub columns: Vec<ColumnVector>,
@@ -31,6 +31,19 @@ impl Schema {
             Err(format!("column {} does not exist in the schema", column_name))
         }
     }
+
+    /// Returns the index of the named column.
+    pub fn index_of(&self, column_name: &str) -> Result<usize, String> {
+        self.fields
+            .iter()
+            .position(|f| f.name == column_name)
+            .ok_or_else(|| format!("column {} does not exist in the schema", column_name))
+    }
+
+    /// Returns a schema with only the columns at `indices`, in that order.
+    pub fn project(&self, indices: &[usize]) -> Schema {
+        Schema::new(indices.iter().map(|&i| self.fields[i].clone()).collect())
+    }
 }
 
 impl RecordBatch {
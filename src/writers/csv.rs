@@ -0,0 +1,109 @@
+use std::io::Write;
+
+use crate::{RecordBatch, Schema, errors::QueryError};
+
+use super::{RecordBatchWriter, format_value, row_count, write_error};
+
+/// How fields are separated and protected from the separator.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Dialect {
+    /// RFC 4180: comma-separated, CRLF line endings, and fields that are empty
+    /// or hold a comma, a quote or a line break quoted with quotes doubled.
+    Csv,
+    /// Tab-separated with LF line endings. Tabs, line breaks and backslashes in
+    /// fields are written as `\t`, `\n`, `\r` and `\\`.
+    Tsv,
+}
+
+/// Writes rows as CSV or TSV with a header line. NULL is an empty field, and in
+/// CSV an empty string is a quoted empty field (`""`).
+pub struct CsvWriter<W: Write> {
+    out: W,
+    dialect: Dialect,
+    header: Vec<String>,
+    wrote_header: bool,
+}
+
+impl<W: Write> CsvWriter<W> {
+    pub fn new(out: W, schema: &Schema) -> Self {
+        Self::with_dialect(out, schema, Dialect::Csv)
+    }
+
+    pub fn tsv(out: W, schema: &Schema) -> Self {
+        Self::with_dialect(out, schema, Dialect::Tsv)
+    }
+
+    /// Leaves out the header line.
+    pub fn without_header(self) -> Self {
+        Self { wrote_header: true, ..self }
+    }
+
+    fn with_dialect(out: W, schema: &Schema, dialect: Dialect) -> Self {
+        let header = schema.fields.iter().map(|field| field.name.clone()).collect();
+        Self { out, dialect, header, wrote_header: false }
+    }
+
+    fn write_header(&mut self) -> Result<(), QueryError> {
+        if !self.wrote_header {
+            self.wrote_header = true;
+            let header = self.header.clone();
+            self.write_record(header.iter().map(|name| Some(name.as_str())))?;
+        }
+        Ok(())
+    }
+
+    fn write_record<'a>(&mut self, fields: impl Iterator<Item = Option<&'a str>>) -> Result<(), QueryError> {
+        let mut line = String::new();
+        for (i, field) in fields.enumerate() {
+            if i > 0 {
+                line.push(match self.dialect {
+                    Dialect::Csv => ',',
+                    Dialect::Tsv => '\t',
+                });
+            }
+            let Some(field) = field else {
+                continue;
+            };
+            match self.dialect {
+                Dialect::Csv if field.is_empty() || field.contains([',', '"', '\r', '\n']) => {
+                    line.push('"');
+                    line.push_str(&field.replace('"', "\"\""));
+                    line.push('"');
+                },
+                Dialect::Csv => line.push_str(field),
+                Dialect::Tsv => {
+                    for c in field.chars() {
+                        match c {
+                            '\t' => line.push_str("\\t"),
+                            '\n' => line.push_str("\\n"),
+                            '\r' => line.push_str("\\r"),
+                            '\\' => line.push_str("\\\\"),
+                            c => line.push(c),
+                        }
+                    }
+                },
+            }
+        }
+        line.push_str(match self.dialect {
+            Dialect::Csv => "\r\n",
+            Dialect::Tsv => "\n",
+        });
+        self.out.write_all(line.as_bytes()).map_err(write_error)
+    }
+}
+
+impl<W: Write> RecordBatchWriter for CsvWriter<W> {
+    fn write(&mut self, batch: &RecordBatch) -> Result<(), QueryError> {
+        self.write_header()?;
+        for row in 0..row_count(batch) {
+            let values: Vec<Option<String>> = batch.columns.iter().map(|column| format_value(&column.values[row])).collect();
+            self.write_record(values.iter().map(Option::as_deref))?;
+        }
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<(), QueryError> {
+        self.write_header()?;
+        self.out.flush().map_err(write_error)
+    }
+}
@@ -0,0 +1,73 @@
+use std::io::Write;
+
+use serde_json::{Map, Number, Value};
+
+use crate::{RecordBatch, ScalarValue, Schema, errors::QueryError};
+
+use super::{RecordBatchWriter, row_count, write_error};
+
+/// Writes each row as a JSON object keyed by column name, either as one JSON
+/// array or as newline-delimited JSON with one object per line. NULL and
+/// floats JSON cannot hold, such as NaN, are written as `null`.
+pub struct JsonWriter<W: Write> {
+    out: W,
+    names: Vec<String>,
+    /// Whether the rows are wrapped in an array rather than written one per line.
+    array: bool,
+    rows_written: usize,
+}
+
+impl<W: Write> JsonWriter<W> {
+    pub fn array(out: W, schema: &Schema) -> Self {
+        Self::new(out, schema, true)
+    }
+
+    pub fn lines(out: W, schema: &Schema) -> Self {
+        Self::new(out, schema, false)
+    }
+
+    fn new(out: W, schema: &Schema, array: bool) -> Self {
+        let names = schema.fields.iter().map(|field| field.name.clone()).collect();
+        Self { out, names, array, rows_written: 0 }
+    }
+}
+
+fn to_json(value: &ScalarValue) -> Value {
+    match value {
+        ScalarValue::Int32(Some(v)) => Value::from(*v),
+        ScalarValue::Float64(Some(v)) => Number::from_f64(*v).map_or(Value::Null, Value::Number),
+        ScalarValue::Bool(Some(v)) => Value::Bool(*v),
+        ScalarValue::String(Some(v)) => Value::String(v.clone()),
+        _ => Value::Null,
+    }
+}
+
+impl<W: Write> RecordBatchWriter for JsonWriter<W> {
+    fn write(&mut self, batch: &RecordBatch) -> Result<(), QueryError> {
+        for row in 0..row_count(batch) {
+            let object: Map<String, Value> = self
+                .names
+                .iter()
+                .zip(&batch.columns)
+                .map(|(name, column)| (name.clone(), to_json(&column.values[row])))
+                .collect();
+            let separator = match (self.array, self.rows_written) {
+                (true, 0) => "[\n",
+                (true, _) => ",\n",
+                (false, _) => "",
+            };
+            let line_end = if self.array { "" } else { "\n" };
+            write!(self.out, "{}{}{}", separator, Value::Object(object), line_end).map_err(write_error)?;
+            self.rows_written += 1;
+        }
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<(), QueryError> {
+        if self.array {
+            let end = if self.rows_written == 0 { "[]\n" } else { "\n]\n" };
+            self.out.write_all(end.as_bytes()).map_err(write_error)?;
+        }
+        self.out.flush().map_err(write_error)
+    }
+}
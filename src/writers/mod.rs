@@ -0,0 +1,251 @@
+use std::{fmt, io::Write, str::FromStr};
+
+use crate::{RecordBatch, ScalarValue, Schema, errors::QueryError};
+
+pub mod csv;
+pub mod json;
+pub mod table;
+
+use csv::CsvWriter;
+use json::JsonWriter;
+use table::TableWriter;
+
+/// Writes query results batch by batch in some output format.
+pub trait RecordBatchWriter {
+    /// Writes the rows of `batch`, which has the schema the writer was created with.
+    fn write(&mut self, batch: &RecordBatch) -> Result<(), QueryError>;
+
+    /// Ends the output and flushes it. Writers that need every row before they
+    /// can lay out the first one, like the aligned tables, write everything here.
+    fn finish(&mut self) -> Result<(), QueryError>;
+}
+
+/// The output formats there is a `RecordBatchWriter` for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputFormat {
+    Csv,
+    Tsv,
+    /// One JSON array of objects.
+    Json,
+    /// One JSON object per line.
+    NdJson,
+    Markdown,
+    /// A table drawn with box-drawing characters.
+    Table,
+}
+
+impl OutputFormat {
+    /// Creates a writer for this format that writes rows of `schema` to `out`.
+    pub fn writer<W: Write + 'static>(self, out: W, schema: &Schema) -> Box<dyn RecordBatchWriter> {
+        match self {
+            OutputFormat::Csv => Box::new(CsvWriter::new(out, schema)),
+            OutputFormat::Tsv => Box::new(CsvWriter::tsv(out, schema)),
+            OutputFormat::Json => Box::new(JsonWriter::array(out, schema)),
+            OutputFormat::NdJson => Box::new(JsonWriter::lines(out, schema)),
+            OutputFormat::Markdown => Box::new(TableWriter::markdown(out, schema)),
+            OutputFormat::Table => Box::new(TableWriter::boxed(out, schema)),
+        }
+    }
+}
+
+impl FromStr for OutputFormat {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name.to_lowercase().as_str() {
+            "csv" => Ok(OutputFormat::Csv),
+            "tsv" => Ok(OutputFormat::Tsv),
+            "json" => Ok(OutputFormat::Json),
+            "ndjson" | "jsonl" => Ok(OutputFormat::NdJson),
+            "markdown" | "md" => Ok(OutputFormat::Markdown),
+            "table" => Ok(OutputFormat::Table),
+            _ => Err(format!("Unknown output format '{}': expected csv, tsv, json, ndjson, markdown or table", name)),
+        }
+    }
+}
+
+impl fmt::Display for OutputFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            OutputFormat::Csv => "csv",
+            OutputFormat::Tsv => "tsv",
+            OutputFormat::Json => "json",
+            OutputFormat::NdJson => "ndjson",
+            OutputFormat::Markdown => "markdown",
+            OutputFormat::Table => "table",
+        })
+    }
+}
+
+/// The text of a value as the writers show it, or `None` for NULL. Whole
+/// floats keep a decimal point, so `2.0` is not mistaken for an integer.
+pub fn format_value(value: &ScalarValue) -> Option<String> {
+    match value {
+        ScalarValue::Int32(v) => v.map(|v| v.to_string()),
+        ScalarValue::Float64(v) => v.map(|v| match v.is_finite() && v.fract() == 0.0 && v.abs() < 1e15 {
+            true => format!("{:.1}", v),
+            false => v.to_string(),
+        }),
+        ScalarValue::Bool(v) => v.map(|v| v.to_string()),
+        ScalarValue::String(v) => v.clone(),
+    }
+}
+
+fn row_count(batch: &RecordBatch) -> usize {
+    batch.columns.first().map_or(0, |column| column.values.len())
+}
+
+fn write_error(e: std::io::Error) -> QueryError {
+    QueryError::DataSourceError {
+        message: format!("Failed to write output: {}", e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{ColumnVector, DataType, Field};
+
+    use super::*;
+
+    /// Two rows whose text needs quoting or escaping in most formats.
+    fn batch() -> RecordBatch {
+        let field = |name: &str, field_type| Field { name: name.to_string(), field_type, is_nullable: true };
+        let schema = Schema::new(vec![
+            field("id", DataType::Int32),
+            field("name", DataType::String),
+            field("score", DataType::Float64),
+            field("ok", DataType::Bool),
+        ]);
+        RecordBatch::new(schema, vec![
+            ColumnVector::new(vec![ScalarValue::Int32(Some(1)), ScalarValue::Int32(None)]),
+            ColumnVector::new(vec![ScalarValue::String(Some("a, \"b\"\nc".to_string())), ScalarValue::String(Some("tab\there|x".to_string()))]),
+            ColumnVector::new(vec![ScalarValue::Float64(Some(2.0)), ScalarValue::Float64(Some(0.25))]),
+            ColumnVector::new(vec![ScalarValue::Bool(Some(true)), ScalarValue::Bool(None)]),
+        ])
+        .unwrap()
+    }
+
+    /// What a writer for `format` writes for `batch()`, sent in two halves.
+    fn render(format: OutputFormat) -> String {
+        let batch = batch();
+        let halves: Vec<RecordBatch> = (0..2)
+            .map(|row| RecordBatch::new(batch.schema.clone(), batch.columns.iter().map(|c| ColumnVector::new(vec![c.values[row].clone()])).collect()).unwrap())
+            .collect();
+        let out = SharedBuffer::default();
+        let mut writer = format.writer(out.clone(), &batch.schema);
+        for half in &halves {
+            writer.write(half).unwrap();
+        }
+        writer.finish().unwrap();
+        String::from_utf8(out.0.borrow().clone()).unwrap()
+    }
+
+    /// A buffer the test can read after handing the writer a clone of it.
+    #[derive(Clone, Default)]
+    struct SharedBuffer(std::rc::Rc<std::cell::RefCell<Vec<u8>>>);
+
+    impl Write for SharedBuffer {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.borrow_mut().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn csv_quotes_fields_and_ends_rows_with_crlf() {
+        assert_eq!(render(OutputFormat::Csv), "id,name,score,ok\r\n1,\"a, \"\"b\"\"\nc\",2.0,true\r\n,tab\there|x,0.25,\r\n");
+    }
+
+    #[test]
+    fn csv_without_header_writes_only_rows() {
+        let mut out = Vec::new();
+        let mut writer = CsvWriter::new(&mut out, &batch().schema).without_header();
+        writer.write(&batch()).unwrap();
+        writer.finish().unwrap();
+        drop(writer);
+        assert_eq!(String::from_utf8(out).unwrap(), "1,\"a, \"\"b\"\"\nc\",2.0,true\r\n,tab\there|x,0.25,\r\n");
+    }
+
+    #[test]
+    fn tsv_escapes_tabs_and_newlines() {
+        assert_eq!(render(OutputFormat::Tsv), "id\tname\tscore\tok\n1\ta, \"b\"\\nc\t2.0\ttrue\n\ttab\\there|x\t0.25\t\n");
+    }
+
+    #[test]
+    fn json_writes_one_array_across_batches() {
+        let json = render(OutputFormat::Json);
+        assert_eq!(
+            json,
+            "[\n{\"id\":1,\"name\":\"a, \\\"b\\\"\\nc\",\"score\":2.0,\"ok\":true},\n{\"id\":null,\"name\":\"tab\\there|x\",\"score\":0.25,\"ok\":null}\n]\n"
+        );
+        let parsed: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed[0]["name"], "a, \"b\"\nc");
+        assert_eq!(parsed[1]["id"], serde_json::Value::Null);
+    }
+
+    #[test]
+    fn ndjson_writes_one_object_per_line() {
+        let lines = render(OutputFormat::NdJson);
+        assert_eq!(lines.lines().count(), 2);
+        for line in lines.lines() {
+            serde_json::from_str::<serde_json::Value>(line).unwrap();
+        }
+        assert!(lines.starts_with("{\"id\":1,"));
+    }
+
+    #[test]
+    fn json_array_of_no_rows_is_empty() {
+        let out = SharedBuffer::default();
+        let mut writer = OutputFormat::Json.writer(out.clone(), &batch().schema);
+        writer.finish().unwrap();
+        assert_eq!(String::from_utf8(out.0.borrow().clone()).unwrap(), "[]\n");
+    }
+
+    #[test]
+    fn markdown_aligns_columns_and_escapes_pipes() {
+        assert_eq!(
+            render(OutputFormat::Markdown),
+            "|   id | name        | score | ok   |\n\
+             | ---: | ----------- | ----: | ---- |\n\
+             |    1 | a, \"b\"<br>c |   2.0 | true |\n\
+             | NULL | tab\there\\|x |  0.25 | NULL |\n"
+        );
+    }
+
+    #[test]
+    fn table_draws_boxes_around_escaped_values() {
+        assert_eq!(
+            render(OutputFormat::Table),
+            "┌──────┬─────────────┬───────┬──────┐\n\
+             │   id │ name        │ score │ ok   │\n\
+             ├──────┼─────────────┼───────┼──────┤\n\
+             │    1 │ a, \"b\"\\nc   │   2.0 │ true │\n\
+             │ NULL │ tab\\there|x │  0.25 │ NULL │\n\
+             └──────┴─────────────┴───────┴──────┘\n"
+        );
+    }
+
+    #[test]
+    fn output_formats_parse_and_display() {
+        for format in [OutputFormat::Csv, OutputFormat::Tsv, OutputFormat::Json, OutputFormat::NdJson, OutputFormat::Markdown, OutputFormat::Table] {
+            assert_eq!(format.to_string().parse::<OutputFormat>(), Ok(format));
+        }
+        assert_eq!("JSONL".parse::<OutputFormat>(), Ok(OutputFormat::NdJson));
+        assert_eq!("md".parse::<OutputFormat>(), Ok(OutputFormat::Markdown));
+        assert!("xml".parse::<OutputFormat>().unwrap_err().contains("Unknown output format 'xml'"));
+    }
+
+    #[test]
+    fn whole_floats_keep_their_decimal_point() {
+        assert_eq!(format_value(&ScalarValue::Float64(Some(3.0))).as_deref(), Some("3.0"));
+        assert_eq!(format_value(&ScalarValue::Float64(Some(-0.5))).as_deref(), Some("-0.5"));
+        assert_eq!(format_value(&ScalarValue::Float64(Some(1e20))).as_deref(), Some("100000000000000000000"));
+        assert_eq!(format_value(&ScalarValue::Float64(Some(f64::NAN))).as_deref(), Some("NaN"));
+        assert_eq!(format_value(&ScalarValue::Int32(Some(3))).as_deref(), Some("3"));
+        assert_eq!(format_value(&ScalarValue::String(None)), None);
+    }
+}
@@ -0,0 +1,135 @@
+use std::io::Write;
+
+use crate::{DataType, RecordBatch, Schema, errors::QueryError};
+
+use super::{RecordBatchWriter, format_value, row_count, write_error};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum TableStyle {
+    /// A GitHub-flavoured Markdown table.
+    Markdown,
+    /// A table drawn with box-drawing characters.
+    Box,
+}
+
+/// Writes rows as a table whose columns are as wide as their widest value.
+/// Rows are kept until `finish`, so the widths take every batch into account.
+/// Numbers are right-aligned and NULL is shown as `NULL`.
+pub struct TableWriter<W: Write> {
+    out: W,
+    style: TableStyle,
+    names: Vec<String>,
+    right_aligned: Vec<bool>,
+    rows: Vec<Vec<String>>,
+}
+
+impl<W: Write> TableWriter<W> {
+    pub fn markdown(out: W, schema: &Schema) -> Self {
+        Self::new(out, schema, TableStyle::Markdown)
+    }
+
+    pub fn boxed(out: W, schema: &Schema) -> Self {
+        Self::new(out, schema, TableStyle::Box)
+    }
+
+    fn new(out: W, schema: &Schema, style: TableStyle) -> Self {
+        let right_aligned = schema
+            .fields
+            .iter()
+            .map(|field| matches!(field.field_type, DataType::Int32 | DataType::Float64))
+            .collect();
+        let mut writer = Self { out, style, names: Vec::new(), right_aligned, rows: Vec::new() };
+        writer.names = schema.fields.iter().map(|field| writer.cell(&field.name)).collect();
+        writer
+    }
+
+    /// Makes a value safe to put in a cell: line breaks would split the row,
+    /// and in Markdown a `|` would end the cell.
+    fn cell(&self, text: &str) -> String {
+        match self.style {
+            TableStyle::Markdown => text.replace('|', "\\|").replace("\r\n", "<br>").replace(['\r', '\n'], "<br>"),
+            TableStyle::Box => text.replace('\r', "\\r").replace('\n', "\\n").replace('\t', "\\t"),
+        }
+    }
+
+    fn write_row(&mut self, cells: &[String], widths: &[usize]) -> Result<(), QueryError> {
+        let padded: Vec<String> = cells
+            .iter()
+            .zip(widths)
+            .zip(&self.right_aligned)
+            .map(|((cell, &width), &right)| match right {
+                true => format!("{:>width$}", cell, width = width),
+                false => format!("{:<width$}", cell, width = width),
+            })
+            .collect();
+        let (left, middle, right) = match self.style {
+            TableStyle::Markdown => ("| ", " | ", " |"),
+            TableStyle::Box => ("│ ", " │ ", " │"),
+        };
+        writeln!(self.out, "{}{}{}", left, padded.join(middle), right).map_err(write_error)
+    }
+
+    /// Writes a line across the table, such as the border above the header.
+    fn write_rule(&mut self, widths: &[usize], left: &str, middle: &str, right: &str) -> Result<(), QueryError> {
+        let segments: Vec<String> = widths.iter().map(|&width| "─".repeat(width + 2)).collect();
+        writeln!(self.out, "{}{}{}", left, segments.join(middle), right).map_err(write_error)
+    }
+}
+
+fn text_width(text: &str) -> usize {
+    text.chars().count()
+}
+
+impl<W: Write> RecordBatchWriter for TableWriter<W> {
+    fn write(&mut self, batch: &RecordBatch) -> Result<(), QueryError> {
+        for row in 0..row_count(batch) {
+            let cells = batch
+                .columns
+                .iter()
+                .map(|column| self.cell(&format_value(&column.values[row]).unwrap_or_else(|| "NULL".to_string())))
+                .collect();
+            self.rows.push(cells);
+        }
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<(), QueryError> {
+        let mut widths: Vec<usize> = self.names.iter().map(|name| text_width(name)).collect();
+        for row in &self.rows {
+            for (width, cell) in widths.iter_mut().zip(row) {
+                *width = (*width).max(text_width(cell));
+            }
+        }
+        let names = std::mem::take(&mut self.names);
+        let rows = std::mem::take(&mut self.rows);
+        match self.style {
+            TableStyle::Markdown => {
+                // Markdown needs at least three dashes per column.
+                let widths: Vec<usize> = widths.iter().map(|&width| width.max(3)).collect();
+                self.write_row(&names, &widths)?;
+                let rules: Vec<String> = widths
+                    .iter()
+                    .zip(&self.right_aligned)
+                    .map(|(&width, &right)| match right {
+                        true => format!("{}:", "-".repeat(width - 1)),
+                        false => "-".repeat(width),
+                    })
+                    .collect();
+                writeln!(self.out, "| {} |", rules.join(" | ")).map_err(write_error)?;
+                for row in &rows {
+                    self.write_row(row, &widths)?;
+                }
+            },
+            TableStyle::Box => {
+                self.write_rule(&widths, "┌", "┬", "┐")?;
+                self.write_row(&names, &widths)?;
+                self.write_rule(&widths, "├", "┼", "┤")?;
+                for row in &rows {
+                    self.write_row(row, &widths)?;
+                }
+                self.write_rule(&widths, "└", "┴", "┘")?;
+            },
+        }
+        self.out.flush().map_err(write_error)
+    }
+}